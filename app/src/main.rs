@@ -1,9 +1,10 @@
 use std::collections::HashMap;
+use std::collections::HashSet;
 use std::collections::BinaryHeap;
 use std::ffi::OsStr;
 use std::fs::File;
 use std::io::{BufReader, BufWriter, ErrorKind, Read as _, Write};
-use std::sync::mpsc;
+use std::sync::{Arc, Mutex, mpsc};
 use std::thread;
 use std::{
     fs,
@@ -20,24 +21,57 @@ use glob::glob;
 //     deflate::Mgzip,
 //     par::compress::{ParCompress, ParCompressBuilder},
 // };
-use coordinate_transformer::{EPSG_WGS84_GEOCENTRIC, EPSG_WGS84_GEOGRAPHIC_3D, PointTransformer};
+use coordinate_transformer::{
+    EPSG_WGS84_GEOCENTRIC, EPSG_WGS84_GEOGRAPHIC_3D, LocalFrame, LocalFrameTransformer,
+    PointTransformer, ProjError, geodetic_to_ecef,
+};
 use log::LevelFilter;
-use pcd_exporter::gltf::GlbOptions;
-use pcd_parser::reader::PointReader;
+use pcd_exporter::gltf::{ColorOptions, ColorSpace, GlbOptions, MeshoptOptions, QuantizeOptions};
+use pcd_parser::raster::geotiff::GeoTiff;
 use pcd_parser::reader::csv::CsvPointReader;
 use pcd_parser::reader::las::LasPointReader;
-use rayon::iter::{IntoParallelIterator as _, IntoParallelRefIterator as _, ParallelIterator as _};
-use tempfile::tempdir;
+use pcd_parser::reader::{InputExtent, PointReader};
+use rayon::iter::{
+    IndexedParallelIterator as _, IntoParallelIterator as _, IntoParallelRefIterator as _,
+    ParallelIterator as _,
+};
+use tempfile::{TempDir, tempdir};
 use tinymvt::tileid::hilbert;
 
-use pcd_core::pointcloud::point::{Point, PointCloud};
-use pcd_exporter::tiling;
+use pcd_core::pointcloud::color_balance::{
+    ColorCorrection, ColorOverlap, ColorStats, balance_colors,
+};
+use pcd_core::pointcloud::colorize::{
+    ColorRamp, Colorization, ColorizeMode, DEFAULT_INTENSITY_PERCENTILES, percentile_range,
+};
+use pcd_core::pointcloud::normal::{
+    DEFAULT_NORMAL_NEIGHBOURS, NormalOrientation, estimate_normals,
+};
+use pcd_core::pointcloud::point::{AvailableAttributes, Color, Point, PointAttributes, PointCloud};
+use pcd_core::pointcloud::spacing::{median, nearest_neighbour_distances};
 use pcd_exporter::{
+    attributes::PointAttribute,
     cesiumtiles::make_tile_content,
-    tiling::{TileContent, TileTree, geometric_error},
+    draco::DracoOptions,
+    features::FeatureIdSource,
+    tiling::{
+        ExternalSplit, Geographic, ROOT_TILESET_PATH, Subdivision, TileContent, TileTree, TileZXYH,
+        TilingScheme, TilingSchemeKind,
+        adaptive::{self, PointBudget},
+        classification::ClassificationGroup,
+        error_model::{DEFAULT_VOXEL_FACTOR, GeometricErrorModel},
+        implicit::{IMPLICIT_ROOT_ZOOM, ImplicitTiling, check_implicit_tile},
+        metadata::{TilesetMetadata, classification_groups, metadata_schema},
+        projected::ProjectedGrid,
+        s2::S2,
+        volume::{BoundingVolumeKind, EcefVolume},
+        web_mercator::WebMercator,
+    },
 };
 use pcd_parser::parser::{Extension, get_extension};
 
+use cesiumtiles::tileset::Refine;
+
 #[derive(Parser, Debug, Clone)]
 #[command(
     name = "Point Tiler",
@@ -52,8 +86,8 @@ struct Cli {
     #[arg(short, long, required = true, value_name = "DIR")]
     output: String,
 
-    #[arg(long, required = true)]
-    input_epsg: u16,
+    #[arg(long, required_unless_present = "local_origin")]
+    input_epsg: Option<u16>,
 
     #[arg(long, required = true)]
     output_epsg: u16,
@@ -64,6 +98,12 @@ struct Cli {
     #[arg(long, default_value_t = 18)]
     max: u8,
 
+    #[arg(long, conflicts_with_all = ["min", "max"])]
+    auto_zoom: bool,
+
+    #[arg(long, value_name = "Z")]
+    overview_zoom: Option<u8>,
+
     #[arg(long, default_value_t = 4 * 1024)]
     max_memory_mb: usize,
 
@@ -73,19 +113,457 @@ struct Cli {
     #[arg(long)]
     quantize: bool,
 
+    #[arg(
+        long,
+        value_name = "BITS",
+        default_value_t = QuantizeOptions::default().bits,
+        value_parser = parse_quantize_bits,
+        requires = "quantize"
+    )]
+    quantize_bits: u8,
+
+    #[arg(long, requires = "quantize")]
+    quantize_signed: bool,
+
+    #[arg(long, value_name = "MM", requires = "quantize")]
+    quantize_tolerance_mm: Option<f64>,
+
     #[arg(long)]
     gzip_compress: bool,
 
     #[arg(long)]
     meshopt: bool,
 
+    #[arg(long, requires = "meshopt")]
+    meshopt_filters: bool,
+
+    #[arg(long, conflicts_with_all = ["meshopt", "quantize"])]
+    draco: bool,
+
+    #[arg(
+        long,
+        value_name = "BITS",
+        default_value_t = DracoOptions::default().position_bits,
+        value_parser = clap::value_parser!(u8).range(1..=30),
+        requires = "draco"
+    )]
+    draco_position_bits: u8,
+
+    #[arg(
+        long,
+        value_name = "BITS",
+        default_value_t = DracoOptions::default().color_bits,
+        value_parser = clap::value_parser!(u8).range(1..=30),
+        requires = "draco"
+    )]
+    draco_color_bits: u8,
+
+    #[arg(
+        long,
+        value_name = "BITS",
+        default_value_t = ColorOptions::default().bits,
+        value_parser = parse_color_bits
+    )]
+    color_bits: u8,
+
+    #[arg(long, default_value = "linear")]
+    color_space: ColorSpace,
+
+    #[arg(long, value_name = "ALPHA", value_parser = parse_color_alpha, conflicts_with = "draco")]
+    color_alpha: Option<f32>,
+
+    #[arg(long, value_name = "MODE")]
+    colorize: Option<ColorizeMode>,
+
+    #[arg(long, value_name = "GEOTIFF", conflicts_with = "local_origin")]
+    orthophoto: Vec<String>,
+
+    #[arg(long, value_name = "EPSG", requires = "orthophoto")]
+    orthophoto_epsg: Option<u16>,
+
+    #[arg(long, requires = "orthophoto")]
+    orthophoto_overwrite: bool,
+
+    #[arg(long, conflicts_with = "colorize")]
+    color_balance: bool,
+
+    #[arg(long, default_value = "viridis", requires = "colorize")]
+    color_ramp: ColorRamp,
+
+    #[arg(
+        long,
+        value_name = "MIN,MAX",
+        value_parser = parse_range,
+        allow_hyphen_values = true,
+        requires = "colorize"
+    )]
+    elevation_range: Option<[f64; 2]>,
+
+    #[arg(
+        long,
+        value_name = "LOW,HIGH",
+        value_parser = parse_percentiles,
+        requires = "colorize"
+    )]
+    intensity_percentiles: Option<[f64; 2]>,
+
+    #[arg(long)]
+    normals: bool,
+
+    #[arg(long, value_name = "K", default_value_t = DEFAULT_NORMAL_NEIGHBOURS, requires = "normals")]
+    normal_neighbours: usize,
+
+    #[arg(
+        long,
+        value_name = "LNG,LAT,HEIGHT",
+        value_parser = parse_local_origin,
+        allow_hyphen_values = true,
+        requires = "normals"
+    )]
+    normal_viewpoint: Option<[f64; 3]>,
+
+    #[arg(long, value_delimiter = ',', value_name = "ATTRIBUTES")]
+    attributes: Vec<PointAttribute>,
+
+    #[arg(long, value_name = "SOURCE")]
+    feature_ids: Option<FeatureIdSource>,
+
     #[arg(long)]
     disable_decimation: bool,
+
+    #[arg(long, conflicts_with = "disable_decimation")]
+    additive: bool,
+
+    #[arg(long, default_value_t = DEFAULT_VOXEL_FACTOR)]
+    voxel_factor: f64,
+
+    #[arg(long, default_value_t = 1.0)]
+    geometric_error_multiplier: f64,
+
+    #[arg(long, value_name = "Z=ERROR", value_parser = parse_geometric_error_override)]
+    geometric_error_override: Vec<(u8, f64)>,
+
+    #[arg(long, conflicts_with = "implicit")]
+    data_driven_error: bool,
+
+    #[arg(long)]
+    octree: bool,
+
+    #[arg(long, default_value = "geographic")]
+    tiling_scheme: TilingSchemeKind,
+
+    #[arg(
+        long,
+        value_name = "EPSG",
+        required_if_eq("tiling_scheme", "projected")
+    )]
+    grid_epsg: Option<u16>,
+
+    #[arg(long, value_name = "POINTS")]
+    point_budget: Option<usize>,
+
+    #[arg(long)]
+    implicit: bool,
+
+    #[arg(long, default_value = "region", conflicts_with = "implicit")]
+    bounding_volume: BoundingVolumeKind,
+
+    #[arg(long, default_value_t = 6, requires = "implicit")]
+    subtree_levels: u32,
+
+    #[arg(long, conflicts_with = "implicit")]
+    external_tilesets: bool,
+
+    #[arg(long, value_name = "N", requires = "external_tilesets")]
+    external_tileset_levels: Option<u8>,
+
+    #[arg(long, conflicts_with = "implicit")]
+    classification_groups: bool,
+
+    #[arg(
+        long,
+        value_name = "LNG,LAT,HEIGHT",
+        value_parser = parse_local_origin,
+        allow_hyphen_values = true,
+        conflicts_with = "input_epsg"
+    )]
+    local_origin: Option<[f64; 3]>,
+
+    #[arg(long, default_value_t = 0.0, requires = "local_origin")]
+    local_heading: f64,
+
+    #[arg(long, default_value_t = 1.0, requires = "local_origin")]
+    local_scale: f64,
+}
+
+fn parse_local_origin(value: &str) -> Result<[f64; 3], String> {
+    let values = value
+        .split(',')
+        .map(|v| v.trim().parse::<f64>().map_err(|e| format!("{v:?}: {e}")))
+        .collect::<Result<Vec<_>, _>>()?;
+    values
+        .try_into()
+        .map_err(|_| "expected LNG,LAT,HEIGHT".to_string())
+}
+
+fn parse_range(value: &str) -> Result<[f64; 2], String> {
+    let values = value
+        .split(',')
+        .map(|v| v.trim().parse::<f64>().map_err(|e| format!("{v:?}: {e}")))
+        .collect::<Result<Vec<_>, _>>()?;
+    match values[..] {
+        [min, max] if min < max => Ok([min, max]),
+        [_, _] => Err("MIN must be lower than MAX".to_string()),
+        _ => Err("expected MIN,MAX".to_string()),
+    }
+}
+
+fn parse_percentiles(value: &str) -> Result<[f64; 2], String> {
+    let [low, high] =
+        parse_range(value).map_err(|e| e.replace("MIN", "LOW").replace("MAX", "HIGH"))?;
+    if low >= 0.0 && high <= 100.0 {
+        Ok([low, high])
+    } else {
+        Err(format!("{value:?}: expected percentiles from 0 to 100"))
+    }
+}
+
+fn parse_quantize_bits(value: &str) -> Result<u8, String> {
+    match value.trim() {
+        "8" => Ok(8),
+        "10" => Ok(10),
+        "16" => Ok(16),
+        _ => Err(format!("{value:?}: expected 8, 10 or 16")),
+    }
+}
+
+fn parse_color_bits(value: &str) -> Result<u8, String> {
+    match value.trim() {
+        "8" => Ok(8),
+        "16" => Ok(16),
+        _ => Err(format!("{value:?}: expected 8 or 16")),
+    }
+}
+
+fn parse_color_alpha(value: &str) -> Result<f32, String> {
+    let alpha = value
+        .trim()
+        .parse::<f32>()
+        .map_err(|e| format!("{value:?}: {e}"))?;
+    if (0.0..=1.0).contains(&alpha) {
+        Ok(alpha)
+    } else {
+        Err(format!("{value:?}: expected a value from 0 to 1"))
+    }
+}
+
+fn parse_geometric_error_override(value: &str) -> Result<(u8, f64), String> {
+    let (z, error) = value
+        .split_once('=')
+        .ok_or_else(|| "expected Z=ERROR".to_string())?;
+    let z = z.trim().parse::<u8>().map_err(|e| format!("{z:?}: {e}"))?;
+    let error = error
+        .trim()
+        .parse::<f64>()
+        .map_err(|e| format!("{error:?}: {e}"))?;
+    Ok((z, error))
+}
+
+impl Cli {
+    fn quantize(&self) -> Option<QuantizeOptions> {
+        self.quantize.then_some(QuantizeOptions {
+            bits: self.quantize_bits,
+            signed: self.quantize_signed,
+            tolerance_mm: self.quantize_tolerance_mm,
+        })
+    }
+
+    fn color(&self) -> ColorOptions {
+        ColorOptions {
+            bits: self.color_bits,
+            space: self.color_space,
+            alpha: self.color_alpha,
+        }
+    }
+
+    fn meshopt(&self) -> Option<MeshoptOptions> {
+        self.meshopt.then_some(MeshoptOptions {
+            filters: self.meshopt_filters,
+        })
+    }
+
+    fn draco(&self) -> Option<DracoOptions> {
+        self.draco.then_some(DracoOptions {
+            position_bits: self.draco_position_bits,
+            color_bits: self.draco_color_bits,
+        })
+    }
+
+    fn subdivision(&self) -> Subdivision {
+        if self.octree {
+            Subdivision::Octree
+        } else {
+            Subdivision::Quadtree
+        }
+    }
+
+    fn tiling_scheme(&self) -> Box<dyn TilingScheme> {
+        match self.tiling_scheme {
+            TilingSchemeKind::Geographic => Box::new(Geographic),
+            TilingSchemeKind::WebMercator => Box::new(WebMercator),
+            TilingSchemeKind::Projected => Box::new(
+                self.grid_epsg
+                    .and_then(ProjectedGrid::japan_map_sheets)
+                    .expect("grid EPSG is validated on startup"),
+            ),
+            TilingSchemeKind::S2 => Box::new(S2),
+        }
+    }
+
+    /// Lowest zoom level with content: the top overview level if any, otherwise `--min`
+    fn top_zoom(&self) -> u8 {
+        self.overview_zoom.unwrap_or(self.min)
+    }
+
+    fn refine(&self) -> Refine {
+        if self.additive {
+            Refine::Add
+        } else {
+            Refine::Replace
+        }
+    }
+
+    fn geometric_error_model(&self) -> GeometricErrorModel {
+        GeometricErrorModel {
+            multiplier: self.geometric_error_multiplier,
+            overrides: self.geometric_error_override.iter().copied().collect(),
+            data_driven: self.data_driven_error,
+            voxel_factor: self.voxel_factor,
+        }
+    }
+
+    fn point_budget(&self) -> Option<PointBudget> {
+        self.point_budget.map(PointBudget::new)
+    }
+
+    /// Deepest zoom level with content: `--max`, or below it where tiles are split to fit
+    /// into `--point-budget`
+    fn deepest_zoom(&self) -> u8 {
+        self.point_budget()
+            .map_or(self.max, |budget| budget.deepest_zoom(self.max))
+    }
+
+    fn leaf_tiling<'a>(&self, scheme: &'a dyn TilingScheme) -> LeafTiling<'a> {
+        LeafTiling {
+            scheme,
+            subdivision: self.subdivision(),
+            point_budget: self.point_budget(),
+            disable_decimation: self.disable_decimation,
+            voxel_factor: self.voxel_factor,
+            normals: self.normals.then(|| LeafNormals {
+                neighbours: self.normal_neighbours,
+                orientation: self.normal_orientation(),
+            }),
+        }
+    }
+
+    /// Side the estimated normals face, in ECEF: toward `--normal-viewpoint`, or upward
+    fn normal_orientation(&self) -> NormalOrientation {
+        match self.normal_viewpoint {
+            Some([lng, lat, height]) => {
+                NormalOrientation::Toward(geodetic_to_ecef(lng, lat, height))
+            }
+            None => NormalOrientation::AwayFrom([0.0; 3]),
+        }
+    }
+
+    fn implicit_tiling(&self) -> Option<ImplicitTiling> {
+        self.implicit.then(|| ImplicitTiling {
+            refine: self.refine(),
+            error_model: self.geometric_error_model(),
+            ..ImplicitTiling::new(self.subdivision(), self.subtree_levels)
+        })
+    }
+
+    /// External tilesets start at the shard zoom level `--min`
+    fn external_split(&self) -> Option<ExternalSplit> {
+        self.external_tilesets.then(|| ExternalSplit {
+            subdivision: self.subdivision(),
+            min_zoom: self.min,
+            interval: self.external_tileset_levels,
+        })
+    }
+
+    fn local_frame(&self) -> Option<LocalFrame> {
+        self.local_origin.map(|origin| LocalFrame {
+            origin_lng: origin[0],
+            origin_lat: origin[1],
+            origin_height: origin[2],
+            heading: self.local_heading,
+            scale: self.local_scale,
+        })
+    }
+
+    /// Input CRS as written to the tileset metadata
+    fn input_crs(&self) -> String {
+        match (self.local_origin, self.input_epsg) {
+            (Some([lng, lat, height]), _) => format!("local frame at {lng}, {lat}, {height}"),
+            (None, Some(epsg)) => format!("EPSG:{epsg}"),
+            (None, None) => unreachable!("clap requires --input-epsg or --local-origin"),
+        }
+    }
+}
+
+/// Transforms input coordinates into the output CRS, either via PROJ or by placing a local
+/// site frame on the globe.
+enum InputTransformer {
+    Epsg(PointTransformer),
+    Local(LocalFrameTransformer),
+}
+
+impl InputTransformer {
+    fn new(args: &Cli) -> Result<Self, ProjError> {
+        match (args.local_frame(), args.input_epsg) {
+            (Some(frame), _) => Ok(Self::Local(LocalFrameTransformer::new(
+                frame,
+                args.output_epsg,
+                None,
+            )?)),
+            (None, Some(epsg_in)) => Ok(Self::Epsg(PointTransformer::new(
+                epsg_in,
+                args.output_epsg,
+                None,
+            )?)),
+            (None, None) => unreachable!("clap requires --input-epsg or --local-origin"),
+        }
+    }
+
+    fn transform_points_in_place(&mut self, points: &mut [Point]) -> Result<(), ProjError> {
+        match self {
+            Self::Epsg(transformer) => transformer.transform_points_in_place(points),
+            Self::Local(transformer) => transformer.transform_points_in_place(points),
+        }
+    }
 }
 
 const IN_MEMORY_WORKFLOW_MULTIPLIER: u64 = 5;
 
-#[derive(Debug, Clone, Copy, Decode, Encode)]
+/// Deepest zoom level `--auto-zoom` may choose
+const MAX_AUTO_ZOOM: u8 = 24;
+
+/// Runs of consecutive points sampled across each input for `--color-balance`, `--auto-zoom`
+/// and the stretch of `--colorize intensity`, and their length
+const SAMPLE_RUNS: u64 = 100;
+const SAMPLE_RUN_LENGTH: u64 = 1_000;
+
+/// Sampled points each of two inputs needs within their overlap to compare their colors
+const MIN_OVERLAP_SAMPLE: u64 = 100;
+
+/// Share of `--max-memory-mb`, one in this many bytes, that caches decoded orthophoto strips and
+/// tiles
+const ORTHOPHOTO_CACHE_SHARE: usize = 8;
+
+#[derive(Debug, Clone, Copy, Default, Decode, Encode)]
 struct CompactPoint {
     x: f64,
     y: f64,
@@ -93,12 +571,60 @@ struct CompactPoint {
     r: u16,
     g: u16,
     b: u16,
+    /// ASPRS class, or 0 (never classified) if unknown
+    classification: u8,
+    intensity: u16,
+    return_number: u8,
+    scan_angle: f32,
+    point_source_id: u16,
+    gps_time: f64,
+    /// Index of the input file
+    source_file: u32,
+    /// Index of the point within its input file
+    point_index: u64,
+    normal: [f32; 3],
+    /// Flags of the optional attributes the point has, so that missing ones stay missing
+    present: u8,
 }
 
-const RUN_RECORD_BYTES: usize = 8 + (8 * 3) + (2 * 3);
+const RUN_RECORD_BYTES: usize =
+    8 + 4 + (8 * 3) + (2 * 3) + 1 + 2 + 1 + 4 + 2 + 8 + 4 + 8 + (4 * 3) + 1;
+
+impl CompactPoint {
+    const HAS_CLASSIFICATION: u8 = 1 << 0;
+    const HAS_INTENSITY: u8 = 1 << 1;
+    const HAS_RETURN_NUMBER: u8 = 1 << 2;
+    const HAS_SCAN_ANGLE: u8 = 1 << 3;
+    const HAS_POINT_SOURCE_ID: u8 = 1 << 4;
+    const HAS_GPS_TIME: u8 = 1 << 5;
+    const HAS_NORMAL: u8 = 1 << 6;
+
+    fn has(&self, flag: u8) -> bool {
+        self.present & flag != 0
+    }
+}
 
 impl From<Point> for CompactPoint {
     fn from(point: Point) -> Self {
+        let attributes = &point.attributes;
+        let present = [
+            (
+                attributes.classification.is_some(),
+                Self::HAS_CLASSIFICATION,
+            ),
+            (attributes.intensity.is_some(), Self::HAS_INTENSITY),
+            (attributes.return_number.is_some(), Self::HAS_RETURN_NUMBER),
+            (attributes.scan_angle.is_some(), Self::HAS_SCAN_ANGLE),
+            (
+                attributes.point_source_id.is_some(),
+                Self::HAS_POINT_SOURCE_ID,
+            ),
+            (attributes.gps_time.is_some(), Self::HAS_GPS_TIME),
+            (attributes.normal.is_some(), Self::HAS_NORMAL),
+        ]
+        .into_iter()
+        .filter(|(has, _)| *has)
+        .fold(0, |present, (_, flag)| present | flag);
         Self {
             x: point.x,
             y: point.y,
@@ -106,6 +632,20 @@ impl From<Point> for CompactPoint {
             r: point.color.r,
             g: point.color.g,
             b: point.color.b,
+            classification: point
+                .attributes
+                .classification
+                .and_then(|class| class.parse().ok())
+                .unwrap_or(0),
+            intensity: point.attributes.intensity.unwrap_or(0),
+            return_number: point.attributes.return_number.unwrap_or(0),
+            scan_angle: point.attributes.scan_angle.unwrap_or(0.0),
+            point_source_id: point.attributes.point_source_id.unwrap_or(0),
+            gps_time: point.attributes.gps_time.unwrap_or(0.0),
+            source_file: point.attributes.source_file.unwrap_or(0),
+            point_index: point.attributes.point_index.unwrap_or(0),
+            normal: point.attributes.normal.unwrap_or_default(),
+            present,
         }
     }
 }
@@ -122,14 +662,29 @@ impl From<CompactPoint> for Point {
                 b: point.b,
             },
             attributes: pcd_core::pointcloud::point::PointAttributes {
-                intensity: None,
-                return_number: None,
-                classification: None,
+                intensity: point
+                    .has(CompactPoint::HAS_INTENSITY)
+                    .then_some(point.intensity),
+                return_number: point
+                    .has(CompactPoint::HAS_RETURN_NUMBER)
+                    .then_some(point.return_number),
+                classification: point
+                    .has(CompactPoint::HAS_CLASSIFICATION)
+                    .then(|| point.classification.to_string()),
                 scanner_channel: None,
-                scan_angle: None,
+                scan_angle: point
+                    .has(CompactPoint::HAS_SCAN_ANGLE)
+                    .then_some(point.scan_angle),
                 user_data: None,
-                point_source_id: None,
-                gps_time: None,
+                point_source_id: point
+                    .has(CompactPoint::HAS_POINT_SOURCE_ID)
+                    .then_some(point.point_source_id),
+                gps_time: point
+                    .has(CompactPoint::HAS_GPS_TIME)
+                    .then_some(point.gps_time),
+                source_file: Some(point.source_file),
+                point_index: Some(point.point_index),
+                normal: point.has(CompactPoint::HAS_NORMAL).then_some(point.normal),
             },
         }
     }
@@ -141,12 +696,25 @@ fn write_run_file(path: &Path, records: &[(SortKey, CompactPoint)]) -> std::io::
 
     for (key, point) in records {
         writer.write_all(&key.tile_id.to_le_bytes())?;
+        writer.write_all(&key.h.to_le_bytes())?;
         writer.write_all(&point.x.to_le_bytes())?;
         writer.write_all(&point.y.to_le_bytes())?;
         writer.write_all(&point.z.to_le_bytes())?;
         writer.write_all(&point.r.to_le_bytes())?;
         writer.write_all(&point.g.to_le_bytes())?;
         writer.write_all(&point.b.to_le_bytes())?;
+        writer.write_all(&[point.classification])?;
+        writer.write_all(&point.intensity.to_le_bytes())?;
+        writer.write_all(&[point.return_number])?;
+        writer.write_all(&point.scan_angle.to_le_bytes())?;
+        writer.write_all(&point.point_source_id.to_le_bytes())?;
+        writer.write_all(&point.gps_time.to_le_bytes())?;
+        writer.write_all(&point.source_file.to_le_bytes())?;
+        writer.write_all(&point.point_index.to_le_bytes())?;
+        for component in point.normal {
+            writer.write_all(&component.to_le_bytes())?;
+        }
+        writer.write_all(&[point.present])?;
     }
 
     writer.flush()?;
@@ -173,14 +741,28 @@ impl RunFileReader {
             Ok(()) => {
                 let key = SortKey {
                     tile_id: u64::from_le_bytes(record[0..8].try_into().unwrap()),
+                    h: u32::from_le_bytes(record[8..12].try_into().unwrap()),
                 };
                 let point = CompactPoint {
-                    x: f64::from_le_bytes(record[8..16].try_into().unwrap()),
-                    y: f64::from_le_bytes(record[16..24].try_into().unwrap()),
-                    z: f64::from_le_bytes(record[24..32].try_into().unwrap()),
-                    r: u16::from_le_bytes(record[32..34].try_into().unwrap()),
-                    g: u16::from_le_bytes(record[34..36].try_into().unwrap()),
-                    b: u16::from_le_bytes(record[36..38].try_into().unwrap()),
+                    x: f64::from_le_bytes(record[12..20].try_into().unwrap()),
+                    y: f64::from_le_bytes(record[20..28].try_into().unwrap()),
+                    z: f64::from_le_bytes(record[28..36].try_into().unwrap()),
+                    r: u16::from_le_bytes(record[36..38].try_into().unwrap()),
+                    g: u16::from_le_bytes(record[38..40].try_into().unwrap()),
+                    b: u16::from_le_bytes(record[40..42].try_into().unwrap()),
+                    classification: record[42],
+                    intensity: u16::from_le_bytes(record[43..45].try_into().unwrap()),
+                    return_number: record[45],
+                    scan_angle: f32::from_le_bytes(record[46..50].try_into().unwrap()),
+                    point_source_id: u16::from_le_bytes(record[50..52].try_into().unwrap()),
+                    gps_time: f64::from_le_bytes(record[52..60].try_into().unwrap()),
+                    source_file: u32::from_le_bytes(record[60..64].try_into().unwrap()),
+                    point_index: u64::from_le_bytes(record[64..72].try_into().unwrap()),
+                    normal: std::array::from_fn(|axis| {
+                        let start = 72 + axis * 4;
+                        f32::from_le_bytes(record[start..start + 4].try_into().unwrap())
+                    }),
+                    present: record[84],
                 };
                 Ok(Some((key, point)))
             }
@@ -260,13 +842,18 @@ fn expand_globs(input_patterns: Vec<String>) -> Vec<PathBuf> {
     paths
 }
 
-fn write_points_to_tile(
-    dir_path: &Path,
-    tile: (u8, u32, u32),
-    points: &[Point],
-) -> std::io::Result<()> {
-    let (z, x, y) = tile;
-    let tile_path = dir_path.join(format!("{}/{}/{}.bin", z, x, y));
+// The vertical index is only encoded when non-zero, so quadtree tiles stay at z/x/y.bin
+fn tile_file_path(dir_path: &Path, tile: TileZXYH) -> PathBuf {
+    let (z, x, y, h) = tile;
+    if h == 0 {
+        dir_path.join(format!("{}/{}/{}.bin", z, x, y))
+    } else {
+        dir_path.join(format!("{}/{}/{}-{}.bin", z, x, y, h))
+    }
+}
+
+fn write_points_to_tile(dir_path: &Path, tile: TileZXYH, points: &[Point]) -> std::io::Result<()> {
+    let tile_path = tile_file_path(dir_path, tile);
 
     fs::create_dir_all(tile_path.parent().unwrap())?;
 
@@ -292,7 +879,7 @@ fn read_points_from_tile(file_path: &Path) -> std::io::Result<Vec<Point>> {
     Ok(points)
 }
 
-fn extract_tile_coords(file_path: &Path) -> (u8, u32, u32) {
+fn extract_tile_coords(file_path: &Path) -> TileZXYH {
     let x_dir = file_path.parent().unwrap();
     let z_dir = x_dir.parent().unwrap();
 
@@ -310,15 +897,13 @@ fn extract_tile_coords(file_path: &Path) -> (u8, u32, u32) {
         .unwrap()
         .parse()
         .unwrap();
-    let y: u32 = file_path
-        .file_stem()
-        .unwrap()
-        .to_str()
-        .unwrap()
-        .parse()
-        .unwrap();
+    let stem = file_path.file_stem().unwrap().to_str().unwrap();
+    let (y, h) = match stem.split_once('-') {
+        Some((y, h)) => (y.parse().unwrap(), h.parse().unwrap()),
+        None => (stem.parse().unwrap(), 0),
+    };
 
-    (z, x, y)
+    (z, x, y, h)
 }
 
 fn get_tile_list_for_zoom(base_path: &Path, z: u8) -> Vec<PathBuf> {
@@ -393,13 +978,171 @@ fn meters_per_degree_longitude(latitude_deg: f64) -> f64 {
     111_412.84 * lat.cos() - 93.5 * (3.0 * lat).cos()
 }
 
-fn maybe_decimate_points(tile: (u8, u32, u32), points: Vec<Point>, disable: bool) -> Vec<Point> {
+/// Average horizontal distance (metres) between points spread evenly over the extent
+fn average_point_spacing(west: f64, east: f64, south: f64, north: f64, count: usize) -> f64 {
+    let center_lat = (south + north) * 0.5;
+    let width = (east - west) * meters_per_degree_longitude(center_lat);
+    let height = (north - south) * meters_per_degree_latitude(center_lat);
+    (width * height / count.max(1) as f64).sqrt()
+}
+
+/// How the points grouped into a tile at the maximum zoom level are written as leaf tiles
+#[derive(Clone, Copy)]
+struct LeafTiling<'a> {
+    scheme: &'a dyn TilingScheme,
+    subdivision: Subdivision,
+    point_budget: Option<PointBudget>,
+    disable_decimation: bool,
+    voxel_factor: f64,
+    normals: Option<LeafNormals>,
+}
+
+/// `--normals`, estimated once on the full-resolution points of the tiles at the maximum zoom
+/// level; coarser tiles keep the normals of the points they keep
+#[derive(Clone, Copy)]
+struct LeafNormals {
+    neighbours: usize,
+    orientation: NormalOrientation,
+}
+
+impl LeafNormals {
+    /// Fits the normals of `points` in ECEF, with the points of the adjacent tiles in `context`
+    /// as neighbours too, so that points along the tile edges get a full neighbourhood
+    fn estimate(&self, points: &mut [Point], context: &[Point]) {
+        let mut ecef = points
+            .iter()
+            .chain(context)
+            .map(|p| {
+                let [x, y, z] = geodetic_to_ecef(p.x, p.y, p.z);
+                Point {
+                    x,
+                    y,
+                    z,
+                    color: Color::default(),
+                    attributes: PointAttributes::default(),
+                }
+            })
+            .collect::<Vec<_>>();
+        let (own, context) = ecef.split_at_mut(points.len());
+        estimate_normals(own, context, self.neighbours, self.orientation);
+        for (point, ecef) in points.iter_mut().zip(own) {
+            point.attributes.normal = ecef.attributes.normal;
+        }
+    }
+}
+
+impl LeafTiling<'_> {
+    /// Writes the points of a tile, split into deeper tiles first when they exceed the point
+    /// budget; `context` holds the points of the adjacent tiles for the normals
+    fn write(
+        &self,
+        base_path: &Path,
+        tile: TileZXYH,
+        mut points: Vec<Point>,
+        context: &[Point],
+    ) -> std::io::Result<()> {
+        if let Some(normals) = &self.normals {
+            normals.estimate(&mut points, context);
+        }
+        let leaves = match self.point_budget {
+            Some(budget) => budget.split_leaf(self.scheme, self.subdivision, tile, points),
+            None => vec![(tile, points)],
+        };
+        for (tile, points) in leaves {
+            let points = maybe_decimate_points(
+                self.scheme,
+                tile,
+                points,
+                self.disable_decimation,
+                self.voxel_factor,
+            );
+            write_points_to_tile(base_path, tile, &points)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the given tiles at the maximum zoom level, whose points `load` returns; with
+    /// `--normals`, it also loads the adjacent tiles as neighbours
+    fn write_all(
+        &self,
+        base_path: &Path,
+        tiles: &[TileZXYH],
+        load: impl Fn(TileZXYH) -> std::io::Result<Option<Vec<Point>>> + Sync,
+    ) -> std::io::Result<()> {
+        tiles.par_iter().try_for_each(|&tile| {
+            let points = load(tile)?.unwrap_or_default();
+            let mut context = Vec::new();
+            if self.normals.is_some() {
+                for adjacent in adjacent_tiles(self.scheme, self.subdivision, tile) {
+                    context.extend(load(adjacent)?.into_iter().flatten());
+                }
+            }
+            self.write(base_path, tile, points, &context)
+        })
+    }
+}
+
+/// Tiles around `tile` at the same zoom level, diagonals included, and with octree subdivision
+/// also those above and below
+fn adjacent_tiles(
+    scheme: &dyn TilingScheme,
+    subdivision: Subdivision,
+    tile: TileZXYH,
+) -> Vec<TileZXYH> {
+    let (z, x, y, h) = tile;
+    let (west, south, east, north) = scheme.bounds(z, x, y);
+    let (lng, lat) = ((west + east) * 0.5, (south + north) * 0.5);
+    let heights = match subdivision {
+        Subdivision::Quadtree => h..=h,
+        Subdivision::Octree => h.saturating_sub(1)..=h + 1,
+    };
+    let mut tiles = Vec::new();
+    for dy in [-1.0, 0.0, 1.0] {
+        let lat = lat + dy * (north - south);
+        if !(-90.0..=90.0).contains(&lat) {
+            continue;
+        }
+        for dx in [-1.0, 0.0, 1.0] {
+            let lng = (lng + dx * (east - west) + 540.0).rem_euclid(360.0) - 180.0;
+            let (z, x, y) = scheme.zxy_from_lng_lat(z, lng, lat);
+            for h in heights.clone() {
+                let adjacent = (z, x, y, h);
+                if adjacent != tile && !tiles.contains(&adjacent) {
+                    tiles.push(adjacent);
+                }
+            }
+        }
+    }
+    tiles
+}
+
+fn maybe_decimate_points(
+    scheme: &dyn TilingScheme,
+    tile: TileZXYH,
+    points: Vec<Point>,
+    disable: bool,
+    voxel_factor: f64,
+) -> Vec<Point> {
     if disable || points.is_empty() {
         return points;
     }
 
-    let (z, _, y) = tile;
-    let voxel_size = geometric_error(z, y) * 0.1;
+    decimated_point_indices(scheme, tile, &points, voxel_factor)
+        .into_iter()
+        .map(|point_index| points[point_index].clone())
+        .collect()
+}
+
+/// Indices of the points kept by voxel decimation, one per voxel of
+/// `geometric_error * voxel_factor`
+fn decimated_point_indices(
+    scheme: &dyn TilingScheme,
+    tile: TileZXYH,
+    points: &[Point],
+    voxel_factor: f64,
+) -> Vec<usize> {
+    let (z, x, y, _) = tile;
+    let voxel_size = scheme.geometric_error(z, x, y) * voxel_factor;
 
     let (min_lon, max_lon, min_lat, max_lat, min_height) = points.iter().fold(
         (f64::MAX, f64::MIN, f64::MAX, f64::MIN, f64::MAX),
@@ -458,20 +1201,22 @@ fn maybe_decimate_points(tile: (u8, u32, u32), points: Vec<Point>, disable: bool
 
     selected
         .into_iter()
-        .map(|(_, (_, point_index))| points[point_index].clone())
+        .map(|(_, (_, point_index))| point_index)
         .collect()
 }
 
 fn group_child_files_by_parent(
+    scheme: &dyn TilingScheme,
     child_files: Vec<PathBuf>,
     z: u8,
-) -> HashMap<(u8, u32, u32), Vec<PathBuf>> {
-    let mut parent_files = HashMap::<(u8, u32, u32), Vec<PathBuf>>::new();
+) -> HashMap<TileZXYH, Vec<PathBuf>> {
+    let mut parent_files = HashMap::<TileZXYH, Vec<PathBuf>>::new();
 
     for child_file in child_files {
-        let (_, cx, cy) = extract_tile_coords(&child_file);
+        let (_, cx, cy, ch) = extract_tile_coords(&child_file);
+        let (_, px, py) = scheme.parent_zxy(z + 1, cx, cy);
         parent_files
-            .entry((z, cx / 2, cy / 2))
+            .entry((z, px, py, ch / 2))
             .or_default()
             .push(child_file);
     }
@@ -483,23 +1228,90 @@ fn group_child_files_by_parent(
     parent_files
 }
 
-fn aggregate_zoom_level(base_path: &Path, z: u8, disable_decimation: bool) -> std::io::Result<()> {
+fn aggregate_zoom_level(
+    base_path: &Path,
+    scheme: &dyn TilingScheme,
+    z: u8,
+    disable_decimation: bool,
+    voxel_factor: f64,
+    point_budget: Option<PointBudget>,
+    additive: bool,
+) -> std::io::Result<()> {
     let child_z = z + 1;
     let child_files = get_tile_list_for_zoom(base_path, child_z);
 
-    group_child_files_by_parent(child_files, z)
+    // Children that still have children of their own cannot be merged into their parent
+    let split_children: HashSet<TileZXYH> = match point_budget {
+        Some(_) => adaptive::parent_tiles(
+            scheme,
+            get_tile_list_for_zoom(base_path, child_z + 1)
+                .iter()
+                .map(|path| extract_tile_coords(path)),
+        ),
+        None => HashSet::new(),
+    };
+
+    group_child_files_by_parent(scheme, child_files, z)
         .into_par_iter()
         .try_for_each(|(parent_tile, child_files)| -> std::io::Result<()> {
             let mut points = Vec::new();
-
-            for child_file in child_files {
-                let (cz, _, _) = extract_tile_coords(&child_file);
-                debug_assert_eq!(cz, child_z);
-                let mut child_points = read_points_from_tile(&child_file)?;
+            let mut child_ranges = Vec::with_capacity(child_files.len());
+            let mut children_are_leaves = true;
+
+            for child_file in &child_files {
+                let child_tile = extract_tile_coords(child_file);
+                debug_assert_eq!(child_tile.0, child_z);
+                children_are_leaves &= !split_children.contains(&child_tile);
+                let mut child_points = read_points_from_tile(child_file)?;
+                let start = points.len();
                 points.append(&mut child_points);
+                child_ranges.push((child_tile, child_file, start..points.len()));
             }
 
-            let points = maybe_decimate_points(parent_tile, points, disable_decimation);
+            if point_budget
+                .is_some_and(|budget| budget.merges_children(points.len(), children_are_leaves))
+            {
+                write_points_to_tile(base_path, parent_tile, &points)?;
+                for child_file in &child_files {
+                    fs::remove_file(child_file)?;
+                }
+                return Ok(());
+            }
+
+            if additive {
+                // The parent takes the points winning its voxels and the children keep the
+                // rest, so no point is stored at more than one level
+                let selected = decimated_point_indices(scheme, parent_tile, &points, voxel_factor);
+                let mut taken = vec![false; points.len()];
+                for &point_index in &selected {
+                    taken[point_index] = true;
+                }
+                for (child_tile, child_file, range) in child_ranges {
+                    let remaining = range
+                        .filter(|&point_index| !taken[point_index])
+                        .map(|point_index| points[point_index].clone())
+                        .collect::<Vec<_>>();
+                    if remaining.is_empty() {
+                        fs::remove_file(child_file)?;
+                    } else {
+                        write_points_to_tile(base_path, child_tile, &remaining)?;
+                    }
+                }
+                let points = selected
+                    .into_iter()
+                    .map(|point_index| points[point_index].clone())
+                    .collect::<Vec<_>>();
+                write_points_to_tile(base_path, parent_tile, &points)?;
+                return Ok(());
+            }
+
+            let points = maybe_decimate_points(
+                scheme,
+                parent_tile,
+                points,
+                disable_decimation,
+                voxel_factor,
+            );
             write_points_to_tile(base_path, parent_tile, &points)?;
             Ok(())
         })?;
@@ -507,28 +1319,53 @@ fn aggregate_zoom_level(base_path: &Path, z: u8, disable_decimation: bool) -> st
     Ok(())
 }
 
+/// Moves the tiles of zoom level `z` to another tile directory.
+///
+/// Shard roots at `--min` are moved, not copied, into the overview directory and exported from
+/// there together with the overview levels, so each of them is written exactly once. They have
+/// to wait for the overviews: additive refinement takes the points picked for an overview tile
+/// out of its children.
+fn move_tiles_for_zoom(from_path: &Path, to_path: &Path, z: u8) -> std::io::Result<()> {
+    for tile_file in get_tile_list_for_zoom(from_path, z) {
+        let moved_path = tile_file_path(to_path, extract_tile_coords(&tile_file));
+        fs::create_dir_all(moved_path.parent().unwrap())?;
+        fs::rename(&tile_file, moved_path)?;
+    }
+    Ok(())
+}
+
 fn export_tiles_to_glb(
     base_path: &Path,
     output_path: &Path,
     min_zoom: u8,
     max_zoom: u8,
+    args: &Cli,
     glb_options: &GlbOptions,
+    input_attributes: AvailableAttributes,
 ) -> std::io::Result<Vec<TileContent>> {
+    let subdivision = args.subdivision();
+    let implicit = args.implicit_tiling();
     let mut all_tiles = Vec::new();
     for z in min_zoom..=max_zoom {
         let files = get_tile_list_for_zoom(base_path, z);
         all_tiles.extend(files);
     }
 
-    let tile_contents: Vec<TileContent> = all_tiles
+    let tile_contents: Vec<Vec<TileContent>> = all_tiles
         .par_iter()
-        .map(|tile_file| -> std::io::Result<TileContent> {
-            let (tz, tx, ty) = extract_tile_coords(tile_file);
-            let mut points = read_points_from_tile(tile_file)?;
+        .map(|tile_file| -> std::io::Result<Vec<TileContent>> {
+            let tile = extract_tile_coords(tile_file);
+            let points = read_points_from_tile(tile_file)?;
             let epsg = EPSG_WGS84_GEOGRAPHIC_3D;
-            let pc = PointCloud::new(points.clone(), epsg);
 
-            let mut tile_content = make_tile_content(&(tz, tx, ty), &pc);
+            let layers = if args.classification_groups {
+                ClassificationGroup::split(points)
+                    .into_iter()
+                    .map(|(group, points)| (Some(group), points))
+                    .collect()
+            } else {
+                vec![(None, points)]
+            };
 
             // EPSG:4979 (Geographic 3D) → EPSG:4978 (Geocentric/ECEF)
             let mut geocentric_transformer =
@@ -538,63 +1375,140 @@ fn export_tiles_to_glb(
                             "Failed to create geocentric transformer: {e}"
                         ))
                     })?;
-            geocentric_transformer
-                .transform_points_in_place(&mut points)
-                .map_err(|e| {
-                    std::io::Error::other(format!("Failed to transform to geocentric: {e}"))
-                })?;
 
-            // Compute ECEF bbox min (before axis swap)
-            let ecef_min = points.iter().fold([f64::MAX; 3], |mut acc, p| {
-                acc[0] = acc[0].min(p.x);
-                acc[1] = acc[1].min(p.y);
-                acc[2] = acc[2].min(p.z);
-                acc
-            });
+            let layers = layers
+                .into_iter()
+                .map(|(group, mut points)| -> std::io::Result<_> {
+                    let pc = PointCloud::new(points.clone(), epsg);
 
-            // Subtract ECEF offset and swap axes for Cesium
-            // Local ECEF (X, Y, Z) → Cesium (X, Z, -Y)
-            for p in &mut points {
-                let (x, y, z) = (p.x - ecef_min[0], p.y - ecef_min[1], p.z - ecef_min[2]);
-                p.x = x;
-                p.y = z;
-                p.z = -y;
-            }
-
-            // Store ECEF offset in TileContent (before axis swap)
-            tile_content.translation = ecef_min;
-
-            let glb_path = output_path.join(&tile_content.content_path);
-            fs::create_dir_all(glb_path.parent().unwrap())?;
+                    let mut tile_content = make_tile_content(&tile, subdivision, &pc);
+                    if let Some(implicit) = &implicit {
+                        tile_content.content_path = implicit.content_path(tile);
+                    }
+                    if let Some(group) = group {
+                        tile_content.content_path = group.content_path(&tile_content.content_path);
+                        tile_content.group = Some(group.index());
+                    }
+                    tile_content.point_spacing = Some(average_point_spacing(
+                        tile_content.min_lng,
+                        tile_content.max_lng,
+                        tile_content.min_lat,
+                        tile_content.max_lat,
+                        pc.points.len(),
+                    ));
+
+                    geocentric_transformer
+                        .transform_points_in_place(&mut points)
+                        .map_err(|e| {
+                            std::io::Error::other(format!("Failed to transform to geocentric: {e}"))
+                        })?;
+
+                    if args.bounding_volume != BoundingVolumeKind::Region {
+                        let ecef_points =
+                            points.iter().map(|p| [p.x, p.y, p.z]).collect::<Vec<_>>();
+                        tile_content.bounding_volume =
+                            EcefVolume::from_points(args.bounding_volume, &ecef_points);
+                    }
+                    Ok((tile_content, points))
+                })
+                .collect::<std::io::Result<Vec<_>>>()?;
+
+            // Compute ECEF bbox min (before axis swap); all layers of a tile share the tile
+            // transform, so they share the offset too
+            let ecef_min =
+                layers
+                    .iter()
+                    .flat_map(|(_, points)| points)
+                    .fold([f64::MAX; 3], |mut acc, p| {
+                        acc[0] = acc[0].min(p.x);
+                        acc[1] = acc[1].min(p.y);
+                        acc[2] = acc[2].min(p.z);
+                        acc
+                    });
+
+            // Implicit tiles cannot have their own transform, so keep absolute ECEF
+            // coordinates and let the GLB node translation carry the offset instead
+            let ecef_origin = if implicit.is_some() {
+                [0.0; 3]
+            } else {
+                ecef_min
+            };
 
-            let glb_point_cloud = PointCloud::new(points, epsg);
-            let glb = pcd_exporter::gltf::generate_glb_with_options(glb_point_cloud, glb_options)
-                .map_err(|e| std::io::Error::other(format!("glb generation failed: {e}")))?;
+            layers
+                .into_iter()
+                .map(|(mut tile_content, mut points)| {
+                    // Subtract ECEF offset and swap axes for Cesium
+                    // Local ECEF (X, Y, Z) → Cesium (X, Z, -Y)
+                    for p in &mut points {
+                        let (x, y, z) = (
+                            p.x - ecef_origin[0],
+                            p.y - ecef_origin[1],
+                            p.z - ecef_origin[2],
+                        );
+                        p.x = x;
+                        p.y = z;
+                        p.z = -y;
+                        if let Some([nx, ny, nz]) = p.attributes.normal {
+                            p.attributes.normal = Some([nx, nz, -ny]);
+                        }
+                    }
 
-            if glb_options.gzip_compress {
-                let file = File::create(glb_path)?;
-                let writer = flate2::write::GzEncoder::new(
-                    BufWriter::new(file),
-                    flate2::Compression::default(),
-                );
-                glb.to_writer_with_alignment(writer, 8)?;
-            } else {
-                let file = File::create(glb_path)?;
-                let writer = BufWriter::new(file);
-                glb.to_writer_with_alignment(writer, 8)?;
-            }
+                    // Store ECEF offset in TileContent (before axis swap)
+                    tile_content.translation = ecef_origin;
+
+                    let glb_path = output_path.join(&tile_content.content_path);
+                    fs::create_dir_all(glb_path.parent().unwrap())?;
+
+                    let mut glb_point_cloud = PointCloud::new(points, epsg);
+                    glb_point_cloud.metadata.attributes = input_attributes;
+                    let glb =
+                        pcd_exporter::gltf::generate_glb_with_options(glb_point_cloud, glb_options)
+                            .map_err(|e| {
+                                std::io::Error::other(format!("glb generation failed: {e}"))
+                            })?;
+
+                    if glb_options.gzip_compress {
+                        let file = File::create(glb_path)?;
+                        let writer = flate2::write::GzEncoder::new(
+                            BufWriter::new(file),
+                            flate2::Compression::default(),
+                        );
+                        glb.to_writer_with_alignment(writer, 8)?;
+                    } else {
+                        let file = File::create(glb_path)?;
+                        let writer = BufWriter::new(file);
+                        glb.to_writer_with_alignment(writer, 8)?;
+                    }
 
-            Ok(tile_content)
+                    Ok(tile_content)
+                })
+                .collect()
         })
         .collect::<std::io::Result<Vec<_>>>()?;
 
-    Ok(tile_contents)
+    Ok(tile_contents.into_iter().flatten().collect())
 }
 
-#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Encode, Decode)]
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Encode, Decode)]
 #[repr(C)]
 pub struct SortKey {
     pub tile_id: u64,
+    pub h: u32,
+}
+
+impl SortKey {
+    fn from_tile(tile: TileZXYH) -> Self {
+        let (z, x, y, h) = tile;
+        Self {
+            tile_id: TileIdMethod::Hilbert.zxy_to_id(z, x, y),
+            h,
+        }
+    }
+
+    fn to_tile(self) -> TileZXYH {
+        let (z, x, y) = TileIdMethod::Hilbert.id_to_zxy(self.tile_id);
+        (z, x, y, self.h)
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -616,26 +1530,32 @@ impl TileIdMethod {
     }
 }
 
+/// Writes the points of a tile at the maximum zoom level: as a leaf tile, or as is into
+/// `staging` when the leaves need the adjacent tiles
 fn flush_tile_points(
     base_path: &Path,
-    tile_id: u64,
+    leaf_tiling: &LeafTiling,
+    staging: Option<&mut (TempDir, Vec<TileZXYH>)>,
+    key: SortKey,
     points: &mut Vec<Point>,
-    disable_decimation: bool,
 ) -> std::io::Result<()> {
     if points.is_empty() {
         return Ok(());
     }
 
-    let tile = TileIdMethod::Hilbert.id_to_zxy(tile_id);
-    let tile_points = std::mem::take(points);
-    let tile_points = maybe_decimate_points(tile, tile_points, disable_decimation);
-    write_points_to_tile(base_path, tile, &tile_points)
+    match staging {
+        Some((staging_dir, tiles)) => {
+            tiles.push(key.to_tile());
+            write_points_to_tile(staging_dir.path(), key.to_tile(), &std::mem::take(points))
+        }
+        None => leaf_tiling.write(base_path, key.to_tile(), std::mem::take(points), &[]),
+    }
 }
 
 fn merge_shard_run_files(
     run_files: Vec<PathBuf>,
     output_base_path: &Path,
-    disable_decimation: bool,
+    leaf_tiling: &LeafTiling,
 ) -> std::io::Result<()> {
     let mut readers = Vec::<Option<RunFileReader>>::new();
     let mut heap = BinaryHeap::<HeapItem>::new();
@@ -660,15 +1580,27 @@ fn merge_shard_run_files(
         }
     }
 
-    let mut current_tile_id = None;
+    // Normals need the points of the adjacent tiles, which are only complete once the whole
+    // shard is sorted
+    let mut staging = match leaf_tiling.normals {
+        Some(_) => Some((tempdir()?, Vec::new())),
+        None => None,
+    };
+    let mut current_key = None;
     let mut tile_points = Vec::<Point>::new();
 
     while let Some(item) = heap.pop() {
-        if current_tile_id != Some(item.key.tile_id) {
-            if let Some(tile_id) = current_tile_id {
-                flush_tile_points(output_base_path, tile_id, &mut tile_points, disable_decimation)?;
+        if current_key != Some(item.key) {
+            if let Some(key) = current_key {
+                flush_tile_points(
+                    output_base_path,
+                    leaf_tiling,
+                    staging.as_mut(),
+                    key,
+                    &mut tile_points,
+                )?;
             }
-            current_tile_id = Some(item.key.tile_id);
+            current_key = Some(item.key);
         }
 
         tile_points.push(Point::from(item.point));
@@ -688,8 +1620,25 @@ fn merge_shard_run_files(
         }
     }
 
-    if let Some(tile_id) = current_tile_id {
-        flush_tile_points(output_base_path, tile_id, &mut tile_points, disable_decimation)?;
+    if let Some(key) = current_key {
+        flush_tile_points(
+            output_base_path,
+            leaf_tiling,
+            staging.as_mut(),
+            key,
+            &mut tile_points,
+        )?;
+    }
+
+    if let Some((staging_dir, tiles)) = staging {
+        leaf_tiling.write_all(output_base_path, &tiles, |tile| {
+            let path = tile_file_path(staging_dir.path(), tile);
+            if path.exists() {
+                read_points_from_tile(&path).map(Some)
+            } else {
+                Ok(None)
+            }
+        })?;
     }
 
     Ok(())
@@ -702,14 +1651,490 @@ fn estimate_total_size(paths: &[PathBuf]) -> u64 {
         .sum()
 }
 
-fn estimate_processing_size(paths: &[PathBuf], extension: Extension) -> u64 {
-    match extension {
-        Extension::Las | Extension::Laz => paths
-            .iter()
-            .map(LasPointReader::estimate_processing_size)
-            .sum(),
-        Extension::Csv | Extension::Txt => estimate_total_size(paths),
-    }
+fn estimate_processing_size(paths: &[PathBuf], extension: Extension) -> u64 {
+    match extension {
+        Extension::Las | Extension::Laz => paths
+            .iter()
+            .map(LasPointReader::estimate_processing_size)
+            .sum(),
+        Extension::Csv | Extension::Txt => estimate_total_size(paths),
+    }
+}
+
+fn read_input_extent(paths: &[PathBuf], extension: Extension) -> std::io::Result<InputExtent> {
+    let extents = paths
+        .par_iter()
+        .map(|path| match extension {
+            Extension::Las | Extension::Laz => LasPointReader::read_extent(path),
+            Extension::Csv | Extension::Txt => CsvPointReader::read_extent(path),
+        })
+        .collect::<std::io::Result<Vec<_>>>()?;
+    Ok(extents
+        .iter()
+        .fold(InputExtent::default(), |acc, extent| acc.merge(extent)))
+}
+
+/// Attributes stored by each input
+fn read_file_attributes(
+    paths: &[PathBuf],
+    extension: Extension,
+) -> std::io::Result<Vec<AvailableAttributes>> {
+    paths
+        .par_iter()
+        .map(|path| match extension {
+            Extension::Las | Extension::Laz => LasPointReader::read_attributes(path),
+            Extension::Csv | Extension::Txt => CsvPointReader::read_attributes(path),
+        })
+        .collect()
+}
+
+/// Attributes stored by any of the inputs
+fn read_input_attributes(
+    paths: &[PathBuf],
+    extension: Extension,
+) -> std::io::Result<AvailableAttributes> {
+    Ok(read_file_attributes(paths, extension)?
+        .iter()
+        .copied()
+        .reduce(|acc, attributes| acc.merge(&attributes))
+        .unwrap_or_default())
+}
+
+/// Attributes of the written points: those of the input, plus the colors of `--colorize` and
+/// `--orthophoto`. `--attributes` that no input stores are left out of the GLBs.
+fn output_attributes(
+    args: &Cli,
+    paths: &[PathBuf],
+    extension: Extension,
+) -> std::io::Result<AvailableAttributes> {
+    let attributes = read_input_attributes(paths, extension)?;
+    for attribute in &args.attributes {
+        if !attribute.is_available(&attributes) {
+            log::warn!(
+                "--attributes: no input stores {:?}; it is left out",
+                attribute
+            );
+        }
+    }
+    Ok(AvailableAttributes {
+        color: attributes.color || args.colorize.is_some() || !args.orthophoto.is_empty(),
+        ..attributes
+    })
+}
+
+/// A GeoTIFF orthophoto with the transformation of the points into its CRS
+struct Orthophoto {
+    image: GeoTiff,
+    /// Transformer from the input CRS, unless the image shares it; created once and shared by
+    /// the reader threads
+    transformer: Option<Mutex<PointTransformer>>,
+}
+
+/// GeoTIFF orthophotos that color the points, sampled in the order they are given
+struct Orthophotos {
+    images: Vec<Orthophoto>,
+    /// Whether each input file has colors of its own, which are kept unless overwriting
+    file_colors: Vec<bool>,
+    overwrite: bool,
+}
+
+impl Orthophotos {
+    fn open(args: &Cli, paths: &[PathBuf], extension: Extension) -> std::io::Result<Option<Self>> {
+        if args.orthophoto.is_empty() {
+            return Ok(None);
+        }
+        let input_epsg = args
+            .input_epsg
+            .expect("clap requires --input-epsg without --local-origin");
+        let cache_bytes =
+            args.max_memory_mb * 1024 * 1024 / ORTHOPHOTO_CACHE_SHARE / args.orthophoto.len();
+        let images = args
+            .orthophoto
+            .par_iter()
+            .map(|path| -> std::io::Result<_> {
+                let image = GeoTiff::open(Path::new(path), cache_bytes).map_err(|e| {
+                    std::io::Error::new(e.kind(), format!("orthophoto {path}: {e}"))
+                })?;
+                let epsg = image.epsg.or(args.orthophoto_epsg).ok_or_else(|| {
+                    std::io::Error::other(format!(
+                        "orthophoto {path} has no EPSG code; set --orthophoto-epsg"
+                    ))
+                })?;
+                log::info!(
+                    "orthophoto {}: {}x{} pixels, EPSG:{}, bounds {:?}, nodata {:?}",
+                    path,
+                    image.width,
+                    image.height,
+                    epsg,
+                    image.bounds(),
+                    image.nodata
+                );
+                let transformer = (epsg != input_epsg)
+                    .then(|| PointTransformer::new(input_epsg, epsg, None))
+                    .transpose()
+                    .map_err(|e| std::io::Error::other(format!("orthophoto {path}: {e}")))?
+                    .map(Mutex::new);
+                Ok(Orthophoto { image, transformer })
+            })
+            .collect::<std::io::Result<Vec<_>>>()?;
+        let file_colors = read_file_attributes(paths, extension)?
+            .iter()
+            .map(|attributes| attributes.color)
+            .collect();
+        Ok(Some(Self {
+            images,
+            file_colors,
+            overwrite: args.orthophoto_overwrite,
+        }))
+    }
+
+    /// Colors the points that have no color of their own, or every point when overwriting,
+    /// from the first orthophoto with data at their position; returns the number of colored
+    /// points
+    fn colorize(&self, points: &mut [Point]) -> std::io::Result<usize> {
+        let mut pending = points
+            .iter()
+            .enumerate()
+            .filter(|(_, point)| {
+                self.overwrite
+                    || point
+                        .attributes
+                        .source_file
+                        .is_none_or(|index| !self.file_colors[index as usize])
+            })
+            .map(|(index, _)| index)
+            .collect::<Vec<_>>();
+        let mut colored = 0;
+        for Orthophoto { image, transformer } in &self.images {
+            if pending.is_empty() {
+                break;
+            }
+            let mut positions = pending
+                .iter()
+                .map(|&index| Point {
+                    x: points[index].x,
+                    y: points[index].y,
+                    z: points[index].z,
+                    color: Color::default(),
+                    attributes: PointAttributes::default(),
+                })
+                .collect::<Vec<_>>();
+            if let Some(transformer) = transformer {
+                transformer
+                    .lock()
+                    .unwrap()
+                    .transform_points_in_place(&mut positions)
+                    .map_err(|e| {
+                        std::io::Error::other(format!(
+                            "Failed to transform points into the orthophoto CRS: {e}"
+                        ))
+                    })?;
+            }
+            let mut remaining = Vec::new();
+            for (&index, position) in pending.iter().zip(&positions) {
+                match image.sample(position.x, position.y)? {
+                    Some(rgb) => {
+                        let [r, g, b] = rgb.map(|value| (value * 65535.0).round() as u16);
+                        points[index].color = Color { r, g, b };
+                        colored += 1;
+                    }
+                    None => remaining.push(index),
+                }
+            }
+            pending = remaining;
+        }
+        Ok(colored)
+    }
+}
+
+/// Intensities of the points sampled across every input, for the intensity stretch
+fn sample_input_intensities(paths: &[PathBuf], extension: Extension) -> std::io::Result<Vec<f64>> {
+    let samples = paths
+        .par_iter()
+        .map(|path| -> std::io::Result<Vec<f64>> {
+            Ok(sample_file_points(path, extension)?
+                .into_iter()
+                .filter_map(|point| point.attributes.intensity.map(f64::from))
+                .collect())
+        })
+        .collect::<std::io::Result<Vec<_>>>()?;
+    Ok(samples.concat())
+}
+
+/// Resolves `--colorize` against the input: the elevation range defaults to the input extent,
+/// and the intensity range to the percentiles of a sample of the intensities
+fn colorization(
+    args: &Cli,
+    paths: &[PathBuf],
+    extension: Extension,
+) -> std::io::Result<Option<Colorization>> {
+    let Some(mode) = args.colorize else {
+        return Ok(None);
+    };
+    let attributes = read_input_attributes(paths, extension)?;
+    let colorization = match mode {
+        ColorizeMode::Elevation => {
+            let range = match args.elevation_range {
+                Some(range) => range,
+                None => {
+                    let extent = read_input_extent(paths, extension)?;
+                    [extent.min[2], extent.max[2]]
+                }
+            };
+            Colorization::Elevation {
+                ramp: args.color_ramp,
+                range,
+            }
+        }
+        ColorizeMode::Intensity => {
+            if !attributes.intensity {
+                log::warn!("--colorize intensity: the input has no intensity");
+            }
+            let mut intensities = sample_input_intensities(paths, extension)?;
+            let percentiles = args
+                .intensity_percentiles
+                .unwrap_or(DEFAULT_INTENSITY_PERCENTILES);
+            let range = percentile_range(&mut intensities, percentiles)
+                .unwrap_or([0.0, f64::from(u16::MAX)]);
+            Colorization::Intensity { range }
+        }
+        ColorizeMode::Classification => {
+            if !attributes.classification {
+                log::warn!("--colorize classification: the input has no classification");
+            }
+            Colorization::Classification
+        }
+        ColorizeMode::ReturnNumber => Colorization::ReturnNumber,
+        ColorizeMode::PointSourceId => Colorization::PointSourceId,
+    };
+    log::info!("colorization: {:?}", colorization);
+    Ok(Some(colorization))
+}
+
+/// `SAMPLE_RUNS` runs of `SAMPLE_RUN_LENGTH` consecutive points spread over the input
+fn sample_file_points(path: &PathBuf, extension: Extension) -> std::io::Result<Vec<Point>> {
+    match extension {
+        Extension::Las | Extension::Laz => {
+            LasPointReader::sample_points(path, SAMPLE_RUNS, SAMPLE_RUN_LENGTH)
+        }
+        Extension::Csv | Extension::Txt => {
+            let count = CsvPointReader::read_extent(path)?.point_count;
+            let period = (count / SAMPLE_RUNS).max(SAMPLE_RUN_LENGTH);
+            let mut reader = CsvPointReader::new(vec![path.clone()])?;
+            let mut points = Vec::new();
+            let mut index = 0;
+            while let Some(point) = reader.next_point()? {
+                if index % period < SAMPLE_RUN_LENGTH {
+                    points.push(point);
+                }
+                index += 1;
+            }
+            Ok(points)
+        }
+    }
+}
+
+/// Color statistics of every two inputs whose bounds overlap, from their sampled points within
+/// the shared rectangle; inputs without a sample, having no color, are skipped
+fn color_overlaps(extents: &[InputExtent], samples: &[Option<Vec<Point>>]) -> Vec<ColorOverlap> {
+    let mut overlaps = Vec::new();
+    for a in 0..extents.len() {
+        for b in a + 1..extents.len() {
+            let (Some(sample_a), Some(sample_b)) = (&samples[a], &samples[b]) else {
+                continue;
+            };
+            let min = [0, 1].map(|i| extents[a].min[i].max(extents[b].min[i]));
+            let max = [0, 1].map(|i| extents[a].max[i].min(extents[b].max[i]));
+            if min[0] >= max[0] || min[1] >= max[1] {
+                continue;
+            }
+            let stats = [sample_a, sample_b].map(|sample| {
+                let mut stats = ColorStats::default();
+                for point in sample {
+                    if (min[0]..=max[0]).contains(&point.x) && (min[1]..=max[1]).contains(&point.y)
+                    {
+                        stats.add(&point.color);
+                    }
+                }
+                stats
+            });
+            if stats.iter().all(|stats| stats.count >= MIN_OVERLAP_SAMPLE) {
+                overlaps.push(ColorOverlap {
+                    files: [a, b],
+                    stats,
+                });
+            }
+        }
+    }
+    overlaps
+}
+
+/// Per-input color corrections of `--color-balance`, from the colors of a sample of the points
+/// of every two inputs where their header bounds overlap
+fn color_corrections(
+    args: &Cli,
+    paths: &[PathBuf],
+    extension: Extension,
+) -> std::io::Result<Option<Arc<Vec<ColorCorrection>>>> {
+    if !args.color_balance {
+        return Ok(None);
+    }
+    log::info!("sampling colors to balance the inputs...");
+    let file_attributes = read_file_attributes(paths, extension)?;
+    let (extents, samples): (Vec<_>, Vec<_>) = paths
+        .par_iter()
+        .zip(&file_attributes)
+        .map(|(path, attributes)| -> std::io::Result<_> {
+            let extent = match extension {
+                Extension::Las | Extension::Laz => LasPointReader::read_extent(path)?,
+                Extension::Csv | Extension::Txt => CsvPointReader::read_extent(path)?,
+            };
+            let sample = if attributes.color {
+                Some(sample_file_points(path, extension)?)
+            } else {
+                None
+            };
+            Ok((extent, sample))
+        })
+        .collect::<std::io::Result<Vec<_>>>()?
+        .into_iter()
+        .unzip();
+    let overlaps = color_overlaps(&extents, &samples);
+    if overlaps.is_empty() {
+        log::warn!("--color-balance: no two inputs with color overlap; colors are left as is");
+    }
+    let corrections = balance_colors(paths.len(), &overlaps);
+    log::info!("color balance: {} overlaps", overlaps.len());
+    for (path, correction) in paths.iter().zip(&corrections) {
+        log::info!(
+            "color correction of {:?}: gain {:.3?}, offset {:.0?}",
+            path,
+            correction.gain,
+            correction.offset
+        );
+    }
+    Ok(Some(Arc::new(corrections)))
+}
+
+/// Median distance (metres) from the points to their nearest neighbours, over runs of
+/// consecutive points sampled across the inputs; `None` if the samples have no two distinct
+/// points. Unlike the bounding box area per point, this holds for corridor, diagonal and sparse
+/// surveys that cover a small part of their bounding box.
+fn sample_point_spacing(
+    paths: &[PathBuf],
+    extension: Extension,
+    args: &Cli,
+) -> std::io::Result<Option<f64>> {
+    let distances = paths
+        .par_iter()
+        .map(|path| -> std::io::Result<Vec<f64>> {
+            let mut points = sample_file_points(path, extension)?;
+            let mut transformer = InputTransformer::new(args)
+                .map_err(|e| std::io::Error::other(format!("Failed to create transformer: {e}")))?;
+            transformer
+                .transform_points_in_place(&mut points)
+                .map_err(|e| std::io::Error::other(format!("Failed to transform sample: {e}")))?;
+            Ok(nearest_neighbour_distances(&local_positions(&points)))
+        })
+        .collect::<std::io::Result<Vec<_>>>()?;
+    Ok(median(distances.into_iter().flatten().collect()))
+}
+
+/// Longitude/latitude/height points as metres east, north and up of the first point
+fn local_positions(points: &[Point]) -> Vec<[f64; 3]> {
+    let Some(first) = points.first() else {
+        return Vec::new();
+    };
+    let (lng, lat) = (first.x, first.y);
+    let (x_scale, y_scale) = (
+        meters_per_degree_longitude(lat),
+        meters_per_degree_latitude(lat),
+    );
+    points
+        .iter()
+        .map(|p| [(p.x - lng) * x_scale, (p.y - lat) * y_scale, p.z])
+        .collect()
+}
+
+/// Picks the max zoom whose decimation voxel matches the point spacing, and the min zoom as the
+/// deepest level whose tiles are as large as the whole extent, so that the extent spans at most
+/// 2x2 tiles there. Levels above are left to `--overview-zoom`.
+fn estimate_zoom_range(
+    extent: &InputExtent,
+    spacing: Option<f64>,
+    args: &Cli,
+) -> std::io::Result<(u8, u8)> {
+    // Corners of the input bounding box, transformed to longitude/latitude
+    let mid_z = (extent.min[2] + extent.max[2]) * 0.5;
+    let mut corners = [
+        (extent.min[0], extent.min[1]),
+        (extent.max[0], extent.min[1]),
+        (extent.min[0], extent.max[1]),
+        (extent.max[0], extent.max[1]),
+    ]
+    .map(|(x, y)| {
+        Point::from(CompactPoint {
+            x,
+            y,
+            z: mid_z,
+            ..Default::default()
+        })
+    });
+    let mut transformer = InputTransformer::new(args)
+        .map_err(|e| std::io::Error::other(format!("Failed to create transformer: {e}")))?;
+    transformer
+        .transform_points_in_place(&mut corners)
+        .map_err(|e| std::io::Error::other(format!("Failed to transform extent: {e}")))?;
+
+    let west = corners.iter().map(|p| p.x).fold(f64::MAX, f64::min);
+    let east = corners.iter().map(|p| p.x).fold(f64::MIN, f64::max);
+    let south = corners.iter().map(|p| p.y).fold(f64::MAX, f64::min);
+    let north = corners.iter().map(|p| p.y).fold(f64::MIN, f64::max);
+
+    let spacing = spacing.unwrap_or_else(|| {
+        log::warn!("--auto-zoom: no spacing sampled; using the bounding box area per point");
+        average_point_spacing(west, east, south, north, extent.point_count as usize)
+    });
+    log::info!(
+        "input extent: lng {:.6}..{:.6}, lat {:.6}..{:.6}, {} points, spacing {:.3} m",
+        west,
+        east,
+        south,
+        north,
+        extent.point_count,
+        spacing
+    );
+    Ok(zoom_range(
+        &*args.tiling_scheme(),
+        (west, south, east, north),
+        spacing,
+        args.voxel_factor,
+    ))
+}
+
+/// Min and max zoom of `--auto-zoom` for an extent (west, south, east, north) in degrees and
+/// a point spacing in metres
+fn zoom_range(
+    scheme: &dyn TilingScheme,
+    (west, south, east, north): (f64, f64, f64, f64),
+    spacing: f64,
+    voxel_factor: f64,
+) -> (u8, u8) {
+    let max_zoom = if spacing > 0.0 {
+        // Decimation keeps one point per `geometric_error * voxel_factor` voxel
+        scheme.zoom_for_geometric_error(
+            (west + east) * 0.5,
+            (south + north) * 0.5,
+            spacing / voxel_factor,
+            MAX_AUTO_ZOOM,
+        )
+    } else {
+        MAX_AUTO_ZOOM
+    };
+    let min_zoom = scheme
+        .zoom_for_extent(west, south, east, north, max_zoom)
+        .clamp(2, max_zoom);
+    (min_zoom, max_zoom)
 }
 
 fn estimated_in_memory_requirement_bytes(processing_size: u64) -> u64 {
@@ -774,6 +2199,98 @@ fn log_directory_summary(label: &str, base_path: &Path) {
     }
 }
 
+/// File names of the inputs, indexed by the `source_file` attribute of the points
+fn source_file_names(input_files: &[PathBuf]) -> Vec<String> {
+    input_files
+        .iter()
+        .map(|file| {
+            file.file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into()
+        })
+        .collect()
+}
+
+fn tileset_metadata(args: &Cli, input_files: &[PathBuf], point_count: u64) -> TilesetMetadata {
+    TilesetMetadata {
+        point_count,
+        source_files: source_file_names(input_files),
+        input_crs: args.input_crs(),
+        generator: format!("ptiler {}", env!("CARGO_PKG_VERSION")),
+        created_at: Local::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, false),
+    }
+}
+
+fn write_tileset(
+    output_path: &Path,
+    tile_contents: Vec<TileContent>,
+    args: &Cli,
+    metadata: TilesetMetadata,
+) -> std::io::Result<()> {
+    let root = match args.implicit_tiling() {
+        Some(implicit) => {
+            let (root, subtree_files) = implicit.build(tile_contents);
+            log::info!("write {} subtree files", subtree_files.len());
+            for subtree_file in subtree_files {
+                let subtree_path = output_path.join(&subtree_file.path);
+                fs::create_dir_all(subtree_path.parent().unwrap())?;
+                fs::write(subtree_path, subtree_file.data)?;
+            }
+            root
+        }
+        None => {
+            let mut tree = TileTree::new(
+                args.refine(),
+                args.geometric_error_model(),
+                args.tiling_scheme(),
+            );
+            for content in tile_contents {
+                tree.add_content(content);
+            }
+            match args.external_split() {
+                Some(split) => {
+                    let (root, externals) = tree.into_tileset_root_with_externals(split);
+                    log::info!("write {} external tilesets", externals.len());
+                    for external in externals {
+                        let external_path = output_path.join(&external.path);
+                        fs::create_dir_all(external_path.parent().unwrap())?;
+                        fs::write(
+                            external_path,
+                            serde_json::to_string_pretty(&external.tileset).unwrap(),
+                        )?;
+                    }
+                    root
+                }
+                None => tree.into_tileset_root(),
+            }
+        }
+    };
+
+    let tileset = cesiumtiles::tileset::Tileset {
+        asset: cesiumtiles::tileset::Asset {
+            version: "1.1".to_string(),
+            ..Default::default()
+        },
+        schema: Some(metadata_schema()),
+        groups: args.classification_groups.then(classification_groups),
+        metadata: Some(metadata.to_entity()),
+        geometric_error: root.geometric_error,
+        root,
+        extensions_used: args.tiling_scheme().extensions_used(),
+        ..Default::default()
+    };
+
+    let root_tileset_path = output_path.join(ROOT_TILESET_PATH);
+    log::info!("write tileset.json: {:?}", root_tileset_path);
+    fs::create_dir_all(root_tileset_path.parent().unwrap())?;
+    fs::write(
+        root_tileset_path,
+        serde_json::to_string_pretty(&tileset).unwrap(),
+    )?;
+    Ok(())
+}
+
 fn in_memory_workflow(
     input_files: Vec<PathBuf>,
     args: &Cli,
@@ -781,16 +2298,18 @@ fn in_memory_workflow(
 ) -> std::io::Result<()> {
     let extension = check_and_get_extension(&input_files).unwrap();
 
+    let color_corrections = color_corrections(args, &input_files, extension)?;
+    let colorization = colorization(args, &input_files, extension)?;
+    let orthophotos = Orthophotos::open(args, &input_files, extension)?;
+
     log::info!("start parse and transform and tiling...");
     let start_local = std::time::Instant::now();
 
-    let epsg_in = args.input_epsg;
-    let epsg_out = args.output_epsg;
-
     // Read multiple files in parallel
     let mut all_points: Vec<Point> = input_files
         .par_iter()
-        .flat_map(|file| {
+        .enumerate()
+        .map(|(file_index, file)| -> std::io::Result<Vec<Point>> {
             let mut reader: Box<dyn PointReader> = match extension {
                 Extension::Las | Extension::Laz => {
                     Box::new(LasPointReader::new(vec![file.clone()]).unwrap())
@@ -801,19 +2320,39 @@ fn in_memory_workflow(
             };
 
             let mut points = Vec::new();
-            while let Ok(Some(p)) = reader.next_point() {
+            while let Ok(Some(mut p)) = reader.next_point() {
+                p.attributes.source_file = Some(file_index as u32);
+                if let Some(corrections) = &color_corrections {
+                    corrections[file_index].apply(&mut p.color);
+                }
+                if let Some(colorization) = &colorization {
+                    colorization.apply(&mut p);
+                }
                 points.push(p);
             }
-            points
+            if let Some(orthophotos) = &orthophotos {
+                let colored = orthophotos.colorize(&mut points)?;
+                log::info!(
+                    "colored {} of {} points of {:?} from the orthophotos",
+                    colored,
+                    points.len(),
+                    file
+                );
+            }
+            Ok(points)
         })
-        .collect();
+        .try_reduce(Vec::new, |mut points, mut more| {
+            points.append(&mut more);
+            Ok(points)
+        })?;
 
     // Coordinate transformation
-    let mut transformer = PointTransformer::new(epsg_in, epsg_out, None)
+    let mut transformer = InputTransformer::new(args)
         .map_err(|e| std::io::Error::other(format!("Failed to create transformer: {e}")))?;
     transformer
         .transform_points_in_place(&mut all_points)
         .map_err(|e| std::io::Error::other(format!("Failed to transform points: {e}")))?;
+    let metadata = tileset_metadata(args, &input_files, all_points.len() as u64);
 
     log::info!(
         "Finish transforming and tiling in {:?}",
@@ -823,15 +2362,16 @@ fn in_memory_workflow(
     log::info!("start grouping...");
     let start_local = std::time::Instant::now();
     let max_zoom = args.max;
+    let subdivision = args.subdivision();
+    let scheme = args.tiling_scheme();
 
-    let map_init = || HashMap::<u64, Vec<Point>>::new();
-    let map_fold = |mut map: HashMap<u64, Vec<Point>>, p: Point| {
-        let (z, x, y) = tiling::scheme::zxy_from_lng_lat(max_zoom, p.x, p.y);
-        let tile_id = TileIdMethod::Hilbert.zxy_to_id(z, x, y);
-        map.entry(tile_id).or_default().push(p);
+    let map_init = || HashMap::<SortKey, Vec<Point>>::new();
+    let map_fold = |mut map: HashMap<SortKey, Vec<Point>>, p: Point| {
+        let tile = subdivision.zxyh_from_lng_lat_height(scheme.as_ref(), max_zoom, p.x, p.y, p.z);
+        map.entry(SortKey::from_tile(tile)).or_default().push(p);
         map
     };
-    let map_reduce = |mut a: HashMap<u64, Vec<Point>>, b: HashMap<u64, Vec<Point>>| {
+    let map_reduce = |mut a: HashMap<SortKey, Vec<Point>>, b: HashMap<SortKey, Vec<Point>>| {
         for (k, mut v) in b {
             a.entry(k).or_default().append(&mut v);
         }
@@ -848,28 +2388,24 @@ fn in_memory_workflow(
         tile_map.len(),
         start_local.elapsed()
     );
+    // Ancestors of a tile that implicit tiling can address are addressable too
+    if args.implicit {
+        for key in tile_map.keys() {
+            let (z, x, y, _) = key.to_tile();
+            check_implicit_tile((z, x, y)).map_err(std::io::Error::other)?;
+        }
+    }
 
     let tmp_tiled_file_dir_path = tempdir().unwrap();
 
     log::info!("start writing tile files...");
     let start_local = std::time::Instant::now();
-    tile_map
-        .into_par_iter()
-        .try_for_each(|(tile_id, points)| -> std::io::Result<()> {
-            let (z, x, y) = TileIdMethod::Hilbert.id_to_zxy(tile_id);
-            let tile = (z, x, y);
-            let points = maybe_decimate_points(tile, points, args.disable_decimation);
-
-            let tile_path = tmp_tiled_file_dir_path
-                .path()
-                .join(format!("{}/{}/{}.bin", z, x, y));
-            fs::create_dir_all(tile_path.parent().unwrap())?;
-            let file = File::create(tile_path)?;
-            let encoded = bitcode::encode(&points);
-            let mut writer = BufWriter::new(file);
-            writer.write_all(&encoded)?;
-            Ok(())
-        })?;
+    let leaf_tiling = args.leaf_tiling(scheme.as_ref());
+    let tiles = tile_map.keys().map(|key| key.to_tile()).collect::<Vec<_>>();
+    leaf_tiling.write_all(tmp_tiled_file_dir_path.path(), &tiles, |tile| {
+        Ok(tile_map.get(&SortKey::from_tile(tile)).cloned())
+    })?;
+    drop(tile_map);
 
     log::info!("Wrote tile files in {:?}", start_local.elapsed());
     log_directory_summary(
@@ -879,9 +2415,17 @@ fn in_memory_workflow(
 
     log::info!("start zoom aggregation...");
     let start_local = std::time::Instant::now();
-    for z in (args.min..max_zoom).rev() {
+    for z in (args.top_zoom()..args.deepest_zoom()).rev() {
         log::info!("aggregating zoom level: {}", z);
-        aggregate_zoom_level(tmp_tiled_file_dir_path.path(), z, args.disable_decimation)?;
+        aggregate_zoom_level(
+            tmp_tiled_file_dir_path.path(),
+            scheme.as_ref(),
+            z,
+            args.disable_decimation,
+            args.voxel_factor,
+            args.point_budget(),
+            args.additive,
+        )?;
         log_directory_summary(
             &format!("tile files after aggregating z={}", z),
             tmp_tiled_file_dir_path.path(),
@@ -892,18 +2436,28 @@ fn in_memory_workflow(
     log::info!("start exporting tiles (GLB)...");
     let start_local = std::time::Instant::now();
 
+    let input_attributes = output_attributes(args, &input_files, extension)?;
+    log::info!("output attributes: {:?}", input_attributes);
     let glb_options = GlbOptions {
-        quantize: args.quantize,
-        meshopt: args.meshopt,
+        quantize: args.quantize(),
+        meshopt: args.meshopt(),
+        color: args.color(),
+        draco: args.draco(),
         gzip_compress: args.gzip_compress,
+        normals: args.normals,
+        attributes: args.attributes.clone(),
+        feature_ids: args.feature_ids,
+        source_files: source_file_names(&input_files),
     };
 
     let tile_contents = export_tiles_to_glb(
         tmp_tiled_file_dir_path.path(),
         output_path,
-        args.min,
-        max_zoom,
+        args.top_zoom(),
+        args.deepest_zoom(),
+        args,
         &glb_options,
+        input_attributes,
     )?;
 
     log::info!("Finish exporting tiles in {:?}", start_local.elapsed());
@@ -912,25 +2466,7 @@ fn in_memory_workflow(
 
     drop(tmp_tiled_file_dir_path);
 
-    let mut tree = TileTree::default();
-    for content in tile_contents {
-        tree.add_content(content);
-    }
-    let tileset = cesiumtiles::tileset::Tileset {
-        asset: cesiumtiles::tileset::Asset {
-            version: "1.1".to_string(),
-            ..Default::default()
-        },
-        root: tree.into_tileset_root(),
-        geometric_error: 1e+100,
-        ..Default::default()
-    };
-    let root_tileset_path = output_path.join("tileset.json");
-    fs::create_dir_all(root_tileset_path.parent().unwrap())?;
-    fs::write(
-        root_tileset_path,
-        serde_json::to_string_pretty(&tileset).unwrap(),
-    )?;
+    write_tileset(output_path, tile_contents, args, metadata)?;
 
     Ok(())
 }
@@ -945,7 +2481,12 @@ fn external_sort_workflow(
     let start_local = std::time::Instant::now();
 
     let tmp_run_file_dir_path = tempdir().unwrap();
+    // Shard roots at `--min`, combined into overview tiles once all shards are done
+    let tmp_overview_dir_path = tempdir().unwrap();
     let mut tile_contents_all = Vec::new();
+    let mut point_count = 0u64;
+    let subdivision = args.subdivision();
+    let scheme = args.tiling_scheme();
 
     {
         let max_memory_mb: usize = args.max_memory_mb;
@@ -961,8 +2502,9 @@ fn external_sort_workflow(
         let num_cores = args.threads.filter(|&n| n > 0).unwrap_or(num_cpus::get());
 
         let extension = check_and_get_extension(&input_files).unwrap();
-        let epsg_in = args.input_epsg;
-        let epsg_out = args.output_epsg;
+        let color_corrections = color_corrections(args, &input_files, extension)?;
+        let colorization = colorization(args, &input_files, extension)?;
+        let orthophotos = Orthophotos::open(args, &input_files, extension)?.map(Arc::new);
 
         log::info!("memory budget: {}", format_size(max_memory_mb_bytes as u64));
         log::info!("reader chunk target: {}", format_size(one_chunk_mem as u64));
@@ -975,15 +2517,33 @@ fn external_sort_workflow(
         let chunk_size = input_files.len().div_ceil(num_cores);
         let mut handles = vec![];
 
-        for chunk in input_files.chunks(chunk_size) {
+        for (chunk_index, chunk) in input_files.chunks(chunk_size).enumerate() {
             let chunk = chunk.to_vec();
+            // The reader counts files from 0 within its chunk
+            let first_file_index = (chunk_index * chunk_size) as u32;
             let tx = tx.clone();
             let extension_copy = extension;
+            let args = args.clone();
+            let orthophotos = orthophotos.clone();
+            let color_corrections = color_corrections.clone();
 
-            let handle = thread::spawn(move || {
+            let handle = thread::spawn(move || -> std::io::Result<()> {
                 // Create a transformer per thread
-                let mut transformer = PointTransformer::new(epsg_in, epsg_out, None)
-                    .expect("Failed to create transformer");
+                let mut transformer = InputTransformer::new(&args).map_err(|e| {
+                    std::io::Error::other(format!("Failed to create transformer: {e}"))
+                })?;
+                let mut transform = |points: &mut [Point]| {
+                    transformer.transform_points_in_place(points).map_err(|e| {
+                        std::io::Error::other(format!("Failed to transform points: {e}"))
+                    })
+                };
+                // Orthophotos are sampled in the input CRS, before the transformation
+                let colorize = |points: &mut [Point]| -> std::io::Result<()> {
+                    if let Some(orthophotos) = &orthophotos {
+                        orthophotos.colorize(points)?;
+                    }
+                    Ok(())
+                };
 
                 let mut buffer = Vec::with_capacity(default_chunk_points_len);
                 let mut reader: Box<dyn PointReader> = match extension_copy {
@@ -995,13 +2555,24 @@ fn external_sort_workflow(
                     }
                 };
 
-                while let Ok(Some(p)) = reader.next_point() {
+                while let Ok(Some(mut p)) = reader.next_point() {
+                    p.attributes.source_file = p
+                        .attributes
+                        .source_file
+                        .map(|index| first_file_index + index);
+                    if let Some(corrections) = &color_corrections
+                        && let Some(index) = p.attributes.source_file
+                    {
+                        corrections[index as usize].apply(&mut p.color);
+                    }
+                    if let Some(colorization) = &colorization {
+                        colorization.apply(&mut p);
+                    }
                     buffer.push(p);
                     if buffer.len() >= default_chunk_points_len {
+                        colorize(&mut buffer)?;
                         // Transform coordinates in batch
-                        transformer
-                            .transform_points_in_place(&mut buffer)
-                            .expect("Failed to transform points");
+                        transform(&mut buffer)?;
                         let to_send = std::mem::replace(
                             &mut buffer,
                             Vec::with_capacity(default_chunk_points_len),
@@ -1012,12 +2583,12 @@ fn external_sort_workflow(
                     }
                 }
                 if !buffer.is_empty() {
+                    colorize(&mut buffer)?;
                     // Transform remaining points
-                    transformer
-                        .transform_points_in_place(&mut buffer)
-                        .expect("Failed to transform points");
+                    transform(&mut buffer)?;
                     let _ = tx.send(buffer);
                 }
+                Ok(())
             });
             handles.push(handle);
         }
@@ -1026,22 +2597,25 @@ fn external_sort_workflow(
         drop(tx);
 
         for (current_run_index, chunk) in rx.into_iter().enumerate() {
+            point_count += chunk.len() as u64;
             let mut shard_points = HashMap::<(u8, u32, u32), Vec<(SortKey, CompactPoint)>>::new();
 
             for p in chunk {
-                let shard = tiling::scheme::zxy_from_lng_lat(args.min, p.x, p.y);
-                let tile_coords = tiling::scheme::zxy_from_lng_lat(args.max, p.x, p.y);
-                let tile_id =
-                    TileIdMethod::Hilbert.zxy_to_id(tile_coords.0, tile_coords.1, tile_coords.2);
+                let shard = scheme.zxy_from_lng_lat(args.min, p.x, p.y);
+                let tile =
+                    subdivision.zxyh_from_lng_lat_height(scheme.as_ref(), args.max, p.x, p.y, p.z);
+                if args.implicit {
+                    check_implicit_tile((tile.0, tile.1, tile.2)).map_err(std::io::Error::other)?;
+                }
 
                 shard_points
                     .entry(shard)
                     .or_default()
-                    .push((SortKey { tile_id }, CompactPoint::from(p)));
+                    .push((SortKey::from_tile(tile), CompactPoint::from(p)));
             }
 
             for ((shard_z, shard_x, shard_y), mut keyed_points) in shard_points {
-                keyed_points.sort_by_key(|(k, _)| k.tile_id);
+                keyed_points.sort_by_key(|(k, _)| *k);
 
                 let run_file_path = tmp_run_file_dir_path.path().join(format!(
                     "{}/{}/{}/run_{}.bin",
@@ -1053,7 +2627,7 @@ fn external_sort_workflow(
         }
 
         for handle in handles {
-            handle.join().expect("Reading thread panicked");
+            handle.join().expect("Reading thread panicked")?;
         }
 
         log::info!(
@@ -1077,10 +2651,19 @@ fn external_sort_workflow(
             .flat_map(|(_, files)| files.iter())
             .map(|path| path.metadata().map(|m| m.len()).unwrap_or(0))
             .sum::<u64>();
+        let extension = check_and_get_extension(&input_files).unwrap();
+        let input_attributes = output_attributes(args, &input_files, extension)?;
+        log::info!("output attributes: {:?}", input_attributes);
         let glb_options = GlbOptions {
-            quantize: args.quantize,
-            meshopt: args.meshopt,
+            quantize: args.quantize(),
+            meshopt: args.meshopt(),
+            color: args.color(),
+            draco: args.draco(),
             gzip_compress: args.gzip_compress,
+            normals: args.normals,
+            attributes: args.attributes.clone(),
+            feature_ids: args.feature_ids,
+            source_files: source_file_names(&input_files),
         };
 
         for (index, (shard, run_files)) in shard_runs.into_iter().enumerate() {
@@ -1105,15 +2688,37 @@ fn external_sort_workflow(
             );
 
             let tmp_tiled_file_dir_path = tempdir().unwrap();
-            merge_shard_run_files(run_files, tmp_tiled_file_dir_path.path(), args.disable_decimation)?;
+            merge_shard_run_files(
+                run_files,
+                tmp_tiled_file_dir_path.path(),
+                &args.leaf_tiling(scheme.as_ref()),
+            )?;
             log_directory_summary(
                 "tile files after shard sort",
                 tmp_tiled_file_dir_path.path(),
             );
 
-            for z in (args.min..args.max).rev() {
-                aggregate_zoom_level(tmp_tiled_file_dir_path.path(), z, args.disable_decimation)
-                    .unwrap();
+            for z in (args.min..args.deepest_zoom()).rev() {
+                aggregate_zoom_level(
+                    tmp_tiled_file_dir_path.path(),
+                    scheme.as_ref(),
+                    z,
+                    args.disable_decimation,
+                    args.voxel_factor,
+                    args.point_budget(),
+                    args.additive,
+                )
+                .unwrap();
+            }
+
+            // Shard roots are exported together with the overview levels, after points for
+            // the overviews have been picked from them
+            if args.overview_zoom.is_some() {
+                move_tiles_for_zoom(
+                    tmp_tiled_file_dir_path.path(),
+                    tmp_overview_dir_path.path(),
+                    args.min,
+                )?;
             }
 
             log_directory_summary(
@@ -1131,8 +2736,10 @@ fn external_sort_workflow(
                 tmp_tiled_file_dir_path.path(),
                 output_path,
                 args.min,
-                args.max,
+                args.deepest_zoom(),
+                args,
                 &glb_options,
+                input_attributes,
             )
             .unwrap();
             tile_contents_all.extend(tile_contents);
@@ -1169,31 +2776,40 @@ fn external_sort_workflow(
 
         drop(tmp_run_file_dir_path);
 
-        log_directory_summary("glb output", output_path);
-
-        let mut tree = TileTree::default();
-        for content in tile_contents_all {
-            tree.add_content(content);
+        if let Some(overview_zoom) = args.overview_zoom {
+            log::info!("start building overview levels...");
+            let start_local = std::time::Instant::now();
+            // Shard roots still have their children in the output, so they are never leaves
+            for z in (overview_zoom..args.min).rev() {
+                aggregate_zoom_level(
+                    tmp_overview_dir_path.path(),
+                    scheme.as_ref(),
+                    z,
+                    args.disable_decimation,
+                    args.voxel_factor,
+                    None,
+                    args.additive,
+                )?;
+            }
+            // Includes the shard roots at `--min`, which the shards do not export themselves
+            let tile_contents = export_tiles_to_glb(
+                tmp_overview_dir_path.path(),
+                output_path,
+                overview_zoom,
+                args.min,
+                args,
+                &glb_options,
+                input_attributes,
+            )?;
+            tile_contents_all.extend(tile_contents);
+            log::info!("Finish overview levels in {:?}", start_local.elapsed());
         }
+        drop(tmp_overview_dir_path);
 
-        let tileset = cesiumtiles::tileset::Tileset {
-            asset: cesiumtiles::tileset::Asset {
-                version: "1.1".to_string(),
-                ..Default::default()
-            },
-            root: tree.into_tileset_root(),
-            geometric_error: 1e+100,
-            ..Default::default()
-        };
+        log_directory_summary("glb output", output_path);
 
-        let root_tileset_path = output_path.join("tileset.json");
-        log::info!("write tileset.json: {:?}", root_tileset_path);
-        fs::create_dir_all(root_tileset_path.parent().unwrap()).unwrap();
-        fs::write(
-            root_tileset_path,
-            serde_json::to_string_pretty(&tileset).unwrap(),
-        )
-        .unwrap();
+        let metadata = tileset_metadata(args, &input_files, point_count);
+        write_tileset(output_path, tile_contents_all, args, metadata)?;
     }
     Ok(())
 }
@@ -1212,7 +2828,7 @@ fn main() -> std::io::Result<()> {
         .filter(None, LevelFilter::Info)
         .init();
 
-    let args = Cli::parse();
+    let mut args = Cli::parse();
 
     let thread_count = args
         .threads
@@ -1226,16 +2842,59 @@ fn main() -> std::io::Result<()> {
     log::info!("rayon threads: {}", thread_count);
     log::info!("input files: {:?}", args.input);
     log::info!("output folder: {}", args.output);
-    log::info!("input EPSG: {}", args.input_epsg);
+    if let Some(epsg) = args.input_epsg {
+        log::info!("input EPSG: {}", epsg);
+    }
+    if let Some(frame) = args.local_frame() {
+        log::info!("input local frame: {:?}", frame);
+    }
     log::info!("output EPSG: {}", args.output_epsg);
-    log::info!("min zoom: {}", args.min);
-    log::info!("max zoom: {}", args.max);
+    if args.auto_zoom {
+        log::info!("zoom range: auto");
+    } else {
+        log::info!("min zoom: {}", args.min);
+        log::info!("max zoom: {}", args.max);
+    }
+    log::info!("overview zoom: {:?}", args.overview_zoom);
     log::info!("max memory mb: {}", args.max_memory_mb);
     log::info!("threads: {:?}", args.threads);
-    log::info!("quantize: {}", args.quantize);
+    log::info!("quantize: {:?}", args.quantize());
     log::info!("gzip compress: {}", args.gzip_compress);
-    log::info!("meshopt: {}", args.meshopt);
+    log::info!("meshopt: {:?}", args.meshopt());
+    log::info!("draco: {:?}", args.draco());
+    log::info!("color: {:?}", args.color());
     log::info!("disable decimation: {}", args.disable_decimation);
+    log::info!("voxel factor: {}", args.voxel_factor);
+    log::info!("geometric error model: {:?}", args.geometric_error_model());
+    log::info!("octree: {}", args.octree);
+    log::info!("tiling scheme: {:?}", args.tiling_scheme());
+    log::info!("point budget: {:?}", args.point_budget);
+    log::info!("implicit tiling: {}", args.implicit);
+    log::info!("external tilesets: {}", args.external_tilesets);
+    log::info!("bounding volume: {:?}", args.bounding_volume);
+    log::info!("classification groups: {}", args.classification_groups);
+    log::info!("color balance: {}", args.color_balance);
+    log::info!("colorize: {:?}", args.colorize);
+    log::info!("orthophotos: {:?}", args.orthophoto);
+    if !args.orthophoto.is_empty() {
+        log::info!("orthophoto EPSG: {:?}", args.orthophoto_epsg);
+        log::info!("orthophoto overwrite: {}", args.orthophoto_overwrite);
+    }
+    if args.colorize.is_some() {
+        log::info!("color ramp: {:?}", args.color_ramp);
+        log::info!("elevation range: {:?}", args.elevation_range);
+        log::info!("intensity percentiles: {:?}", args.intensity_percentiles);
+    }
+    log::info!("normals: {}", args.normals);
+    if args.normals {
+        log::info!("normal neighbours: {}", args.normal_neighbours);
+        log::info!("normal viewpoint: {:?}", args.normal_viewpoint);
+    }
+    log::info!("point attributes: {:?}", args.attributes);
+    log::info!("feature IDs: {:?}", args.feature_ids);
+    if args.implicit {
+        log::info!("subtree levels: {}", args.subtree_levels);
+    }
 
     let start = std::time::Instant::now();
 
@@ -1247,6 +2906,41 @@ fn main() -> std::io::Result<()> {
     std::fs::create_dir_all(&output_path).unwrap();
 
     let extension = check_and_get_extension(&input_files).unwrap();
+
+    if let Some(epsg) = args.grid_epsg
+        && ProjectedGrid::japan_map_sheets(epsg).is_none()
+    {
+        return Err(std::io::Error::other(format!(
+            "--grid-epsg {epsg} is not a Japan Plane Rectangular CS zone"
+        )));
+    }
+    if args.implicit && args.tiling_scheme != TilingSchemeKind::Geographic {
+        return Err(std::io::Error::other(
+            "--implicit requires the geographic tiling scheme",
+        ));
+    }
+
+    if args.auto_zoom {
+        let extent = read_input_extent(&input_files, extension)?;
+        let spacing = sample_point_spacing(&input_files, extension, &args)?;
+        (args.min, args.max) = estimate_zoom_range(&extent, spacing, &args)?;
+        log::info!("auto min zoom: {}", args.min);
+        log::info!("auto max zoom: {}", args.max);
+    }
+
+    if let Some(overview_zoom) = args.overview_zoom
+        && overview_zoom >= args.min
+    {
+        return Err(std::io::Error::other(
+            "--overview-zoom must be lower than --min",
+        ));
+    }
+    if args.implicit && args.top_zoom() < IMPLICIT_ROOT_ZOOM {
+        return Err(std::io::Error::other(format!(
+            "--implicit requires --min {IMPLICIT_ROOT_ZOOM} or higher"
+        )));
+    }
+
     let total_size = estimate_total_size(&input_files);
     let processing_size = estimate_processing_size(&input_files, extension);
     let max_memory_bytes = args.max_memory_mb as u64 * 1024 * 1024;
@@ -1295,15 +2989,24 @@ mod tests {
                 user_data: None,
                 point_source_id: None,
                 gps_time: None,
+                source_file: None,
+                point_index: None,
+                normal: None,
             },
         }
     }
 
     #[test]
     fn maybe_decimate_points_can_be_disabled() {
-        let tile = (18, 0, 0);
+        let tile = (18, 0, 0, 0);
         let points = vec![point(1.0, 2.0, 3.0), point(4.0, 5.0, 6.0)];
-        let decimated = maybe_decimate_points(tile, points.clone(), true);
+        let decimated = maybe_decimate_points(
+            &Geographic,
+            tile,
+            points.clone(),
+            true,
+            DEFAULT_VOXEL_FACTOR,
+        );
         assert_eq!(decimated.len(), points.len());
         assert_eq!(decimated[0].x, points[0].x);
         assert_eq!(decimated[1].x, points[1].x);
@@ -1318,26 +3021,656 @@ mod tests {
             PathBuf::from("/tmp/18/11/21.bin"),
         ];
 
-        let grouped = group_child_files_by_parent(child_files, 17);
+        let grouped = group_child_files_by_parent(&Geographic, child_files, 17);
         assert_eq!(grouped.len(), 1);
-        let files = grouped.get(&(17, 5, 10)).unwrap();
+        let files = grouped.get(&(17, 5, 10, 0)).unwrap();
         assert_eq!(files.len(), 4);
     }
 
+    #[test]
+    fn group_child_files_by_parent_splits_octree_heights() {
+        let dir = Path::new("/tmp");
+        let child_files = vec![
+            tile_file_path(dir, (18, 10, 20, 64)),
+            tile_file_path(dir, (18, 11, 20, 65)),
+            tile_file_path(dir, (18, 10, 21, 66)),
+        ];
+        assert_eq!(extract_tile_coords(&child_files[1]), (18, 11, 20, 65));
+
+        let grouped = group_child_files_by_parent(&Geographic, child_files, 17);
+        assert_eq!(grouped.len(), 2);
+        assert_eq!(grouped.get(&(17, 5, 10, 32)).unwrap().len(), 2);
+        assert_eq!(grouped.get(&(17, 5, 10, 33)).unwrap().len(), 1);
+    }
+
     #[test]
     fn aggregate_zoom_level_without_decimation_preserves_all_points() {
         let dir = tempdir().unwrap();
-        write_points_to_tile(dir.path(), (18, 10, 20), &[point(1.0, 1.0, 1.0)]).unwrap();
-        write_points_to_tile(dir.path(), (18, 10, 21), &[point(2.0, 2.0, 2.0)]).unwrap();
-        write_points_to_tile(dir.path(), (18, 11, 20), &[point(3.0, 3.0, 3.0)]).unwrap();
-        write_points_to_tile(dir.path(), (18, 11, 21), &[point(4.0, 4.0, 4.0)]).unwrap();
-
-        aggregate_zoom_level(dir.path(), 17, true).unwrap();
+        write_points_to_tile(dir.path(), (18, 10, 20, 0), &[point(1.0, 1.0, 1.0)]).unwrap();
+        write_points_to_tile(dir.path(), (18, 10, 21, 0), &[point(2.0, 2.0, 2.0)]).unwrap();
+        write_points_to_tile(dir.path(), (18, 11, 20, 0), &[point(3.0, 3.0, 3.0)]).unwrap();
+        write_points_to_tile(dir.path(), (18, 11, 21, 0), &[point(4.0, 4.0, 4.0)]).unwrap();
+
+        aggregate_zoom_level(
+            dir.path(),
+            &Geographic,
+            17,
+            true,
+            DEFAULT_VOXEL_FACTOR,
+            None,
+            false,
+        )
+        .unwrap();
 
         let parent_points = read_points_from_tile(&dir.path().join("17/5/10.bin")).unwrap();
         assert_eq!(parent_points.len(), 4);
     }
 
+    #[test]
+    fn aggregate_zoom_level_merges_children_within_point_budget() {
+        let dir = tempdir().unwrap();
+        let dense = (0..10)
+            .map(|i| point(i as f64, 0.0, 0.0))
+            .collect::<Vec<_>>();
+        // Sparse parent 17/5/10 with two small children
+        write_points_to_tile(dir.path(), (18, 10, 20, 0), &[point(1.0, 1.0, 1.0)]).unwrap();
+        write_points_to_tile(dir.path(), (18, 11, 21, 0), &[point(2.0, 2.0, 2.0)]).unwrap();
+        // Dense parent 17/4/10 exceeding the budget
+        write_points_to_tile(dir.path(), (18, 8, 20, 0), &dense).unwrap();
+
+        let budget = Some(PointBudget::new(5));
+        aggregate_zoom_level(
+            dir.path(),
+            &Geographic,
+            17,
+            true,
+            DEFAULT_VOXEL_FACTOR,
+            budget,
+            false,
+        )
+        .unwrap();
+
+        assert!(!dir.path().join("18/10/20.bin").exists());
+        assert!(!dir.path().join("18/11/21.bin").exists());
+        assert!(dir.path().join("18/8/20.bin").exists());
+        let sparse = read_points_from_tile(&dir.path().join("17/5/10.bin")).unwrap();
+        assert_eq!(sparse.len(), 2);
+
+        // 17/4/10 was split, so their parent cannot absorb them even though 17/5/10 is small
+        aggregate_zoom_level(
+            dir.path(),
+            &Geographic,
+            16,
+            true,
+            DEFAULT_VOXEL_FACTOR,
+            budget,
+            false,
+        )
+        .unwrap();
+        assert!(dir.path().join("17/4/10.bin").exists());
+        assert!(dir.path().join("17/5/10.bin").exists());
+    }
+
+    #[test]
+    fn dense_leaves_are_split_below_max_zoom_and_aggregated_back() {
+        let dir = tempdir().unwrap();
+        let (z, x, y) = Geographic.zxy_from_lng_lat(18, 139.7, 35.6);
+        let (west, south, east, north) = Geographic.bounds(z, x, y);
+        // Two clusters in opposite corners of the tile
+        let points = (0..6)
+            .map(|i| {
+                let t = if i < 3 { 0.1 } else { 0.9 };
+                point(west + (east - west) * t, south + (north - south) * t, 0.0)
+            })
+            .collect::<Vec<_>>();
+        let leaf_tiling = LeafTiling {
+            scheme: &Geographic,
+            subdivision: Subdivision::Quadtree,
+            point_budget: Some(PointBudget::new(4)),
+            disable_decimation: true,
+            voxel_factor: DEFAULT_VOXEL_FACTOR,
+            normals: None,
+        };
+        leaf_tiling
+            .write(dir.path(), (z, x, y, 0), points, &[])
+            .unwrap();
+
+        assert!(get_tile_list_for_zoom(dir.path(), 18).is_empty());
+        let children = get_tile_list_for_zoom(dir.path(), 19);
+        assert_eq!(children.len(), 2);
+
+        aggregate_zoom_level(
+            dir.path(),
+            &Geographic,
+            18,
+            true,
+            DEFAULT_VOXEL_FACTOR,
+            leaf_tiling.point_budget,
+            false,
+        )
+        .unwrap();
+        // The split tile is rebuilt from its children, which are kept
+        let parent = read_points_from_tile(&tile_file_path(dir.path(), (z, x, y, 0))).unwrap();
+        assert_eq!(parent.len(), 6);
+        assert!(children.iter().all(|child| child.exists()));
+    }
+
+    #[test]
+    fn leaf_normals_are_estimated_once_and_kept_by_parents() {
+        let dir = tempdir().unwrap();
+        // Flat ground of 0.5 m spacing across the z=22 tiles of a z=21 tile
+        let (z, x, y) = Geographic.zxy_from_lng_lat(21, 139.7, 35.6);
+        let (west, south, east, north) = Geographic.bounds(z, x, y);
+        let (columns, rows) = (40, 40);
+        let mut tiles = HashMap::<TileZXYH, Vec<Point>>::new();
+        for i in 0..columns * rows {
+            let lng = west + (east - west) * ((i % columns) as f64 + 0.5) / columns as f64;
+            let lat = south + (north - south) * ((i / columns) as f64 + 0.5) / rows as f64;
+            let mut p = point(lng, lat, 0.0);
+            p.attributes.point_index = Some(i as u64);
+            let (z, x, y) = Geographic.zxy_from_lng_lat(22, lng, lat);
+            tiles.entry((z, x, y, 0)).or_default().push(p);
+        }
+        assert_eq!(tiles.len(), 4);
+        let leaf_tiling = LeafTiling {
+            scheme: &Geographic,
+            subdivision: Subdivision::Quadtree,
+            point_budget: None,
+            disable_decimation: false,
+            voxel_factor: 1.0,
+            normals: Some(LeafNormals {
+                neighbours: 8,
+                orientation: NormalOrientation::AwayFrom([0.0; 3]),
+            }),
+        };
+        let leaves = tiles.keys().copied().collect::<Vec<_>>();
+        leaf_tiling
+            .write_all(dir.path(), &leaves, |tile| Ok(tiles.get(&tile).cloned()))
+            .unwrap();
+
+        // Every leaf point, edges included, faces the ellipsoid normal
+        let mut leaf_normals = HashMap::new();
+        for leaf in &leaves {
+            for p in read_points_from_tile(&tile_file_path(dir.path(), *leaf)).unwrap() {
+                let (sin_lat, cos_lat) = p.y.to_radians().sin_cos();
+                let (sin_lng, cos_lng) = p.x.to_radians().sin_cos();
+                let up = [cos_lat * cos_lng, cos_lat * sin_lng, sin_lat];
+                let normal = p.attributes.normal.unwrap().map(f64::from);
+                let dot = (0..3).map(|axis| up[axis] * normal[axis]).sum::<f64>();
+                assert!(dot > 0.999, "{dot}");
+                leaf_normals.insert(p.attributes.point_index.unwrap(), normal);
+            }
+        }
+
+        // Voxels of about 1 m at z=21 make the parent keep some of the points
+        aggregate_zoom_level(dir.path(), &Geographic, 21, false, 1.0, None, false).unwrap();
+        let parent = read_points_from_tile(&tile_file_path(dir.path(), (z, x, y, 0))).unwrap();
+        assert!(!parent.is_empty() && parent.len() < leaf_normals.len());
+        for p in parent {
+            let normal = p.attributes.normal.unwrap().map(f64::from);
+            assert_eq!(normal, leaf_normals[&p.attributes.point_index.unwrap()]);
+        }
+    }
+
+    #[test]
+    fn adjacent_tiles_surround_the_tile() {
+        let (z, x, y) = Geographic.zxy_from_lng_lat(18, 139.7, 35.6);
+        let adjacent = adjacent_tiles(&Geographic, Subdivision::Quadtree, (z, x, y, 0));
+        assert_eq!(adjacent.len(), 8);
+        for (az, ax, ay, ah) in &adjacent {
+            assert_eq!((*az, *ah), (z, 0));
+            assert!(ax.abs_diff(x) <= 1 && ay.abs_diff(y) <= 1);
+        }
+        let adjacent = adjacent_tiles(&Geographic, Subdivision::Octree, (z, x, y, 0));
+        assert_eq!(adjacent.len(), 3 * 3 * 2 - 1);
+    }
+
+    #[test]
+    fn additive_aggregation_moves_selected_points_to_parent() {
+        let dir = tempdir().unwrap();
+        // Two points in the same z=17 voxel and one far away in another child
+        let base = (139.7, 35.6);
+        let near = [
+            point(base.0, base.1, 0.0),
+            point(base.0 + 1e-7, base.1, 0.0),
+        ];
+        let tile =
+            Subdivision::Quadtree.zxyh_from_lng_lat_height(&Geographic, 18, base.0, base.1, 0.0);
+        let (_, x, y, _) = tile;
+        let sibling = (18, x ^ 1, y, 0);
+        let (west, east) = pcd_exporter::tiling::scheme::x_slice_range(18, sibling.1 as i32, 1);
+        let far = point((west + east) * 0.5, base.1, 0.0);
+        write_points_to_tile(dir.path(), tile, &near).unwrap();
+        write_points_to_tile(dir.path(), sibling, &[far]).unwrap();
+
+        aggregate_zoom_level(
+            dir.path(),
+            &Geographic,
+            17,
+            false,
+            DEFAULT_VOXEL_FACTOR,
+            None,
+            true,
+        )
+        .unwrap();
+
+        let parent = read_points_from_tile(&tile_file_path(dir.path(), (17, x / 2, y / 2, 0)));
+        assert_eq!(parent.unwrap().len(), 2);
+        // One of the near points stays in the child, the far one moved up entirely
+        let child = read_points_from_tile(&tile_file_path(dir.path(), tile));
+        assert_eq!(child.unwrap().len(), 1);
+        assert!(!tile_file_path(dir.path(), sibling).exists());
+    }
+
+    #[test]
+    fn overview_levels_combine_moved_shard_roots() {
+        let shard_a = tempdir().unwrap();
+        let shard_b = tempdir().unwrap();
+        let overview = tempdir().unwrap();
+        write_points_to_tile(shard_a.path(), (15, 100, 40, 0), &[point(1.0, 1.0, 1.0)]).unwrap();
+        write_points_to_tile(shard_a.path(), (16, 200, 80, 0), &[point(1.0, 1.0, 1.0)]).unwrap();
+        write_points_to_tile(shard_b.path(), (15, 101, 41, 0), &[point(2.0, 2.0, 2.0)]).unwrap();
+
+        move_tiles_for_zoom(shard_a.path(), overview.path(), 15).unwrap();
+        move_tiles_for_zoom(shard_b.path(), overview.path(), 15).unwrap();
+        assert!(get_tile_list_for_zoom(shard_a.path(), 15).is_empty());
+        assert!(get_tile_list_for_zoom(overview.path(), 16).is_empty());
+
+        aggregate_zoom_level(
+            overview.path(),
+            &Geographic,
+            14,
+            true,
+            DEFAULT_VOXEL_FACTOR,
+            None,
+            false,
+        )
+        .unwrap();
+        let points = read_points_from_tile(&overview.path().join("14/50/20.bin")).unwrap();
+        assert_eq!(points.len(), 2);
+    }
+
+    #[test]
+    fn local_origin_replaces_input_epsg() {
+        let args = Cli::try_parse_from([
+            "ptiler",
+            "-i",
+            "scan.las",
+            "-o",
+            "out",
+            "--output-epsg",
+            "4979",
+            "--local-origin",
+            "139.7,35.6,-12.5",
+            "--local-heading",
+            "30",
+        ])
+        .unwrap();
+        assert!(args.input_epsg.is_none());
+        let frame = args.local_frame().unwrap();
+        assert_eq!(frame.origin_lng, 139.7);
+        assert_eq!(frame.origin_height, -12.5);
+        assert_eq!(frame.heading, 30.0);
+        assert_eq!(frame.scale, 1.0);
+        assert_eq!(args.input_crs(), "local frame at 139.7, 35.6, -12.5");
+
+        let mut with_orthophoto = vec![
+            "ptiler",
+            "-i",
+            "scan.las",
+            "-o",
+            "out",
+            "--output-epsg",
+            "4979",
+            "--local-origin",
+            "139.7,35.6,-12.5",
+        ];
+        with_orthophoto.extend(["--orthophoto", "ortho.tif"]);
+        assert!(Cli::try_parse_from(with_orthophoto).is_err());
+
+        let missing_crs = ["ptiler", "-i", "a", "-o", "o", "--output-epsg", "4979"];
+        assert!(Cli::try_parse_from(missing_crs).is_err());
+        assert!(parse_local_origin("139.7,35.6").is_err());
+    }
+
+    #[test]
+    fn auto_zoom_range_follows_extent_size_and_sampled_spacing() {
+        // A diagonal corridor across 135°E, a tile boundary from z=3 down, with points about
+        // 0.5 m apart
+        let (west, south) = (134.95, 34.6);
+        let corridor = (0..2000)
+            .map(|i| {
+                let t = f64::from(i) * 0.000_005;
+                point(west + t, south + t, 50.0)
+            })
+            .collect::<Vec<_>>();
+        let (east, north) = (corridor[1999].x, corridor[1999].y);
+        let spacing = median(nearest_neighbour_distances(&local_positions(&corridor))).unwrap();
+        assert!((0.5..0.8).contains(&spacing), "{spacing}");
+        // The bounding box area per point is far larger than the spacing along the corridor
+        assert!(average_point_spacing(west, east, south, north, corridor.len()) > 10.0 * spacing);
+
+        let (min_zoom, max_zoom) =
+            zoom_range(&Geographic, (134.95, 34.6, 135.05, 34.7), spacing, 1.0);
+        assert_eq!(min_zoom, 11);
+        assert!(max_zoom >= 18, "{max_zoom}");
+    }
+
+    #[test]
+    fn auto_zoom_conflicts_with_explicit_zoom_range() {
+        let base = [
+            "ptiler",
+            "-i",
+            "scan.las",
+            "-o",
+            "out",
+            "--input-epsg",
+            "6677",
+            "--output-epsg",
+            "4979",
+        ];
+        let parse = |extra: &[&str]| Cli::try_parse_from(base.iter().chain(extra));
+        assert!(parse(&["--auto-zoom"]).unwrap().auto_zoom);
+        assert!(parse(&["--auto-zoom", "--max", "20"]).is_err());
+        assert!(parse(&["--auto-zoom", "--min", "12"]).is_err());
+
+        let args = parse(&["--attributes", "intensity,gps-time"]).unwrap();
+        assert_eq!(
+            args.attributes,
+            [PointAttribute::Intensity, PointAttribute::GpsTime]
+        );
+        assert!(parse(&["--attributes", "rgb"]).is_err());
+        let args = parse(&["--draco", "--draco-position-bits", "11"]).unwrap();
+        assert_eq!(
+            args.draco(),
+            Some(DracoOptions {
+                position_bits: 11,
+                color_bits: 8
+            })
+        );
+        assert!(parse(&["--draco", "--meshopt"]).is_err());
+        let args = parse(&[
+            "--quantize",
+            "--quantize-bits",
+            "10",
+            "--quantize-signed",
+            "--quantize-tolerance-mm",
+            "2.5",
+        ])
+        .unwrap();
+        assert_eq!(
+            args.quantize(),
+            Some(QuantizeOptions {
+                bits: 10,
+                signed: true,
+                tolerance_mm: Some(2.5)
+            })
+        );
+        assert!(parse(&["--quantize", "--quantize-bits", "12"]).is_err());
+        assert!(parse(&["--quantize-signed"]).is_err());
+        let args = parse(&[
+            "--color-bits",
+            "16",
+            "--color-space",
+            "srgb",
+            "--color-alpha",
+            "0.8",
+        ])
+        .unwrap();
+        assert_eq!(
+            args.color(),
+            ColorOptions {
+                bits: 16,
+                space: ColorSpace::Srgb,
+                alpha: Some(0.8)
+            }
+        );
+        assert_eq!(parse(&[]).unwrap().color(), ColorOptions::default());
+        assert!(parse(&["--color-bits", "10"]).is_err());
+        assert!(parse(&["--color-alpha", "1.5"]).is_err());
+        assert!(parse(&["--color-alpha", "0.5", "--draco"]).is_err());
+        let args = parse(&["--meshopt", "--meshopt-filters"]).unwrap();
+        assert_eq!(args.meshopt(), Some(MeshoptOptions { filters: true }));
+        assert!(parse(&["--meshopt-filters"]).is_err());
+        assert!(parse(&["--draco", "--draco-color-bits", "31"]).is_err());
+        let args = parse(&["--normals", "--normal-viewpoint", "139.7,35.6,-2"]).unwrap();
+        assert_eq!(args.normal_neighbours, DEFAULT_NORMAL_NEIGHBOURS);
+        assert_eq!(args.normal_viewpoint, Some([139.7, 35.6, -2.0]));
+        assert!(parse(&["--normal-neighbours", "8"]).is_err());
+        let args = parse(&["--colorize", "elevation", "--elevation-range=-5,120.5"]).unwrap();
+        assert_eq!(args.colorize, Some(ColorizeMode::Elevation));
+        assert_eq!(args.color_ramp, ColorRamp::Viridis);
+        assert_eq!(args.elevation_range, Some([-5.0, 120.5]));
+        assert_eq!(args.intensity_percentiles, None);
+        let args = parse(&[
+            "--colorize",
+            "intensity",
+            "--intensity-percentiles",
+            "1,99.5",
+        ])
+        .unwrap();
+        assert_eq!(args.intensity_percentiles, Some([1.0, 99.5]));
+        assert!(parse(&["--colorize", "height"]).is_err());
+        assert!(parse(&["--color-ramp", "terrain"]).is_err());
+        assert!(parse(&["--colorize", "elevation", "--elevation-range", "10,5"]).is_err());
+        assert!(
+            parse(&[
+                "--colorize",
+                "intensity",
+                "--intensity-percentiles",
+                "5,101"
+            ])
+            .is_err()
+        );
+        let args = parse(&[
+            "--orthophoto",
+            "east.tif",
+            "--orthophoto",
+            "west.tif",
+            "--orthophoto-epsg",
+            "6677",
+        ])
+        .unwrap();
+        assert_eq!(args.orthophoto, ["east.tif", "west.tif"]);
+        assert_eq!(args.orthophoto_epsg, Some(6677));
+        assert!(!args.orthophoto_overwrite);
+        assert!(parse(&["--orthophoto-overwrite"]).is_err());
+        assert!(parse(&["--color-balance"]).unwrap().color_balance);
+        assert!(parse(&["--color-balance", "--colorize", "intensity"]).is_err());
+        let args = parse(&["--feature-ids", "point-source-id"]).unwrap();
+        assert_eq!(args.feature_ids, Some(FeatureIdSource::PointSourceId));
+        let args = parse(&["--feature-ids", "point"]).unwrap();
+        assert_eq!(args.feature_ids, Some(FeatureIdSource::Point));
+    }
+
+    #[test]
+    fn geometric_error_overrides_are_collected_by_zoom() {
+        let args = Cli::try_parse_from([
+            "ptiler",
+            "-i",
+            "scan.las",
+            "-o",
+            "out",
+            "--input-epsg",
+            "6677",
+            "--output-epsg",
+            "4979",
+            "--geometric-error-override",
+            "15=200",
+            "--geometric-error-override",
+            "0=1e6",
+        ])
+        .unwrap();
+        let model = args.geometric_error_model();
+        assert_eq!(model.overrides.get(&15), Some(&200.0));
+        assert_eq!(model.overrides.get(&0), Some(&1e6));
+        assert!(parse_geometric_error_override("15:200").is_err());
+    }
+
+    /// Writes an uncompressed 8-bit RGB GeoTIFF in EPSG:6677 whose top left corner is at
+    /// (`origin`, `origin`), with 1 m pixels and black as nodata
+    fn write_geotiff(path: &Path, width: u32, pixels: &[[u8; 3]], origin: f64) {
+        let height = pixels.len() as u32 / width;
+        let data = pixels.concat();
+        let mut entries: Vec<(u16, u16, u32, Vec<u8>)> = Vec::new();
+        let short = |values: &[u16]| values.iter().flat_map(|v| v.to_le_bytes()).collect();
+        let long = |value: u32| value.to_le_bytes().to_vec();
+        let double = |values: &[f64]| values.iter().flat_map(|v| v.to_le_bytes()).collect();
+        entries.push((256, 4, 1, long(width)));
+        entries.push((257, 4, 1, long(height)));
+        entries.push((258, 3, 3, short(&[8, 8, 8])));
+        entries.push((259, 3, 1, short(&[1])));
+        entries.push((262, 3, 1, short(&[2])));
+        entries.push((273, 4, 1, long(8)));
+        entries.push((277, 3, 1, short(&[3])));
+        entries.push((278, 4, 1, long(height)));
+        entries.push((279, 4, 1, long(data.len() as u32)));
+        entries.push((33550, 12, 3, double(&[1.0, 1.0, 0.0])));
+        entries.push((33922, 12, 6, double(&[0.0, 0.0, 0.0, origin, origin, 0.0])));
+        entries.push((34735, 3, 8, short(&[1, 1, 0, 1, 3072, 0, 1, 6677])));
+        entries.push((42113, 2, 2, b"0\0".to_vec()));
+
+        let ifd_offset = 8 + data.len().next_multiple_of(2);
+        let mut values_offset = ifd_offset + 2 + entries.len() * 12 + 4;
+        let mut ifd = (entries.len() as u16).to_le_bytes().to_vec();
+        let mut values = Vec::new();
+        for (tag, field_type, count, bytes) in entries {
+            ifd.extend(tag.to_le_bytes());
+            ifd.extend(field_type.to_le_bytes());
+            ifd.extend(count.to_le_bytes());
+            if bytes.len() <= 4 {
+                ifd.extend(&bytes);
+                ifd.resize(ifd.len() + 4 - bytes.len(), 0);
+            } else {
+                ifd.extend((values_offset as u32).to_le_bytes());
+                values_offset += bytes.len();
+                values.extend(bytes);
+            }
+        }
+        ifd.extend(0u32.to_le_bytes());
+
+        let mut tiff = b"II".to_vec();
+        tiff.extend(42u16.to_le_bytes());
+        tiff.extend((ifd_offset as u32).to_le_bytes());
+        tiff.extend(&data);
+        tiff.resize(ifd_offset, 0);
+        tiff.extend(ifd);
+        tiff.extend(values);
+        fs::write(path, tiff).unwrap();
+    }
+
+    #[test]
+    fn orthophotos_color_points_without_colors_of_their_own() {
+        let dir = tempdir().unwrap();
+        // Red and blue pixels over nodata, next to a green image
+        let east = dir.path().join("east.tif");
+        write_geotiff(
+            &east,
+            2,
+            &[[200, 0, 0], [0, 0, 200], [0, 0, 0], [0, 0, 0]],
+            0.0,
+        );
+        let west = dir.path().join("west.tif");
+        write_geotiff(&west, 1, &[[0, 100, 0]; 4], 0.0);
+        let open = |path: &Path| Orthophoto {
+            image: GeoTiff::open(path, 1 << 20).unwrap(),
+            transformer: None,
+        };
+        let mut orthophotos = Orthophotos {
+            images: vec![open(&east), open(&west)],
+            file_colors: vec![false, true],
+            overwrite: false,
+        };
+        assert_eq!(orthophotos.images[0].image.bounds(), [0.0, -2.0, 2.0, 0.0]);
+
+        let points = || {
+            [
+                (0.5, -0.5, 0),
+                (1.0, -0.5, 0),
+                (0.5, -1.5, 0),
+                (5.0, 5.0, 0),
+                (0.5, -0.5, 1),
+            ]
+            .map(|(x, y, file)| {
+                let mut point = point(x, y, 0.0);
+                point.color = Color { r: 1, g: 2, b: 3 };
+                point.attributes.source_file = Some(file);
+                point
+            })
+        };
+        let rgb = |point: &Point| [point.color.r, point.color.g, point.color.b];
+
+        let mut colored = points();
+        assert_eq!(orthophotos.colorize(&mut colored).unwrap(), 3);
+        // Pixel centre, then halfway between the red and the blue pixel
+        assert_eq!(rgb(&colored[0]), [51400, 0, 0]);
+        assert_eq!(rgb(&colored[1]), [25700, 0, 25700]);
+        // Nodata in the first image falls through to the second
+        assert_eq!(rgb(&colored[2]), [0, 25700, 0]);
+        // Outside every image, and a point with its own color
+        assert_eq!(rgb(&colored[3]), [1, 2, 3]);
+        assert_eq!(rgb(&colored[4]), [1, 2, 3]);
+
+        orthophotos.overwrite = true;
+        let mut colored = points();
+        assert_eq!(orthophotos.colorize(&mut colored).unwrap(), 4);
+        assert_eq!(rgb(&colored[4]), [51400, 0, 0]);
+    }
+
+    #[test]
+    fn color_balance_compares_inputs_where_their_bounds_overlap() {
+        let extent = |min_x: f64, max_x: f64| InputExtent {
+            min: [min_x, 0.0, 0.0],
+            max: [max_x, 10.0, 0.0],
+            point_count: 400,
+        };
+        let strip = |min_x: f64, value: u16| {
+            (0..400)
+                .map(|i| {
+                    let mut point = point(min_x + f64::from(i) * 0.025, 5.0, 0.0);
+                    point.color = Color {
+                        r: value,
+                        g: value,
+                        b: value,
+                    };
+                    point
+                })
+                .collect::<Vec<_>>()
+        };
+        // A bright strip, a darker one overlapping its east half, a distant one and one without
+        // color on top of the first two
+        let extents = [
+            extent(0.0, 10.0),
+            extent(5.0, 15.0),
+            extent(20.0, 30.0),
+            extent(0.0, 15.0),
+        ];
+        let samples = [
+            Some(strip(0.0, 30_000)),
+            Some(strip(5.0, 20_000)),
+            Some(strip(20.0, 10_000)),
+            None,
+        ];
+        let overlaps = color_overlaps(&extents, &samples);
+        assert_eq!(overlaps.len(), 1);
+        assert_eq!(overlaps[0].files, [0, 1]);
+        assert_eq!(overlaps[0].stats.map(|stats| stats.count), [200, 201]);
+
+        let corrections = balance_colors(extents.len(), &overlaps);
+        let corrected = |file: usize, value: u16| {
+            let mut color = Color {
+                r: value,
+                g: value,
+                b: value,
+            };
+            corrections[file].apply(&mut color);
+            color.r
+        };
+        assert_eq!(corrected(0, 30_000), 25_000);
+        assert_eq!(corrected(1, 20_000), 25_000);
+        assert_eq!(corrected(2, 10_000), 10_000);
+    }
+
     #[test]
     fn should_use_in_memory_requires_five_times_processing_size() {
         let processing_size = 100;
@@ -1346,6 +3679,38 @@ mod tests {
         assert!(should_use_in_memory(processing_size, 500));
     }
 
+    #[test]
+    fn run_files_keep_point_attributes() {
+        let run_dir = tempdir().unwrap();
+        let run = run_dir.path().join("run.bin");
+        let mut ground = point(1.0, 2.0, 3.0);
+        ground.attributes.classification = Some("2".to_string());
+        ground.attributes.intensity = Some(1200);
+        ground.attributes.gps_time = Some(400_000.125);
+        ground.attributes.source_file = Some(3);
+        ground.attributes.point_index = Some(5_000_000_000);
+        ground.attributes.normal = Some([0.6, 0.0, 0.8]);
+        let key = SortKey { tile_id: 7, h: 1 };
+        write_run_file(&run, &[(key, CompactPoint::from(ground))]).unwrap();
+
+        let mut reader = RunFileReader::open(run).unwrap();
+        let (read_key, read_point) = reader.next_record().unwrap().unwrap();
+        assert_eq!(read_key, key);
+        assert_eq!(read_point.classification, 2);
+        let attributes = Point::from(read_point).attributes;
+        assert_eq!(attributes.classification.as_deref(), Some("2"));
+        assert_eq!(attributes.intensity, Some(1200));
+        assert_eq!(attributes.gps_time, Some(400_000.125));
+        assert_eq!(attributes.source_file, Some(3));
+        assert_eq!(attributes.point_index, Some(5_000_000_000));
+        assert_eq!(attributes.normal, Some([0.6, 0.0, 0.8]));
+        // Attributes the input did not provide stay missing rather than becoming 0
+        assert_eq!(attributes.return_number, None);
+        assert_eq!(attributes.scan_angle, None);
+        assert_eq!(attributes.point_source_id, None);
+        assert!(reader.next_record().unwrap().is_none());
+    }
+
     #[test]
     fn merge_shard_run_files_creates_tiles_and_deletes_runs() {
         let run_dir = tempdir().unwrap();
@@ -1363,11 +3728,17 @@ mod tests {
             &run_a,
             &[
                 (
-                    SortKey { tile_id: tile_a_id },
+                    SortKey {
+                        tile_id: tile_a_id,
+                        h: 0,
+                    },
                     CompactPoint::from(point(1.0, 1.0, 1.0)),
                 ),
                 (
-                    SortKey { tile_id: tile_b_id },
+                    SortKey {
+                        tile_id: tile_b_id,
+                        h: 0,
+                    },
                     CompactPoint::from(point(4.0, 4.0, 4.0)),
                 ),
             ],
@@ -1377,18 +3748,36 @@ mod tests {
             &run_b,
             &[
                 (
-                    SortKey { tile_id: tile_a_id },
+                    SortKey {
+                        tile_id: tile_a_id,
+                        h: 0,
+                    },
                     CompactPoint::from(point(2.0, 2.0, 2.0)),
                 ),
                 (
-                    SortKey { tile_id: tile_a_id },
+                    SortKey {
+                        tile_id: tile_a_id,
+                        h: 0,
+                    },
                     CompactPoint::from(point(3.0, 3.0, 3.0)),
                 ),
             ],
         )
         .unwrap();
 
-        merge_shard_run_files(vec![run_a.clone(), run_b.clone()], tile_dir.path(), true).unwrap();
+        merge_shard_run_files(
+            vec![run_a.clone(), run_b.clone()],
+            tile_dir.path(),
+            &LeafTiling {
+                scheme: &Geographic,
+                subdivision: Subdivision::Quadtree,
+                point_budget: None,
+                disable_decimation: true,
+                voxel_factor: DEFAULT_VOXEL_FACTOR,
+                normals: None,
+            },
+        )
+        .unwrap();
 
         let tile_a_points = read_points_from_tile(
             &tile_dir
@@ -1408,4 +3797,49 @@ mod tests {
         assert!(!run_a.exists());
         assert!(!run_b.exists());
     }
+
+    #[test]
+    fn merge_shard_run_files_splits_octree_heights() {
+        let run_dir = tempdir().unwrap();
+        let tile_dir = tempdir().unwrap();
+
+        let ground = (18, 10, 20, 65);
+        let roof = (18, 10, 20, 67);
+        let run = run_dir.path().join("run.bin");
+        write_run_file(
+            &run,
+            &[
+                (
+                    SortKey::from_tile(ground),
+                    CompactPoint::from(point(1.0, 1.0, 10.0)),
+                ),
+                (
+                    SortKey::from_tile(roof),
+                    CompactPoint::from(point(1.0, 1.0, 300.0)),
+                ),
+            ],
+        )
+        .unwrap();
+
+        merge_shard_run_files(
+            vec![run],
+            tile_dir.path(),
+            &LeafTiling {
+                scheme: &Geographic,
+                subdivision: Subdivision::Octree,
+                point_budget: None,
+                disable_decimation: true,
+                voxel_factor: DEFAULT_VOXEL_FACTOR,
+                normals: None,
+            },
+        )
+        .unwrap();
+
+        let ground_points =
+            read_points_from_tile(&tile_file_path(tile_dir.path(), ground)).unwrap();
+        let roof_points = read_points_from_tile(&tile_file_path(tile_dir.path(), roof)).unwrap();
+        assert_eq!(ground_points.len(), 1);
+        assert_eq!(roof_points.len(), 1);
+        assert_eq!(roof_points[0].z, 300.0);
+    }
 }
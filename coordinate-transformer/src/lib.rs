@@ -1,7 +1,9 @@
 mod error;
+mod local;
 mod transformer;
 
 pub use error::ProjError;
+pub use local::{LocalFrame, LocalFrameTransformer, geodetic_to_ecef};
 pub use transformer::{
     EPSG_WGS84_GEOCENTRIC, EPSG_WGS84_GEOGRAPHIC_3D, EpsgCode, PointTransformer,
 };
@@ -0,0 +1,169 @@
+use pcd_core::pointcloud::point::Point;
+
+use crate::{
+    error::ProjError,
+    transformer::{EPSG_WGS84_GEOGRAPHIC_3D, EpsgCode, PointTransformer},
+};
+
+/// WGS84 semi-major axis (metres)
+const WGS84_A: f64 = 6_378_137.0;
+
+/// WGS84 flattening
+const WGS84_F: f64 = 1.0 / 298.257_223_563;
+
+/// Anchor of a local engineering coordinate system (site grid) on the WGS84 ellipsoid.
+///
+/// Local coordinates are metres with X pointing to the site east, Y to the site north and
+/// Z up. `heading` is the clockwise angle in degrees from true north to the site +Y axis.
+#[derive(Debug, Clone, Copy)]
+pub struct LocalFrame {
+    pub origin_lng: f64,
+    pub origin_lat: f64,
+    pub origin_height: f64,
+    pub heading: f64,
+    pub scale: f64,
+}
+
+impl LocalFrame {
+    pub fn new(origin_lng: f64, origin_lat: f64, origin_height: f64) -> Self {
+        Self {
+            origin_lng,
+            origin_lat,
+            origin_height,
+            heading: 0.0,
+            scale: 1.0,
+        }
+    }
+
+    /// Convert local site coordinates to WGS84 geographic 3D (EPSG:4979) coordinates.
+    pub fn to_geographic(&self, x: f64, y: f64, z: f64) -> (f64, f64, f64) {
+        let (sin_h, cos_h) = self.heading.to_radians().sin_cos();
+        let (x, y, z) = (x * self.scale, y * self.scale, z * self.scale);
+
+        // Site grid → ENU
+        let east = x * cos_h + y * sin_h;
+        let north = -x * sin_h + y * cos_h;
+        let up = z;
+
+        // ENU → ECEF
+        let (sin_lat, cos_lat) = self.origin_lat.to_radians().sin_cos();
+        let (sin_lng, cos_lng) = self.origin_lng.to_radians().sin_cos();
+        let origin = geodetic_to_ecef(self.origin_lng, self.origin_lat, self.origin_height);
+        let ecef_x =
+            origin[0] - sin_lng * east - sin_lat * cos_lng * north + cos_lat * cos_lng * up;
+        let ecef_y =
+            origin[1] + cos_lng * east - sin_lat * sin_lng * north + cos_lat * sin_lng * up;
+        let ecef_z = origin[2] + cos_lat * north + sin_lat * up;
+
+        ecef_to_geodetic(ecef_x, ecef_y, ecef_z)
+    }
+}
+
+/// Places points given in a [`LocalFrame`] on the globe and optionally reprojects them to
+/// another CRS via PROJ.
+#[derive(Debug)]
+pub struct LocalFrameTransformer {
+    frame: LocalFrame,
+    output: Option<PointTransformer>,
+}
+
+impl LocalFrameTransformer {
+    pub fn new(
+        frame: LocalFrame,
+        output_epsg: EpsgCode,
+        proj_data_dir: Option<&std::path::Path>,
+    ) -> Result<Self, ProjError> {
+        let output = if output_epsg == EPSG_WGS84_GEOGRAPHIC_3D {
+            None
+        } else {
+            Some(PointTransformer::new(
+                EPSG_WGS84_GEOGRAPHIC_3D,
+                output_epsg,
+                proj_data_dir,
+            )?)
+        };
+        Ok(Self { frame, output })
+    }
+
+    pub fn transform_points_in_place(&mut self, points: &mut [Point]) -> Result<(), ProjError> {
+        for point in points.iter_mut() {
+            let (lng, lat, height) = self.frame.to_geographic(point.x, point.y, point.z);
+            point.x = lng;
+            point.y = lat;
+            point.z = height;
+        }
+        match self.output.as_mut() {
+            Some(output) => output.transform_points_in_place(points),
+            None => Ok(()),
+        }
+    }
+}
+
+/// WGS84 longitude, latitude (degrees) and ellipsoidal height to geocentric (EPSG:4978)
+/// coordinates, without PROJ
+pub fn geodetic_to_ecef(lng: f64, lat: f64, height: f64) -> [f64; 3] {
+    let e2 = WGS84_F * (2.0 - WGS84_F);
+    let (sin_lat, cos_lat) = lat.to_radians().sin_cos();
+    let (sin_lng, cos_lng) = lng.to_radians().sin_cos();
+    let n = WGS84_A / (1.0 - e2 * sin_lat * sin_lat).sqrt();
+    [
+        (n + height) * cos_lat * cos_lng,
+        (n + height) * cos_lat * sin_lng,
+        (n * (1.0 - e2) + height) * sin_lat,
+    ]
+}
+
+fn ecef_to_geodetic(x: f64, y: f64, z: f64) -> (f64, f64, f64) {
+    let e2 = WGS84_F * (2.0 - WGS84_F);
+    let p = x.hypot(y);
+    let lng = y.atan2(x);
+
+    // Iterative solution; converges to sub-millimetre in a few steps near the surface
+    let mut lat = z.atan2(p * (1.0 - e2));
+    let mut height = 0.0;
+    for _ in 0..5 {
+        let sin_lat = lat.sin();
+        let n = WGS84_A / (1.0 - e2 * sin_lat * sin_lat).sqrt();
+        height = p / lat.cos() - n;
+        lat = z.atan2(p * (1.0 - e2 * n / (n + height)));
+    }
+
+    (lng.to_degrees(), lat.to_degrees(), height)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn origin_maps_to_anchor() {
+        let frame = LocalFrame::new(139.7, 35.6, 40.0);
+        let (lng, lat, height) = frame.to_geographic(0.0, 0.0, 0.0);
+        assert!((lng - 139.7).abs() < 1e-9);
+        assert!((lat - 35.6).abs() < 1e-9);
+        assert!((height - 40.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn heading_rotates_site_axes() {
+        let frame = LocalFrame {
+            heading: 90.0,
+            ..LocalFrame::new(139.7, 35.6, 0.0)
+        };
+        // Site north points east when heading is 90°
+        let (lng, lat, _) = frame.to_geographic(0.0, 100.0, 0.0);
+        assert!(lng > 139.7);
+        assert!((lat - 35.6).abs() < 1e-6);
+    }
+
+    #[test]
+    fn scale_is_applied() {
+        let frame = LocalFrame {
+            scale: 0.001,
+            ..LocalFrame::new(0.0, 0.0, 0.0)
+        };
+        // 1000 site units at scale 0.001 is 1 m up
+        let (_, _, height) = frame.to_geographic(0.0, 0.0, 1000.0);
+        assert!((height - 1.0).abs() < 1e-6);
+    }
+}
@@ -228,6 +228,9 @@ mod tests {
                 user_data: None,
                 point_source_id: None,
                 gps_time: None,
+                source_file: None,
+                point_index: None,
+                normal: None,
             },
         }
     }
@@ -0,0 +1,255 @@
+use crate::pointcloud::point::Color;
+
+/// Standard deviation below which a channel is too flat to estimate a gain from, in 16-bit units
+const MIN_STD_DEV: f64 = 1.0;
+
+/// Mean and spread of the color channels of a set of points
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ColorStats {
+    pub count: u64,
+    sum: [f64; 3],
+    sum_squares: [f64; 3],
+}
+
+impl ColorStats {
+    pub fn add(&mut self, color: &Color) {
+        self.count += 1;
+        for (i, value) in [color.r, color.g, color.b].into_iter().enumerate() {
+            let value = f64::from(value);
+            self.sum[i] += value;
+            self.sum_squares[i] += value * value;
+        }
+    }
+
+    pub fn mean(&self) -> [f64; 3] {
+        let count = self.count.max(1) as f64;
+        self.sum.map(|sum| sum / count)
+    }
+
+    pub fn std_dev(&self) -> [f64; 3] {
+        let count = self.count.max(1) as f64;
+        let mean = self.mean();
+        std::array::from_fn(|i| {
+            (self.sum_squares[i] / count - mean[i] * mean[i])
+                .max(0.0)
+                .sqrt()
+        })
+    }
+}
+
+/// Colors of two input files within the region where they overlap
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorOverlap {
+    pub files: [usize; 2],
+    pub stats: [ColorStats; 2],
+}
+
+/// Per-channel linear correction of the colors of an input file
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorCorrection {
+    pub gain: [f64; 3],
+    pub offset: [f64; 3],
+}
+
+impl Default for ColorCorrection {
+    fn default() -> Self {
+        Self {
+            gain: [1.0; 3],
+            offset: [0.0; 3],
+        }
+    }
+}
+
+impl ColorCorrection {
+    pub fn apply(&self, color: &mut Color) {
+        let Color { r, g, b } = color;
+        for (i, value) in [r, g, b].into_iter().enumerate() {
+            let corrected = f64::from(*value) * self.gain[i] + self.offset[i];
+            *value = corrected.round().clamp(0.0, f64::from(u16::MAX)) as u16;
+        }
+    }
+}
+
+/// Gains and offsets that make the colors of overlapping files agree, in the least squares
+/// sense, weighted by the number of points in each overlap
+///
+/// Gains match the standard deviations and offsets the means of the corrected colors in every
+/// overlap. Within each group of connected files, the logarithms of the gains and the offsets
+/// average to zero, so that the overall brightness is kept; files without overlaps are left as
+/// they are.
+pub fn balance_colors(file_count: usize, overlaps: &[ColorOverlap]) -> Vec<ColorCorrection> {
+    let mut corrections = vec![ColorCorrection::default(); file_count];
+    for channel in 0..3 {
+        let gain_differences = overlaps
+            .iter()
+            .filter_map(|overlap| {
+                let [a, b] = overlap.stats.map(|stats| stats.std_dev()[channel]);
+                (a >= MIN_STD_DEV && b >= MIN_STD_DEV)
+                    .then(|| (overlap.files, weight(overlap), (b / a).ln()))
+            })
+            .collect::<Vec<_>>();
+        let log_gains = solve_differences(file_count, &gain_differences);
+
+        let offset_differences = overlaps
+            .iter()
+            .map(|overlap| {
+                let [a, b] = overlap.files;
+                let [mean_a, mean_b] = overlap.stats.map(|stats| stats.mean()[channel]);
+                let difference = mean_b * log_gains[b].exp() - mean_a * log_gains[a].exp();
+                (overlap.files, weight(overlap), difference)
+            })
+            .collect::<Vec<_>>();
+        let offsets = solve_differences(file_count, &offset_differences);
+
+        for (file, correction) in corrections.iter_mut().enumerate() {
+            correction.gain[channel] = log_gains[file].exp();
+            correction.offset[channel] = offsets[file];
+        }
+    }
+    corrections
+}
+
+fn weight(overlap: &ColorOverlap) -> f64 {
+    overlap.stats[0].count.min(overlap.stats[1].count) as f64
+}
+
+/// Minimum norm `x` minimizing the sum of `weight * (x[a] - x[b] - difference)^2`, by conjugate
+/// gradients on the weighted graph Laplacian of the files
+fn solve_differences(count: usize, differences: &[([usize; 2], f64, f64)]) -> Vec<f64> {
+    let laplacian = |x: &[f64]| {
+        let mut y = vec![0.0; count];
+        for &([a, b], weight, _) in differences {
+            let flow = weight * (x[a] - x[b]);
+            y[a] += flow;
+            y[b] -= flow;
+        }
+        y
+    };
+    let dot = |x: &[f64], y: &[f64]| x.iter().zip(y).map(|(x, y)| x * y).sum::<f64>();
+
+    let mut residual = vec![0.0; count];
+    for &([a, b], weight, difference) in differences {
+        residual[a] += weight * difference;
+        residual[b] -= weight * difference;
+    }
+    // Starting from zero, the iterates stay orthogonal to the constant vector of every group of
+    // connected files, which gives the minimum norm solution
+    let mut x = vec![0.0; count];
+    let mut direction = residual.clone();
+    let mut residual_norm = dot(&residual, &residual);
+    let tolerance = residual_norm * 1e-24;
+    for _ in 0..count.max(1) * 4 {
+        if residual_norm <= tolerance || residual_norm == 0.0 {
+            break;
+        }
+        let product = laplacian(&direction);
+        let step = residual_norm / dot(&direction, &product);
+        for ((x, residual), (direction, product)) in x
+            .iter_mut()
+            .zip(&mut residual)
+            .zip(direction.iter().zip(&product))
+        {
+            *x += step * direction;
+            *residual -= step * product;
+        }
+        let next_norm = dot(&residual, &residual);
+        for (direction, residual) in direction.iter_mut().zip(&residual) {
+            *direction = residual + next_norm / residual_norm * *direction;
+        }
+        residual_norm = next_norm;
+    }
+    x
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(colors: impl IntoIterator<Item = u16>) -> ColorStats {
+        let mut stats = ColorStats::default();
+        for value in colors {
+            stats.add(&Color {
+                r: value,
+                g: value,
+                b: value / 2,
+            });
+        }
+        stats
+    }
+
+    #[test]
+    fn stats_track_mean_and_spread() {
+        let stats = stats([1000, 3000]);
+        assert_eq!(stats.count, 2);
+        assert_eq!(stats.mean(), [2000.0, 2000.0, 1000.0]);
+        assert_eq!(stats.std_dev(), [1000.0, 1000.0, 500.0]);
+    }
+
+    #[test]
+    fn overlapping_files_meet_halfway() {
+        // The second strip is darker and flatter than the first
+        let bright = (0..100).map(|i| 10_000 + i * 100);
+        let dark = (0..100).map(|i| 6_000 + i * 50);
+        let overlaps = [ColorOverlap {
+            files: [0, 2],
+            stats: [stats(bright.clone()), stats(dark.clone())],
+        }];
+        let corrections = balance_colors(3, &overlaps);
+
+        // Gains of 1/sqrt(2) and sqrt(2) bring the spreads together
+        let [first, isolated, second] = corrections.as_slice() else {
+            panic!("one correction per file");
+        };
+        assert!((first.gain[0] * second.gain[0] - 1.0).abs() < 1e-9);
+        assert!((first.gain[0] - 0.5_f64.sqrt()).abs() < 1e-9);
+        assert!((first.offset[0] + second.offset[0]).abs() < 1e-6);
+        assert_eq!(*isolated, ColorCorrection::default());
+
+        let corrected = |correction: &ColorCorrection, values: &mut dyn Iterator<Item = u16>| {
+            let mut stats = ColorStats::default();
+            for value in values {
+                let mut color = Color {
+                    r: value,
+                    g: value,
+                    b: value / 2,
+                };
+                correction.apply(&mut color);
+                stats.add(&color);
+            }
+            stats
+        };
+        let first = corrected(first, &mut bright.clone());
+        let second = corrected(second, &mut dark.clone());
+        for channel in 0..3 {
+            assert!((first.mean()[channel] - second.mean()[channel]).abs() < 1.0);
+            assert!((first.std_dev()[channel] - second.std_dev()[channel]).abs() < 1.0);
+        }
+    }
+
+    #[test]
+    fn chained_strips_are_balanced_together() {
+        // Strips 0-1-2 in a row, each overlap showing the same offset of 3000
+        let overlap = |files| ColorOverlap {
+            files,
+            stats: [
+                stats((0..50).map(|i| 20_000 + i * 10)),
+                stats((0..50).map(|i| 17_000 + i * 10)),
+            ],
+        };
+        let corrections = balance_colors(3, &[overlap([0, 1]), overlap([1, 2])]);
+        let offsets = corrections
+            .iter()
+            .map(|correction| correction.offset[0].round())
+            .collect::<Vec<_>>();
+        assert_eq!(offsets, [-3000.0, 0.0, 3000.0]);
+        assert!(corrections.iter().all(|c| (c.gain[0] - 1.0).abs() < 1e-9));
+
+        let mut color = Color {
+            r: 65_000,
+            g: 100,
+            b: 0,
+        };
+        corrections[2].apply(&mut color);
+        assert_eq!((color.r, color.g, color.b), (65_535, 3_100, 1_500));
+    }
+}
@@ -0,0 +1,354 @@
+use std::str::FromStr;
+
+use crate::pointcloud::point::{Color, Point};
+
+/// Default percentiles of the intensities mapped to black and white
+pub const DEFAULT_INTENSITY_PERCENTILES: [f64; 2] = [2.0, 98.0];
+
+/// Attribute the derived colors are computed from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorizeMode {
+    Elevation,
+    Intensity,
+    Classification,
+    ReturnNumber,
+    PointSourceId,
+}
+
+impl FromStr for ColorizeMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "elevation" => Ok(Self::Elevation),
+            "intensity" => Ok(Self::Intensity),
+            "classification" => Ok(Self::Classification),
+            "return-number" => Ok(Self::ReturnNumber),
+            "point-source-id" => Ok(Self::PointSourceId),
+            _ => Err(format!(
+                "unknown colorize mode {s:?} (expected elevation, intensity, classification, \
+                 return-number or point-source-id)"
+            )),
+        }
+    }
+}
+
+/// Color ramp of the elevation mode, from low to high
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorRamp {
+    /// Perceptually uniform dark blue to yellow
+    #[default]
+    Viridis,
+    /// Green lowlands through yellow and brown to white peaks
+    Terrain,
+    /// Blue through green to red
+    Rainbow,
+    Grayscale,
+}
+
+impl FromStr for ColorRamp {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "viridis" => Ok(Self::Viridis),
+            "terrain" => Ok(Self::Terrain),
+            "rainbow" => Ok(Self::Rainbow),
+            "grayscale" => Ok(Self::Grayscale),
+            _ => Err(format!(
+                "unknown color ramp {s:?} (expected viridis, terrain, rainbow or grayscale)"
+            )),
+        }
+    }
+}
+
+const VIRIDIS: [[u8; 3]; 9] = [
+    [68, 1, 84],
+    [71, 44, 122],
+    [59, 81, 139],
+    [44, 113, 142],
+    [33, 144, 141],
+    [39, 173, 129],
+    [92, 200, 99],
+    [170, 220, 50],
+    [253, 231, 37],
+];
+
+const TERRAIN: [[u8; 3]; 5] = [
+    [0, 128, 64],
+    [128, 192, 64],
+    [224, 216, 112],
+    [150, 110, 70],
+    [255, 255, 255],
+];
+
+const RAINBOW: [[u8; 3]; 5] = [
+    [0, 0, 255],
+    [0, 255, 255],
+    [0, 255, 0],
+    [255, 255, 0],
+    [255, 0, 0],
+];
+
+const GRAYSCALE: [[u8; 3]; 2] = [[0, 0, 0], [255, 255, 255]];
+
+impl ColorRamp {
+    fn stops(self) -> &'static [[u8; 3]] {
+        match self {
+            Self::Viridis => &VIRIDIS,
+            Self::Terrain => &TERRAIN,
+            Self::Rainbow => &RAINBOW,
+            Self::Grayscale => &GRAYSCALE,
+        }
+    }
+
+    /// Color at `t`, from 0 to 1, interpolated between evenly spaced sRGB stops
+    pub fn color(self, t: f64) -> Color {
+        let stops = self.stops();
+        let position = t.clamp(0.0, 1.0) * (stops.len() - 1) as f64;
+        let index = (position.floor() as usize).min(stops.len() - 2);
+        let fraction = position - index as f64;
+        let [r, g, b] = std::array::from_fn(|i| {
+            let from = f64::from(stops[index][i]);
+            let to = f64::from(stops[index + 1][i]);
+            from + (to - from) * fraction
+        });
+        rgb(r, g, b)
+    }
+}
+
+/// ASPRS standard point classes 0-18 of LAS 1.4; other classes are white
+const CLASSIFICATION_PALETTE: [[u8; 3]; 19] = [
+    [128, 128, 128], // 0 created, never classified
+    [170, 170, 170], // 1 unclassified
+    [166, 118, 67],  // 2 ground
+    [144, 238, 144], // 3 low vegetation
+    [46, 184, 46],   // 4 medium vegetation
+    [0, 100, 0],     // 5 high vegetation
+    [230, 80, 60],   // 6 building
+    [255, 0, 255],   // 7 low point (noise)
+    [255, 255, 0],   // 8 model key-point
+    [30, 120, 255],  // 9 water
+    [140, 80, 40],   // 10 rail
+    [90, 90, 90],    // 11 road surface
+    [255, 215, 0],   // 12 overlap
+    [255, 255, 128], // 13 wire guard
+    [255, 160, 0],   // 14 wire conductor
+    [200, 0, 200],   // 15 transmission tower
+    [255, 128, 192], // 16 wire connector
+    [160, 160, 255], // 17 bridge deck
+    [255, 0, 0],     // 18 high noise
+];
+
+/// Returns 1 to 5; later returns share the last color
+const RETURN_NUMBER_PALETTE: [[u8; 3]; 5] = [
+    [230, 60, 60],
+    [60, 180, 75],
+    [60, 100, 230],
+    [255, 200, 0],
+    [200, 60, 200],
+];
+
+/// Color of points lacking the attribute a mode is computed from
+const MISSING: [u8; 3] = [128, 128, 128];
+
+/// Derived coloring of the points, replacing their `color`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Colorization {
+    /// `ramp` over the heights from `range[0]` to `range[1]`
+    Elevation {
+        ramp: ColorRamp,
+        range: [f64; 2],
+    },
+    /// Black to white over the intensities from `range[0]` to `range[1]`
+    Intensity {
+        range: [f64; 2],
+    },
+    /// ASPRS standard classification palette
+    Classification,
+    ReturnNumber,
+    /// A distinct hue per point source ID, usually the flight line
+    PointSourceId,
+}
+
+impl Colorization {
+    pub fn apply(&self, point: &mut Point) {
+        point.color = match *self {
+            Self::Elevation { ramp, range } => ramp.color(normalize(point.z, range)),
+            Self::Intensity { range } => match point.attributes.intensity {
+                Some(intensity) => ColorRamp::Grayscale.color(normalize(intensity.into(), range)),
+                None => palette(MISSING),
+            },
+            Self::Classification => {
+                let class = point
+                    .attributes
+                    .classification
+                    .as_deref()
+                    .and_then(|class| class.parse::<usize>().ok());
+                match class {
+                    Some(class) => CLASSIFICATION_PALETTE
+                        .get(class)
+                        .copied()
+                        .map_or(rgb(255.0, 255.0, 255.0), palette),
+                    None => palette(MISSING),
+                }
+            }
+            Self::ReturnNumber => match point.attributes.return_number {
+                Some(number) if number > 0 => {
+                    let index = usize::from(number - 1).min(RETURN_NUMBER_PALETTE.len() - 1);
+                    palette(RETURN_NUMBER_PALETTE[index])
+                }
+                _ => palette(MISSING),
+            },
+            Self::PointSourceId => match point.attributes.point_source_id {
+                Some(id) => distinct_color(id),
+                None => palette(MISSING),
+            },
+        };
+    }
+}
+
+/// Low and high percentiles (0-100) of the values, by nearest rank; sorts `values`
+pub fn percentile_range(values: &mut [f64], percentiles: [f64; 2]) -> Option<[f64; 2]> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_by(f64::total_cmp);
+    let last = values.len() - 1;
+    Some(percentiles.map(|p| {
+        let rank = (p.clamp(0.0, 100.0) / 100.0 * last as f64).round() as usize;
+        values[rank]
+    }))
+}
+
+/// Position of `value` within `range`, from 0 to 1
+fn normalize(value: f64, [min, max]: [f64; 2]) -> f64 {
+    if max > min {
+        ((value - min) / (max - min)).clamp(0.0, 1.0)
+    } else {
+        0.5
+    }
+}
+
+/// Hues stepped by the golden ratio, so that consecutive IDs get clearly different colors
+fn distinct_color(id: u16) -> Color {
+    let hue = (f64::from(id) * 0.618_033_988_749_895).fract() * 6.0;
+    let (saturation, value) = (0.65, 0.95);
+    let chroma = value * saturation;
+    let x = chroma * (1.0 - (hue % 2.0 - 1.0).abs());
+    let (r, g, b) = match hue as u8 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let m = value - chroma;
+    rgb((r + m) * 255.0, (g + m) * 255.0, (b + m) * 255.0)
+}
+
+fn palette([r, g, b]: [u8; 3]) -> Color {
+    rgb(r.into(), g.into(), b.into())
+}
+
+/// 16-bit color from 8-bit sRGB components
+fn rgb(r: f64, g: f64, b: f64) -> Color {
+    let widen = |v: f64| (v.clamp(0.0, 255.0) * 257.0).round() as u16;
+    Color {
+        r: widen(r),
+        g: widen(g),
+        b: widen(b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pointcloud::point::PointAttributes;
+
+    fn point(z: f64) -> Point {
+        Point {
+            x: 0.0,
+            y: 0.0,
+            z,
+            color: Color::default(),
+            attributes: PointAttributes::default(),
+        }
+    }
+
+    fn rgb8(point: &Point) -> [u8; 3] {
+        [point.color.r, point.color.g, point.color.b].map(|v| (v / 257) as u8)
+    }
+
+    #[test]
+    fn elevation_follows_the_ramp_and_clamps_to_the_range() {
+        let colorization = Colorization::Elevation {
+            ramp: ColorRamp::Viridis,
+            range: [100.0, 200.0],
+        };
+        let colors = [50.0, 100.0, 150.0, 250.0].map(|z| {
+            let mut point = point(z);
+            colorization.apply(&mut point);
+            rgb8(&point)
+        });
+        assert_eq!(colors, [VIRIDIS[0], VIRIDIS[0], VIRIDIS[4], VIRIDIS[8]]);
+
+        // Between two stops
+        let color = ColorRamp::Rainbow.color(0.375);
+        assert_eq!((color.r, color.g, color.b), (0, 65535, 32768));
+    }
+
+    #[test]
+    fn intensity_is_stretched_between_the_percentiles() {
+        let mut values = (0..100).map(f64::from).collect::<Vec<_>>();
+        values.push(60_000.0);
+        let range = percentile_range(&mut values, [2.0, 98.0]).unwrap();
+        assert_eq!(range, [2.0, 98.0]);
+        assert_eq!(percentile_range(&mut [], [2.0, 98.0]), None);
+
+        let colorization = Colorization::Intensity { range };
+        let mut bright = point(0.0);
+        bright.attributes.intensity = Some(60_000);
+        colorization.apply(&mut bright);
+        assert_eq!(rgb8(&bright), [255; 3]);
+        let mut middle = point(0.0);
+        middle.attributes.intensity = Some(50);
+        colorization.apply(&mut middle);
+        let color = middle.color;
+        assert_eq!((color.r, color.g, color.b), (32768, 32768, 32768));
+    }
+
+    #[test]
+    fn categorical_modes_use_their_palettes() {
+        let mut ground = point(0.0);
+        ground.attributes.classification = Some("2".to_string());
+        Colorization::Classification.apply(&mut ground);
+        assert_eq!(rgb8(&ground), CLASSIFICATION_PALETTE[2]);
+
+        let mut user_defined = point(0.0);
+        user_defined.attributes.classification = Some("64".to_string());
+        Colorization::Classification.apply(&mut user_defined);
+        assert_eq!(rgb8(&user_defined), [255; 3]);
+
+        let mut seventh = point(0.0);
+        seventh.attributes.return_number = Some(7);
+        Colorization::ReturnNumber.apply(&mut seventh);
+        assert_eq!(rgb8(&seventh), RETURN_NUMBER_PALETTE[4]);
+
+        let mut missing = point(0.0);
+        Colorization::PointSourceId.apply(&mut missing);
+        assert_eq!(rgb8(&missing), MISSING);
+
+        let colors = (1..=3)
+            .map(|id| {
+                let mut point = point(0.0);
+                point.attributes.point_source_id = Some(id);
+                Colorization::PointSourceId.apply(&mut point);
+                rgb8(&point)
+            })
+            .collect::<Vec<_>>();
+        assert_ne!(colors[0], colors[1]);
+        assert_ne!(colors[1], colors[2]);
+    }
+}
@@ -1,2 +1,6 @@
+pub mod color_balance;
+pub mod colorize;
 pub mod decimation;
+pub mod normal;
 pub mod point;
+pub mod spacing;
@@ -0,0 +1,370 @@
+use std::collections::{BinaryHeap, HashMap};
+
+use crate::pointcloud::point::Point;
+
+/// Default number of neighbours fitted by the PCA
+pub const DEFAULT_NORMAL_NEIGHBOURS: usize = 16;
+
+/// Which side of the fitted plane the normals face
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NormalOrientation {
+    /// Away from the given centre; with geocentric coordinates and the Earth's centre, normals
+    /// point upward
+    AwayFrom([f64; 3]),
+    /// Toward the given sensor position
+    Toward([f64; 3]),
+}
+
+impl NormalOrientation {
+    /// Unit direction the normal of a point at `position` should face
+    fn direction(self, position: [f64; 3]) -> [f64; 3] {
+        let direction = match self {
+            Self::AwayFrom(center) => sub(position, center),
+            Self::Toward(sensor) => sub(sensor, position),
+        };
+        normalize(direction).unwrap_or([0.0, 0.0, 1.0])
+    }
+}
+
+/// Estimates the `normal` attribute of every point by PCA over its `neighbours` nearest
+/// neighbours: the normal is the direction of least variance, flipped to face `orientation`.
+/// Points with fewer than 3 neighbours get the orientation direction itself.
+///
+/// `context` holds other points, such as those of the adjacent tiles, that serve as neighbours
+/// but get no normal themselves.
+pub fn estimate_normals(
+    points: &mut [Point],
+    context: &[Point],
+    neighbours: usize,
+    orientation: NormalOrientation,
+) {
+    let positions = points
+        .iter()
+        .chain(context)
+        .map(|p| [p.x, p.y, p.z])
+        .collect::<Vec<_>>();
+    let grid = Grid::new(&positions, neighbours);
+
+    for (point, &position) in points.iter_mut().zip(&positions) {
+        let facing = orientation.direction(position);
+        let nearest = grid.nearest(&positions, position, neighbours);
+        let normal = if nearest.len() < 3 {
+            facing
+        } else {
+            let normal = smallest_eigenvector(covariance(nearest.iter().map(|&i| positions[i])));
+            if dot(normal, facing) < 0.0 {
+                normal.map(|v| -v)
+            } else {
+                normal
+            }
+        };
+        point.attributes.normal = Some(normal.map(|v| v as f32));
+    }
+}
+
+/// Uniform grid over the points for k-nearest-neighbour queries
+pub(crate) struct Grid {
+    cell_size: f64,
+    cells: HashMap<[i64; 3], Vec<usize>>,
+    /// Largest ring that can still contain points
+    max_ring: i64,
+}
+
+impl Grid {
+    pub(crate) fn new(positions: &[[f64; 3]], neighbours: usize) -> Self {
+        let mut min = [f64::MAX; 3];
+        let mut max = [f64::MIN; 3];
+        for position in positions {
+            for axis in 0..3 {
+                min[axis] = min[axis].min(position[axis]);
+                max[axis] = max[axis].max(position[axis]);
+            }
+        }
+
+        // Point clouds are mostly surfaces, so size cells from the area spanned by the two
+        // largest extents for about `neighbours` points per cell
+        let mut extents = std::array::from_fn::<f64, 3, _>(|axis| (max[axis] - min[axis]).max(0.0));
+        extents.sort_by(f64::total_cmp);
+        let area = extents[1] * extents[2];
+        let cell_size = (area * neighbours as f64 / positions.len().max(1) as f64).sqrt();
+        let cell_size = if cell_size.is_normal() {
+            cell_size
+        } else {
+            1.0
+        };
+
+        let mut cells = HashMap::<[i64; 3], Vec<usize>>::new();
+        for (i, &position) in positions.iter().enumerate() {
+            cells
+                .entry(Self::cell(position, cell_size))
+                .or_default()
+                .push(i);
+        }
+        let max_ring = (extents[2] / cell_size).ceil() as i64 + 1;
+
+        Self {
+            cell_size,
+            cells,
+            max_ring,
+        }
+    }
+
+    fn cell(position: [f64; 3], cell_size: f64) -> [i64; 3] {
+        position.map(|v| (v / cell_size).floor() as i64)
+    }
+
+    /// Indices of the `k` points nearest to `position`, including the point itself
+    pub(crate) fn nearest(
+        &self,
+        positions: &[[f64; 3]],
+        position: [f64; 3],
+        k: usize,
+    ) -> Vec<usize> {
+        let center = Self::cell(position, self.cell_size);
+        // Max-heap of (squared distance, index), holding the best `k` candidates
+        let mut heap = BinaryHeap::<(Distance, usize)>::with_capacity(k + 1);
+
+        for ring in 0..=self.max_ring {
+            for cell in ring_cells(center, ring) {
+                for &i in self.cells.get(&cell).into_iter().flatten() {
+                    let d = sub(positions[i], position);
+                    heap.push((Distance(dot(d, d)), i));
+                    if heap.len() > k {
+                        heap.pop();
+                    }
+                }
+            }
+            // Points beyond this ring are at least `ring` cells away
+            let reach = ring as f64 * self.cell_size;
+            if heap.len() == k && heap.peek().is_some_and(|(d, _)| d.0 <= reach * reach) {
+                break;
+            }
+        }
+        heap.into_iter().map(|(_, i)| i).collect()
+    }
+}
+
+/// Cells at Chebyshev distance `ring` from `center`
+fn ring_cells(center: [i64; 3], ring: i64) -> impl Iterator<Item = [i64; 3]> {
+    (-ring..=ring)
+        .flat_map(move |dx| {
+            (-ring..=ring).flat_map(move |dy| (-ring..=ring).map(move |dz| [dx, dy, dz]))
+        })
+        .filter(move |d| d.iter().any(|v| v.abs() == ring))
+        .map(move |d| [center[0] + d[0], center[1] + d[1], center[2] + d[2]])
+}
+
+/// Totally ordered squared distance for the neighbour heap
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Distance(f64);
+
+impl Eq for Distance {}
+
+impl PartialOrd for Distance {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Distance {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+fn covariance(positions: impl Iterator<Item = [f64; 3]> + Clone) -> [[f64; 3]; 3] {
+    let count = positions.clone().count() as f64;
+    let mean = positions
+        .clone()
+        .fold([0.0; 3], |acc, p| {
+            [acc[0] + p[0], acc[1] + p[1], acc[2] + p[2]]
+        })
+        .map(|v| v / count);
+
+    let mut covariance = [[0.0; 3]; 3];
+    for position in positions {
+        let d = sub(position, mean);
+        for row in 0..3 {
+            for col in 0..3 {
+                covariance[row][col] += d[row] * d[col] / count;
+            }
+        }
+    }
+    covariance
+}
+
+/// Unit eigenvector of the smallest eigenvalue of a symmetric 3x3 matrix, by Jacobi rotations
+fn smallest_eigenvector(mut a: [[f64; 3]; 3]) -> [f64; 3] {
+    let mut v = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+
+    for _ in 0..32 {
+        let off_diagonal = a[0][1].powi(2) + a[0][2].powi(2) + a[1][2].powi(2);
+        if off_diagonal < 1e-30 {
+            break;
+        }
+        for (p, q) in [(0, 1), (0, 2), (1, 2)] {
+            if a[p][q] == 0.0 {
+                continue;
+            }
+            let theta = (a[q][q] - a[p][p]) / (2.0 * a[p][q]);
+            let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+            let c = 1.0 / (t * t + 1.0).sqrt();
+            let s = t * c;
+
+            // a = Jᵀ a J, v = v J
+            for row in &mut a {
+                let (akp, akq) = (row[p], row[q]);
+                row[p] = c * akp - s * akq;
+                row[q] = s * akp + c * akq;
+            }
+            let (row_p, row_q) = (a[p], a[q]);
+            a[p] = std::array::from_fn(|k| c * row_p[k] - s * row_q[k]);
+            a[q] = std::array::from_fn(|k| s * row_p[k] + c * row_q[k]);
+            for row in &mut v {
+                let (vkp, vkq) = (row[p], row[q]);
+                row[p] = c * vkp - s * vkq;
+                row[q] = s * vkp + c * vkq;
+            }
+        }
+    }
+
+    let smallest = (0..3).min_by(|&i, &j| a[i][i].total_cmp(&a[j][j])).unwrap();
+    normalize([v[0][smallest], v[1][smallest], v[2][smallest]]).unwrap_or([0.0, 0.0, 1.0])
+}
+
+fn sub(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn normalize(v: [f64; 3]) -> Option<[f64; 3]> {
+    let length = dot(v, v).sqrt();
+    (length > 0.0).then(|| v.map(|c| c / length))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pointcloud::point::{Color, PointAttributes};
+
+    fn point(x: f64, y: f64, z: f64) -> Point {
+        Point {
+            x,
+            y,
+            z,
+            color: Color::default(),
+            attributes: PointAttributes::default(),
+        }
+    }
+
+    /// Points on the plane x + z = 10, with a little unevenness
+    fn tilted_plane() -> Vec<Point> {
+        (0..20)
+            .flat_map(|i| (0..20).map(move |j| (i as f64, j as f64)))
+            .map(|(x, y)| point(x, y, 10.0 - x + 0.01 * ((x * y) % 3.0)))
+            .collect()
+    }
+
+    #[test]
+    fn normals_are_perpendicular_to_the_surface() {
+        let mut points = tilted_plane();
+        estimate_normals(
+            &mut points,
+            &[],
+            DEFAULT_NORMAL_NEIGHBOURS,
+            NormalOrientation::AwayFrom([0.0, 0.0, -1000.0]),
+        );
+        let expected = std::f32::consts::FRAC_1_SQRT_2;
+        for point in &points {
+            let [x, y, z] = point.attributes.normal.unwrap();
+            assert!((x - expected).abs() < 0.02, "{x}");
+            assert!(y.abs() < 0.02, "{y}");
+            assert!((z - expected).abs() < 0.02, "{z}");
+        }
+    }
+
+    #[test]
+    fn normals_face_the_sensor() {
+        let mut points = tilted_plane();
+        estimate_normals(
+            &mut points,
+            &[],
+            8,
+            NormalOrientation::Toward([-100.0, 0.0, -100.0]),
+        );
+        assert!(
+            points
+                .iter()
+                .all(|p| p.attributes.normal.unwrap()[0] < -0.6)
+        );
+
+        // Too few neighbours for a plane
+        let mut points = vec![point(0.0, 0.0, 0.0), point(1.0, 0.0, 0.0)];
+        estimate_normals(
+            &mut points,
+            &[],
+            8,
+            NormalOrientation::AwayFrom([0.0, 0.0, -1.0]),
+        );
+        assert_eq!(points[0].attributes.normal, Some([0.0, 0.0, 1.0]));
+    }
+
+    #[test]
+    fn context_points_serve_as_neighbours() {
+        // A single row of points along a tile edge, which alone fits no plane; the rows of the
+        // adjacent tiles on either side make it part of the ground
+        let mut points = (0..10)
+            .map(|i| point(i as f64, 0.0, 0.0))
+            .collect::<Vec<_>>();
+        let context = [-2.0, -1.0, 1.0, 2.0]
+            .into_iter()
+            .flat_map(|y| (0..10).map(move |i| point(i as f64, y, 0.0)))
+            .collect::<Vec<_>>();
+        estimate_normals(
+            &mut points,
+            &context,
+            8,
+            NormalOrientation::AwayFrom([0.0, 0.0, -1000.0]),
+        );
+        for point in &points {
+            let [x, y, z] = point.attributes.normal.unwrap();
+            assert!(x.abs() < 1e-3 && y.abs() < 1e-3 && z > 0.999, "{x} {y} {z}");
+        }
+        assert!(context.iter().all(|p| p.attributes.normal.is_none()));
+    }
+
+    #[test]
+    fn nearest_neighbours_match_brute_force() {
+        let positions = tilted_plane()
+            .iter()
+            .map(|p| [p.x, p.y * 0.37, p.z])
+            .collect::<Vec<_>>();
+        let grid = Grid::new(&positions, 5);
+        for &query in positions.iter().step_by(7) {
+            let mut nearest = grid.nearest(&positions, query, 5);
+            let mut expected = (0..positions.len()).collect::<Vec<_>>();
+            expected.sort_by(|&a, &b| {
+                let da = sub(positions[a], query);
+                let db = sub(positions[b], query);
+                dot(da, da).total_cmp(&dot(db, db))
+            });
+            let kth = sub(positions[expected[4]], query);
+            let kth = dot(kth, kth);
+            // Ties at the k-th distance may pick either point
+            nearest.retain(|&i| {
+                let d = sub(positions[i], query);
+                dot(d, d) < kth
+            });
+            expected.retain(|&i| {
+                let d = sub(positions[i], query);
+                dot(d, d) < kth
+            });
+            nearest.sort();
+            expected.sort();
+            assert_eq!(nearest, expected);
+        }
+    }
+}
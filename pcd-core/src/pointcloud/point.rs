@@ -5,7 +5,7 @@ use bitcode::{Decode, Encode};
 /// EPSG code type alias
 pub type EpsgCode = u16;
 
-#[derive(Debug, Clone, Decode, Encode)]
+#[derive(Debug, Clone, Default, Decode, Encode)]
 pub struct PointAttributes {
     pub intensity: Option<u16>,
     pub return_number: Option<u8>,
@@ -15,6 +15,12 @@ pub struct PointAttributes {
     pub user_data: Option<u8>,
     pub point_source_id: Option<u16>,
     pub gps_time: Option<f64>,
+    /// Index of the input file the point was read from
+    pub source_file: Option<u32>,
+    /// Index of the point within its input file
+    pub point_index: Option<u64>,
+    /// Unit surface normal, if estimated
+    pub normal: Option<[f32; 3]>,
 }
 
 #[derive(Debug, Clone, Default, Decode, Encode)]
@@ -62,20 +68,19 @@ impl Point {
         }
     }
 
-    pub fn to_rgb8_normalized(&self) -> [u8; 3] {
-        let r = self.color.r as f64 / 65535.0;
-        let g = self.color.g as f64 / 65535.0;
-        let b = self.color.b as f64 / 65535.0;
-
-        let r_linear = Self::srgb_to_linear(r);
-        let g_linear = Self::srgb_to_linear(g);
-        let b_linear = Self::srgb_to_linear(b);
+    /// Color as stored (sRGB), from 0 to 1
+    pub fn to_srgb(&self) -> [f64; 3] {
+        [self.color.r, self.color.g, self.color.b].map(|c| c as f64 / 65535.0)
+    }
 
-        let r8 = (r_linear * 255.0).round().clamp(0.0, 255.0) as u8;
-        let g8 = (g_linear * 255.0).round().clamp(0.0, 255.0) as u8;
-        let b8 = (b_linear * 255.0).round().clamp(0.0, 255.0) as u8;
+    /// Color with the sRGB transfer function removed, from 0 to 1
+    pub fn to_linear_rgb(&self) -> [f64; 3] {
+        self.to_srgb().map(Self::srgb_to_linear)
+    }
 
-        [r8, g8, b8]
+    pub fn to_rgb8_normalized(&self) -> [u8; 3] {
+        self.to_linear_rgb()
+            .map(|c| (c * 255.0).round().clamp(0.0, 255.0) as u8)
     }
 }
 
@@ -121,6 +126,7 @@ impl PointCloud {
             epsg,
             scale: [fixed_scale, fixed_scale, fixed_scale],
             offset,
+            attributes: AvailableAttributes::default(),
             other: HashMap::new(),
         };
 
@@ -165,5 +171,48 @@ pub struct Metadata {
     pub epsg: EpsgCode,
     pub scale: [f64; 3],
     pub offset: [f64; 3],
+    /// Attributes the input actually stores, as opposed to the defaults filled in by the readers
+    pub attributes: AvailableAttributes,
     pub other: HashMap<String, String>,
 }
+
+/// Which optional attributes the input provides; every attribute is assumed present by default
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AvailableAttributes {
+    pub color: bool,
+    pub intensity: bool,
+    pub classification: bool,
+    pub return_number: bool,
+    pub scan_angle: bool,
+    pub point_source_id: bool,
+    pub gps_time: bool,
+}
+
+impl Default for AvailableAttributes {
+    fn default() -> Self {
+        Self {
+            color: true,
+            intensity: true,
+            classification: true,
+            return_number: true,
+            scan_angle: true,
+            point_source_id: true,
+            gps_time: true,
+        }
+    }
+}
+
+impl AvailableAttributes {
+    /// Attributes provided by any of the two
+    pub fn merge(&self, other: &Self) -> Self {
+        Self {
+            color: self.color || other.color,
+            intensity: self.intensity || other.intensity,
+            classification: self.classification || other.classification,
+            return_number: self.return_number || other.return_number,
+            scan_angle: self.scan_angle || other.scan_angle,
+            point_source_id: self.point_source_id || other.point_source_id,
+            gps_time: self.gps_time || other.gps_time,
+        }
+    }
+}
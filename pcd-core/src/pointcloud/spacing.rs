@@ -0,0 +1,58 @@
+use crate::pointcloud::normal::Grid;
+
+/// Distance from every point to its nearest neighbour. Points sharing their position with
+/// another point are left out, since their distance says nothing about the spacing.
+pub fn nearest_neighbour_distances(positions: &[[f64; 3]]) -> Vec<f64> {
+    let grid = Grid::new(positions, 2);
+    positions
+        .iter()
+        .map(|&position| {
+            // The nearest two are the point itself and its neighbour
+            grid.nearest(positions, position, 2)
+                .into_iter()
+                .map(|i| {
+                    let d = std::array::from_fn::<f64, 3, _>(|axis| {
+                        positions[i][axis] - position[axis]
+                    });
+                    (d[0] * d[0] + d[1] * d[1] + d[2] * d[2]).sqrt()
+                })
+                .fold(0.0, f64::max)
+        })
+        .filter(|&distance| distance > 0.0)
+        .collect()
+}
+
+/// Median of the distances, or `None` if there are none
+pub fn median(mut distances: Vec<f64>) -> Option<f64> {
+    if distances.is_empty() {
+        return None;
+    }
+    let middle = distances.len() / 2;
+    let (_, median, _) = distances.select_nth_unstable_by(middle, f64::total_cmp);
+    Some(*median)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn distances_follow_the_points_not_their_bounding_box() {
+        // A diagonal line of points 0.5 apart, which covers a tiny part of its bounding box
+        let corridor = (0..1000)
+            .map(|i| {
+                let t = f64::from(i) * 0.5 / 3f64.sqrt();
+                [t, t, t]
+            })
+            .collect::<Vec<_>>();
+        let distances = nearest_neighbour_distances(&corridor);
+        assert_eq!(distances.len(), corridor.len());
+        let spacing = median(distances).unwrap();
+        assert!((spacing - 0.5).abs() < 1e-9, "{spacing}");
+
+        // Duplicates are left out
+        let distances = nearest_neighbour_distances(&[[0.0; 3], [0.0; 3], [2.0, 0.0, 0.0]]);
+        assert_eq!(distances, [2.0]);
+        assert_eq!(median(Vec::new()), None);
+    }
+}
@@ -0,0 +1,350 @@
+use std::{collections::HashMap, str::FromStr};
+
+use byteorder::{ByteOrder as _, LittleEndian};
+use cesiumtiles::gltf_extensions::gltf::ext_structural_metadata::{
+    Class, ClassProperty, ClassPropertyComponentType, ClassPropertyType, Schema,
+};
+use cesiumtiles_gltf_json::ComponentType;
+use pcd_core::pointcloud::point::{AvailableAttributes, Point};
+use serde_json::{Value, json};
+
+use crate::features::SOURCE_CLASS;
+
+/// glTF extension carrying the schema and property attributes of the point attributes
+pub const EXT_STRUCTURAL_METADATA: &str = "EXT_structural_metadata";
+
+/// Class of the point properties in the GLB schema
+const POINT_CLASS: &str = "point";
+
+/// Bytes of one attribute in the interleaved vertex; every attribute is padded to 4 bytes so
+/// that all accessors stay aligned
+pub const ATTRIBUTE_SLOT_BYTES: usize = 4;
+
+/// Point attributes that can be written to the GLB as `_`-prefixed vertex attributes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PointAttribute {
+    Intensity,
+    Classification,
+    ReturnNumber,
+    ScanAngle,
+    PointSourceId,
+    /// Stored as 32-bit floats relative to the earliest time of the GLB, with the property
+    /// `offset` restoring the absolute time
+    GpsTime,
+}
+
+impl FromStr for PointAttribute {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "intensity" => Ok(Self::Intensity),
+            "classification" => Ok(Self::Classification),
+            "return-number" => Ok(Self::ReturnNumber),
+            "scan-angle" => Ok(Self::ScanAngle),
+            "point-source-id" => Ok(Self::PointSourceId),
+            "gps-time" => Ok(Self::GpsTime),
+            _ => Err(format!(
+                "unknown point attribute {s:?} (expected intensity, classification, \
+                 return-number, scan-angle, point-source-id or gps-time)"
+            )),
+        }
+    }
+}
+
+impl PointAttribute {
+    /// Name of the glTF vertex attribute
+    pub fn semantic(self) -> &'static str {
+        match self {
+            Self::Intensity => "_INTENSITY",
+            Self::Classification => "_CLASSIFICATION",
+            Self::ReturnNumber => "_RETURN_NUMBER",
+            Self::ScanAngle => "_SCAN_ANGLE",
+            Self::PointSourceId => "_POINT_SOURCE_ID",
+            Self::GpsTime => "_GPS_TIME",
+        }
+    }
+
+    /// ID of the property in the schema
+    pub fn property_id(self) -> &'static str {
+        match self {
+            Self::Intensity => "intensity",
+            Self::Classification => "classification",
+            Self::ReturnNumber => "returnNumber",
+            Self::ScanAngle => "scanAngle",
+            Self::PointSourceId => "pointSourceId",
+            Self::GpsTime => "gpsTime",
+        }
+    }
+
+    pub fn component_type(self) -> ComponentType {
+        match self {
+            Self::Intensity | Self::PointSourceId => ComponentType::UnsignedShort,
+            Self::Classification | Self::ReturnNumber => ComponentType::UnsignedByte,
+            Self::ScanAngle | Self::GpsTime => ComponentType::Float,
+        }
+    }
+
+    fn property_component_type(self) -> ClassPropertyComponentType {
+        match self.component_type() {
+            ComponentType::UnsignedShort => ClassPropertyComponentType::Uint16,
+            ComponentType::UnsignedByte => ClassPropertyComponentType::Uint8,
+            _ => ClassPropertyComponentType::Float32,
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            Self::Intensity => "Pulse return magnitude",
+            Self::Classification => "ASPRS classification",
+            Self::ReturnNumber => "Return number of the pulse",
+            Self::ScanAngle => "Scan angle (degrees)",
+            Self::PointSourceId => "Source (flight line) of the point",
+            Self::GpsTime => "GPS time of the point",
+        }
+    }
+
+    /// Whether the inputs store the attribute, rather than leaving it to the readers' defaults
+    pub fn is_available(self, attributes: &AvailableAttributes) -> bool {
+        match self {
+            Self::Intensity => attributes.intensity,
+            Self::Classification => attributes.classification,
+            Self::ReturnNumber => attributes.return_number,
+            Self::ScanAngle => attributes.scan_angle,
+            Self::PointSourceId => attributes.point_source_id,
+            Self::GpsTime => attributes.gps_time,
+        }
+    }
+
+    /// Value written for points without the attribute, declared as the `noData` of the property
+    pub fn no_data(self) -> f64 {
+        match self.component_type() {
+            ComponentType::UnsignedShort => f64::from(u16::MAX),
+            ComponentType::UnsignedByte => f64::from(u8::MAX),
+            _ => f64::from(f32::MIN),
+        }
+    }
+
+    /// Value of the attribute, if the point has one
+    pub fn value(self, point: &Point) -> Option<f64> {
+        let attributes = &point.attributes;
+        match self {
+            Self::Intensity => attributes.intensity.map(f64::from),
+            Self::Classification => attributes
+                .classification
+                .as_deref()
+                .and_then(|class| u8::from_str(class).ok())
+                .map(f64::from),
+            Self::ReturnNumber => attributes.return_number.map(f64::from),
+            Self::ScanAngle => attributes.scan_angle.map(f64::from),
+            Self::PointSourceId => attributes.point_source_id.map(f64::from),
+            Self::GpsTime => attributes.gps_time,
+        }
+    }
+
+    /// Writes the value of the point minus the offset into its slot; missing values are written
+    /// as [`Self::no_data`]
+    pub fn write(
+        self,
+        point: &Point,
+        encoding: AttributeEncoding,
+        slot: &mut [u8; ATTRIBUTE_SLOT_BYTES],
+    ) {
+        let value = match self.value(point) {
+            Some(value) => value - encoding.offset,
+            None => self.no_data(),
+        };
+        *slot = [0; ATTRIBUTE_SLOT_BYTES];
+        match self.component_type() {
+            ComponentType::UnsignedShort => LittleEndian::write_u16(&mut slot[..2], value as u16),
+            ComponentType::UnsignedByte => slot[0] = value as u8,
+            _ => LittleEndian::write_f32(slot, value as f32),
+        }
+    }
+}
+
+/// How the values of an attribute are stored in one GLB
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AttributeEncoding {
+    /// Subtracted from every value
+    pub offset: f64,
+    /// Whether any point lacks the attribute, so that the property declares `noData`
+    pub has_missing: bool,
+}
+
+/// Encodings of the attribute values of `points`: offset by the earliest GPS time, and by 0 for
+/// the other attributes
+pub fn attribute_encodings<'a>(
+    attributes: &[PointAttribute],
+    points: impl Iterator<Item = &'a Point> + Clone,
+) -> Vec<AttributeEncoding> {
+    attributes
+        .iter()
+        .map(|&attribute| {
+            let offset = match attribute {
+                PointAttribute::GpsTime => points
+                    .clone()
+                    .filter_map(|point| attribute.value(point))
+                    .reduce(f64::min)
+                    .unwrap_or(0.0),
+                _ => 0.0,
+            };
+            let has_missing = points.clone().any(|point| attribute.value(point).is_none());
+            AttributeEncoding {
+                offset,
+                has_missing,
+            }
+        })
+        .collect()
+}
+
+/// Root `EXT_structural_metadata` object of a GLB with the given attributes, whose values were
+/// written with `encodings`, and with the class and `propertyTables` entry of its feature IDs,
+/// if any
+pub fn structural_metadata(
+    attributes: &[PointAttribute],
+    encodings: &[AttributeEncoding],
+    property_table: Option<(Class, Value)>,
+) -> Value {
+    let mut classes = HashMap::new();
+    let mut metadata = json!({});
+
+    if !attributes.is_empty() {
+        let class = Class {
+            name: Some("Point".to_string()),
+            properties: attributes
+                .iter()
+                .zip(encodings)
+                .map(|(&attribute, encoding)| {
+                    let property = ClassProperty {
+                        description: Some(attribute.description().to_string()),
+                        type_: ClassPropertyType::Scalar,
+                        component_type: Some(attribute.property_component_type()),
+                        no_data: encoding.has_missing.then(|| json!(attribute.no_data())),
+                        ..Default::default()
+                    };
+                    (attribute.property_id().to_string(), property)
+                })
+                .collect(),
+            ..Default::default()
+        };
+        classes.insert(POINT_CLASS.to_string(), class);
+
+        let properties = attributes
+            .iter()
+            .zip(encodings)
+            .map(|(&attribute, encoding)| {
+                let mut property = json!({ "attribute": attribute.semantic() });
+                if encoding.offset != 0.0 {
+                    property["offset"] = json!(encoding.offset);
+                }
+                (attribute.property_id().to_string(), property)
+            })
+            .collect::<serde_json::Map<_, _>>();
+        metadata["propertyAttributes"] = json!([{
+            "class": POINT_CLASS,
+            "properties": properties,
+        }]);
+    }
+
+    if let Some((class, table)) = property_table {
+        classes.insert(SOURCE_CLASS.to_string(), class);
+        metadata["propertyTables"] = json!([table]);
+    }
+
+    metadata["schema"] = json!(Schema {
+        id: "ptiler_points".to_string(),
+        classes,
+        ..Default::default()
+    });
+    metadata
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pcd_core::pointcloud::point::{Color, PointAttributes};
+
+    fn point(gps_time: f64) -> Point {
+        Point {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+            color: Color::default(),
+            attributes: PointAttributes {
+                intensity: Some(1200),
+                return_number: Some(2),
+                classification: Some("6".to_string()),
+                scan_angle: Some(-12.5),
+                gps_time: Some(gps_time),
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn values_are_written_relative_to_the_offset() {
+        let points = [point(400_000.25), point(400_010.5)];
+        let attributes = [
+            PointAttribute::Intensity,
+            PointAttribute::Classification,
+            PointAttribute::GpsTime,
+            PointAttribute::PointSourceId,
+        ];
+        let encodings = attribute_encodings(&attributes, points.iter());
+        assert_eq!(
+            encodings.iter().map(|e| e.offset).collect::<Vec<_>>(),
+            [0.0, 0.0, 400_000.25, 0.0]
+        );
+        assert_eq!(
+            encodings.iter().map(|e| e.has_missing).collect::<Vec<_>>(),
+            [false, false, false, true]
+        );
+
+        let mut slot = [0; ATTRIBUTE_SLOT_BYTES];
+        PointAttribute::Intensity.write(&points[0], encodings[0], &mut slot);
+        assert_eq!(slot, [0xb0, 0x04, 0, 0]);
+        PointAttribute::Classification.write(&points[0], encodings[1], &mut slot);
+        assert_eq!(slot, [6, 0, 0, 0]);
+        PointAttribute::GpsTime.write(&points[1], encodings[2], &mut slot);
+        assert_eq!(LittleEndian::read_f32(&slot), 10.25);
+        // Missing values are written as the noData sentinel, not as 0
+        PointAttribute::PointSourceId.write(&points[1], encodings[3], &mut slot);
+        assert_eq!(slot, [0xff, 0xff, 0, 0]);
+        let mut missing = point(0.0);
+        missing.attributes.gps_time = None;
+        PointAttribute::GpsTime.write(&missing, encodings[2], &mut slot);
+        assert_eq!(LittleEndian::read_f32(&slot), f32::MIN);
+    }
+
+    #[test]
+    fn property_attributes_reference_the_schema() {
+        let attributes = [PointAttribute::ScanAngle, PointAttribute::GpsTime];
+        let encodings = [
+            AttributeEncoding {
+                offset: 0.0,
+                has_missing: true,
+            },
+            AttributeEncoding {
+                offset: 100.0,
+                has_missing: false,
+            },
+        ];
+        let metadata = structural_metadata(&attributes, &encodings, None);
+
+        let class = &metadata["schema"]["classes"]["point"]["properties"];
+        assert_eq!(class["scanAngle"]["componentType"], "FLOAT32");
+        assert_eq!(class["scanAngle"]["noData"], f64::from(f32::MIN));
+        assert_eq!(class["gpsTime"]["type"], "SCALAR");
+        assert!(class["gpsTime"].get("noData").is_none());
+
+        let property_attribute = &metadata["propertyAttributes"][0];
+        assert_eq!(property_attribute["class"], "point");
+        let properties = &property_attribute["properties"];
+        assert_eq!(
+            properties["scanAngle"],
+            json!({ "attribute": "_SCAN_ANGLE" })
+        );
+        assert_eq!(properties["gpsTime"]["offset"], 100.0);
+    }
+}
@@ -3,10 +3,14 @@ use std::collections::HashMap;
 use pcd_core::pointcloud::point::{Point, PointCloud};
 use tinymvt::TileZXY;
 
-use crate::tiling::{self, TileContent};
+use crate::tiling::{self, Subdivision, TileContent, TileZXYH};
 
-pub fn make_tile_content(tile_coord: &TileZXY, point_cloud: &PointCloud) -> TileContent {
-    let (tile_zoom, tile_x, tile_y) = tile_coord;
+pub fn make_tile_content(
+    tile_coord: &TileZXYH,
+    subdivision: Subdivision,
+    point_cloud: &PointCloud,
+) -> TileContent {
+    let (tile_zoom, tile_x, tile_y, tile_h) = tile_coord;
 
     let min_lng = point_cloud.metadata.bounding_volume.min[0];
     let max_lng = point_cloud.metadata.bounding_volume.max[0];
@@ -15,10 +19,11 @@ pub fn make_tile_content(tile_coord: &TileZXY, point_cloud: &PointCloud) -> Tile
     let min_height = point_cloud.metadata.bounding_volume.min[2];
     let max_height = point_cloud.metadata.bounding_volume.max[2];
 
-    let content_path = { format!("{tile_zoom}/{tile_x}/{tile_y}.glb") };
+    let content_path = subdivision.content_path(*tile_coord);
 
     TileContent {
         zxy: (*tile_zoom, *tile_x, *tile_y),
+        h: *tile_h,
         content_path,
         min_lng,
         max_lng,
@@ -27,6 +32,10 @@ pub fn make_tile_content(tile_coord: &TileZXY, point_cloud: &PointCloud) -> Tile
         min_height,
         max_height,
         translation: [0.0; 3],
+        bounding_volume: None,
+        point_spacing: None,
+        point_count: point_cloud.points.len() as u64,
+        group: None,
     }
 }
 
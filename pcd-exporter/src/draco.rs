@@ -0,0 +1,622 @@
+/// glTF extension carrying Draco-compressed primitives
+pub const KHR_DRACO_MESH_COMPRESSION: &str = "KHR_draco_mesh_compression";
+
+/// Draco attribute ID of the positions
+pub const POSITION_ID: u32 = 0;
+
+/// Draco attribute ID of the colors
+pub const COLOR_ID: u32 = 1;
+
+/// Draco bitstream version 2.2
+const VERSION: [u8; 2] = [2, 2];
+const ENCODER_TYPE_POINT_CLOUD: u8 = 0;
+const ENCODING_METHOD_SEQUENTIAL: u8 = 0;
+
+const ATTRIBUTE_TYPE_POSITION: u8 = 0;
+const ATTRIBUTE_TYPE_COLOR: u8 = 2;
+const DATA_TYPE_FLOAT32: u8 = 9;
+
+const SEQUENTIAL_ENCODER_QUANTIZATION: u8 = 2;
+const PREDICTION_DIFFERENCE: u8 = 0;
+const PREDICTION_TRANSFORM_WRAP: u8 = 1;
+const SYMBOL_CODING_RAW: u8 = 1;
+
+/// Largest symbol bit length that Draco stores with the raw rANS scheme
+const MAX_RAW_SYMBOL_BITS: u32 = 18;
+
+/// Base of the byte-wise renormalization of the rANS state
+const RANS_IO_BASE: u32 = 256;
+
+/// Quantization of the Draco-compressed attributes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DracoOptions {
+    pub position_bits: u8,
+    pub color_bits: u8,
+}
+
+impl Default for DracoOptions {
+    fn default() -> Self {
+        Self {
+            position_bits: 14,
+            color_bits: 8,
+        }
+    }
+}
+
+/// Encodes the positions and linear colors (0-1), if any, of a point cloud as a sequential
+/// Draco point cloud, with attribute IDs [`POSITION_ID`] and [`COLOR_ID`]. The quantized values
+/// are predicted from the previous point and the corrections entropy coded with rANS, as the
+/// Draco encoder does for sequential point clouds; point order is kept.
+pub fn encode_point_cloud(
+    positions: &[[f32; 3]],
+    colors: Option<&[[f32; 3]]>,
+    options: DracoOptions,
+) -> Vec<u8> {
+    let mut attributes = vec![(
+        ATTRIBUTE_TYPE_POSITION,
+        POSITION_ID,
+        positions,
+        Quantization::new(positions, options.position_bits),
+    )];
+    if let Some(colors) = colors {
+        assert_eq!(positions.len(), colors.len());
+        attributes.push((
+            ATTRIBUTE_TYPE_COLOR,
+            COLOR_ID,
+            colors,
+            Quantization::new(colors, options.color_bits),
+        ));
+    }
+
+    let mut bytes = b"DRACO".to_vec();
+    bytes.extend_from_slice(&VERSION);
+    bytes.extend_from_slice(&[ENCODER_TYPE_POINT_CLOUD, ENCODING_METHOD_SEQUENTIAL]);
+    bytes.extend_from_slice(&0u16.to_le_bytes()); // flags
+    bytes.extend_from_slice(&(positions.len() as i32).to_le_bytes());
+
+    // One attributes decoder holding every attribute
+    bytes.push(1);
+    write_varint(&mut bytes, attributes.len() as u32);
+    for (attribute_type, id, _, _) in &attributes {
+        bytes.extend_from_slice(&[*attribute_type, DATA_TYPE_FLOAT32, 3, 0]);
+        write_varint(&mut bytes, *id);
+    }
+    bytes.resize(
+        bytes.len() + attributes.len(),
+        SEQUENTIAL_ENCODER_QUANTIZATION,
+    );
+
+    // Values of every attribute come first, then the parameters to dequantize them
+    for (_, _, values, quantization) in &attributes {
+        quantization.write_values(&mut bytes, values);
+    }
+    for (_, _, _, quantization) in &attributes {
+        quantization.write_parameters(&mut bytes);
+    }
+    bytes
+}
+
+/// Quantization of one attribute to `bits` over the largest range of its components
+struct Quantization {
+    min: [f32; 3],
+    range: f32,
+    bits: u8,
+}
+
+impl Quantization {
+    fn new(values: &[[f32; 3]], bits: u8) -> Self {
+        let mut min = [f32::MAX; 3];
+        let mut max = [f32::MIN; 3];
+        for value in values {
+            for axis in 0..3 {
+                min[axis] = min[axis].min(value[axis]);
+                max[axis] = max[axis].max(value[axis]);
+            }
+        }
+        let range = (0..3).map(|axis| max[axis] - min[axis]).fold(0.0, f32::max);
+        Self {
+            min,
+            range: if range > 0.0 { range } else { 1.0 },
+            bits,
+        }
+    }
+
+    fn max_quantized_value(&self) -> u32 {
+        (1 << self.bits) - 1
+    }
+
+    /// Writes the quantized values as differences to the previous point (with the wrap
+    /// transform), entropy coded with the raw rANS scheme when their symbols are short enough
+    fn write_values(&self, bytes: &mut Vec<u8>, values: &[[f32; 3]]) {
+        let inverse_delta = self.max_quantized_value() as f32 / self.range;
+        let quantized = values
+            .iter()
+            .flat_map(|value| {
+                std::array::from_fn::<i32, 3, _>(|axis| {
+                    let quantized = ((value[axis] - self.min[axis]) * inverse_delta + 0.5).floor();
+                    (quantized as u32).min(self.max_quantized_value()) as i32
+                })
+            })
+            .collect::<Vec<_>>();
+        let wrap = WrapTransform::new(&quantized);
+        let symbols = (0..quantized.len())
+            .map(|i| {
+                let predicted = if i < 3 { 0 } else { quantized[i - 3] };
+                signed_to_symbol(wrap.correction(quantized[i], predicted))
+            })
+            .collect::<Vec<_>>();
+
+        bytes.extend_from_slice(&[PREDICTION_DIFFERENCE, PREDICTION_TRANSFORM_WRAP]);
+        let max_symbol = symbols.iter().copied().max().unwrap_or(0);
+        let symbol_bits = u32::BITS - max_symbol.leading_zeros();
+        if symbol_bits <= MAX_RAW_SYMBOL_BITS {
+            bytes.push(1); // compressed
+            write_raw_symbols(bytes, &symbols);
+        } else {
+            // Stored in the fewest whole bytes that fit the symbols
+            let num_bytes = symbol_bits.div_ceil(8) as usize;
+            bytes.extend_from_slice(&[0, num_bytes as u8]);
+            for symbol in symbols {
+                bytes.extend_from_slice(&symbol.to_le_bytes()[..num_bytes]);
+            }
+        }
+        bytes.extend_from_slice(&wrap.min.to_le_bytes());
+        bytes.extend_from_slice(&wrap.max.to_le_bytes());
+    }
+
+    fn write_parameters(&self, bytes: &mut Vec<u8>) {
+        for min in self.min {
+            bytes.extend_from_slice(&min.to_le_bytes());
+        }
+        bytes.extend_from_slice(&self.range.to_le_bytes());
+        bytes.push(self.bits);
+    }
+}
+
+/// Wrap transform of the Draco prediction schemes, which maps corrections into the range
+/// of the values so that they never need more bits than the values themselves
+struct WrapTransform {
+    min: i32,
+    max: i32,
+}
+
+impl WrapTransform {
+    fn new(values: &[i32]) -> Self {
+        Self {
+            min: values.iter().copied().min().unwrap_or(0),
+            max: values.iter().copied().max().unwrap_or(0),
+        }
+    }
+
+    fn correction(&self, value: i32, predicted: i32) -> i32 {
+        let max_difference = 1 + self.max - self.min;
+        let max_correction = max_difference / 2 - i32::from(max_difference % 2 == 0);
+        let correction = value - predicted.clamp(self.min, self.max);
+        if correction < -(max_difference / 2) {
+            correction + max_difference
+        } else if correction > max_correction {
+            correction - max_difference
+        } else {
+            correction
+        }
+    }
+}
+
+/// Zigzag mapping of Draco from signed values to symbols
+fn signed_to_symbol(value: i32) -> u32 {
+    if value >= 0 {
+        (value as u32) << 1
+    } else {
+        (((-(value + 1)) as u32) << 1) | 1
+    }
+}
+
+/// Writes the symbols with the raw rANS scheme of Draco: a probability table over all symbol
+/// values followed by the rANS stream, which is encoded backwards so that it decodes forwards
+fn write_raw_symbols(bytes: &mut Vec<u8>, symbols: &[u32]) {
+    if symbols.is_empty() {
+        return;
+    }
+    let mut frequencies = vec![0u64; *symbols.iter().max().unwrap() as usize + 1];
+    for &symbol in symbols {
+        frequencies[symbol as usize] += 1;
+    }
+    let unique_symbols = frequencies.iter().filter(|&&f| f > 0).count() as u32;
+    let unique_symbols_bits = (u32::BITS - unique_symbols.leading_zeros()).clamp(1, 18);
+    let precision_bits = (3 * unique_symbols_bits / 2).clamp(12, 20);
+    bytes.extend_from_slice(&[SYMBOL_CODING_RAW, unique_symbols_bits as u8]);
+
+    let probabilities = rans_probabilities(&frequencies, 1 << precision_bits);
+    write_varint(bytes, probabilities.len() as u32);
+    let mut symbol = 0;
+    while symbol < probabilities.len() {
+        let probability = probabilities[symbol];
+        if probability == 0 {
+            // Runs of up to 64 zero probabilities take one byte
+            let run = probabilities[symbol + 1..]
+                .iter()
+                .take(63)
+                .take_while(|&&p| p == 0)
+                .count();
+            bytes.push(((run as u8) << 2) | 3);
+            symbol += run + 1;
+        } else {
+            let extra_bytes = match probability {
+                0..64 => 0,
+                64..16384 => 1,
+                _ => 2,
+            };
+            bytes.push(((probability << 2) as u8) | extra_bytes as u8);
+            for b in 0..extra_bytes {
+                bytes.push((probability >> (8 * (b + 1) - 2)) as u8);
+            }
+            symbol += 1;
+        }
+    }
+
+    let mut cumulative = Vec::with_capacity(probabilities.len());
+    let mut total = 0;
+    for &probability in &probabilities {
+        cumulative.push(total);
+        total += probability;
+    }
+    let precision = 1u32 << precision_bits;
+    let rans_base = 4 * precision;
+    let mut stream = Vec::new();
+    let mut state = rans_base;
+    for &symbol in symbols.iter().rev() {
+        let probability = probabilities[symbol as usize];
+        while state >= rans_base / precision * RANS_IO_BASE * probability {
+            stream.push((state % RANS_IO_BASE) as u8);
+            state /= RANS_IO_BASE;
+        }
+        state =
+            (state / probability) * precision + state % probability + cumulative[symbol as usize];
+    }
+    // The final state goes last, with its byte length in the top two bits
+    let state = state - rans_base;
+    let (tag, length) = match state {
+        0..0x40 => (0, 1),
+        0x40..0x4000 => (1, 2),
+        0x4000..0x40_0000 => (2, 3),
+        _ => (3, 4),
+    };
+    let tagged = state | (tag << (8 * length - 2));
+    stream.extend_from_slice(&tagged.to_le_bytes()[..length as usize]);
+
+    write_varint(bytes, stream.len() as u32);
+    bytes.extend_from_slice(&stream);
+}
+
+/// Symbol probabilities scaled to sum up to `precision`, keeping every occurring symbol at
+/// least 1
+fn rans_probabilities(frequencies: &[u64], precision: u32) -> Vec<u32> {
+    let total = frequencies.iter().sum::<u64>() as f64;
+    let mut probabilities = frequencies
+        .iter()
+        .map(|&frequency| match frequency {
+            0 => 0,
+            _ => ((frequency as f64 / total * f64::from(precision) + 0.5) as u32).max(1),
+        })
+        .collect::<Vec<_>>();
+    loop {
+        let sum = probabilities.iter().sum::<u32>();
+        if sum == precision {
+            return probabilities;
+        }
+        // Rounding errors are taken from or given to the most probable symbol
+        let probability = probabilities.iter_mut().max().unwrap();
+        if sum < precision {
+            *probability += precision - sum;
+        } else {
+            *probability -= (sum - precision).min(*probability - 1);
+        }
+    }
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut value: u32) {
+    while value >= 0x80 {
+        bytes.push((value & 0x7f) as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+#[cfg(test)]
+mod tests {
+    use byteorder::{ByteOrder as _, LittleEndian};
+
+    use super::*;
+
+    struct Reader<'a> {
+        bytes: &'a [u8],
+        cursor: usize,
+    }
+
+    impl Reader<'_> {
+        fn take(&mut self, length: usize) -> &[u8] {
+            self.cursor += length;
+            &self.bytes[self.cursor - length..self.cursor]
+        }
+
+        fn u8(&mut self) -> u8 {
+            self.take(1)[0]
+        }
+
+        fn u32(&mut self, length: usize) -> u32 {
+            let mut value = [0u8; 4];
+            value[..length].copy_from_slice(self.take(length));
+            u32::from_le_bytes(value)
+        }
+
+        fn varint(&mut self) -> u32 {
+            let mut value = 0;
+            for shift in (0..).step_by(7) {
+                let byte = self.u8();
+                value |= u32::from(byte & 0x7f) << shift;
+                if byte & 0x80 == 0 {
+                    break;
+                }
+            }
+            value
+        }
+    }
+
+    /// Reads `count` symbols coded with the raw rANS scheme, following the Draco decoder
+    fn decode_raw_symbols(reader: &mut Reader, count: usize) -> Vec<u32> {
+        assert_eq!(reader.u8(), SYMBOL_CODING_RAW);
+        let precision_bits = (3 * u32::from(reader.u8()) / 2).clamp(12, 20);
+        let precision = 1u32 << precision_bits;
+
+        let num_symbols = reader.varint() as usize;
+        let mut probabilities = Vec::new();
+        while probabilities.len() < num_symbols {
+            let data = reader.u8();
+            if data & 3 == 3 {
+                probabilities.resize(probabilities.len() + usize::from(data >> 2) + 1, 0);
+            } else {
+                let mut probability = u32::from(data >> 2);
+                for b in 0..u32::from(data & 3) {
+                    probability |= u32::from(reader.u8()) << (8 * (b + 1) - 2);
+                }
+                probabilities.push(probability);
+            }
+        }
+        assert_eq!(probabilities.len(), num_symbols);
+        assert_eq!(probabilities.iter().sum::<u32>(), precision);
+
+        let length = reader.varint() as usize;
+        let stream = reader.take(length);
+        let state_length = usize::from(stream[length - 1] >> 6) + 1;
+        let mut offset = length - state_length;
+        let mut state_bytes = [0u8; 4];
+        state_bytes[..state_length].copy_from_slice(&stream[offset..]);
+        let rans_base = 4 * precision;
+        let mut state =
+            (u32::from_le_bytes(state_bytes) & ((1 << (8 * state_length - 2)) - 1)) + rans_base;
+        assert!(state < rans_base * RANS_IO_BASE);
+
+        (0..count)
+            .map(|_| {
+                while state < rans_base && offset > 0 {
+                    offset -= 1;
+                    state = state * RANS_IO_BASE + u32::from(stream[offset]);
+                }
+                let (quotient, remainder) = (state / precision, state % precision);
+                let mut cumulative = 0;
+                let symbol = probabilities
+                    .iter()
+                    .position(|&probability| {
+                        cumulative += probability;
+                        remainder < cumulative
+                    })
+                    .unwrap();
+                let probability = probabilities[symbol];
+                state = quotient * probability + remainder - (cumulative - probability);
+                symbol as u32
+            })
+            .collect()
+    }
+
+    /// Reads back the dequantized attributes of a stream written by [`encode_point_cloud`],
+    /// following the sequential point cloud decoder of Draco
+    fn decode(bytes: &[u8]) -> [Vec<[f32; 3]>; 2] {
+        let num_points = LittleEndian::read_i32(&bytes[11..15]) as usize;
+        let mut reader = Reader {
+            bytes,
+            cursor: 15 + 1 + 1 + 2 * 5 + 2,
+        };
+
+        let mut quantized = Vec::new();
+        for _ in 0..2 {
+            assert_eq!(reader.u8(), PREDICTION_DIFFERENCE);
+            assert_eq!(reader.u8(), PREDICTION_TRANSFORM_WRAP);
+            let count = num_points * 3;
+            let symbols = if reader.u8() == 1 {
+                decode_raw_symbols(&mut reader, count)
+            } else {
+                let num_bytes = usize::from(reader.u8());
+                (0..count).map(|_| reader.u32(num_bytes)).collect()
+            };
+            let min = reader.u32(4) as i32;
+            let max = reader.u32(4) as i32;
+            let max_difference = 1 + max - min;
+            let mut values = Vec::<i32>::with_capacity(count);
+            for (i, symbol) in symbols.into_iter().enumerate() {
+                let correction = if symbol & 1 == 0 {
+                    (symbol >> 1) as i32
+                } else {
+                    -((symbol >> 1) as i32) - 1
+                };
+                let predicted = if i < 3 { 0 } else { values[i - 3] };
+                let mut value = predicted.clamp(min, max) + correction;
+                if value > max {
+                    value -= max_difference;
+                } else if value < min {
+                    value += max_difference;
+                }
+                values.push(value);
+            }
+            quantized.push(values);
+        }
+
+        let quantized: [Vec<i32>; 2] = quantized.try_into().unwrap();
+        let decoded = quantized.map(|values| {
+            let min = std::array::from_fn::<f32, 3, _>(|_| f32::from_bits(reader.u32(4)));
+            let range = f32::from_bits(reader.u32(4));
+            let bits = reader.u8();
+            let delta = range / ((1u32 << bits) - 1) as f32;
+            values
+                .chunks_exact(3)
+                .map(|v| std::array::from_fn(|i| min[i] + v[i] as f32 * delta))
+                .collect()
+        });
+        assert_eq!(reader.cursor, bytes.len());
+        decoded
+    }
+
+    /// Points along a scan line with a few jumps, in the spatially coherent order of a tile
+    fn scan_line(count: usize) -> (Vec<[f32; 3]>, Vec<[f32; 3]>) {
+        let positions = (0..count)
+            .map(|i| {
+                let t = i as f32;
+                [
+                    t * 0.37 % 100.0,
+                    (t * 0.011).floor(),
+                    (t * 0.05).sin() * 3.0,
+                ]
+            })
+            .collect::<Vec<_>>();
+        let colors = (0..count)
+            .map(|i| {
+                let t = i as f32 / count as f32;
+                [t, 1.0 - t, if i % 7 == 0 { 1.0 } else { 0.25 }]
+            })
+            .collect();
+        (positions, colors)
+    }
+
+    fn assert_round_trip(positions: &[[f32; 3]], colors: &[[f32; 3]], options: DracoOptions) {
+        let bytes = encode_point_cloud(positions, Some(colors), options);
+        let [decoded_positions, decoded_colors] = decode(&bytes);
+        let range = Quantization::new(positions, options.position_bits).range;
+        let position_step = range / ((1u32 << options.position_bits) - 1) as f32;
+        let color_step = 1.0 / ((1u32 << options.color_bits) - 1) as f32;
+        for (decoded, original) in decoded_positions.iter().zip(positions) {
+            for axis in 0..3 {
+                let error = (decoded[axis] - original[axis]).abs();
+                assert!(
+                    error <= position_step / 2.0 + 1e-4,
+                    "{decoded:?} {original:?}"
+                );
+            }
+        }
+        for (decoded, original) in decoded_colors.iter().zip(colors) {
+            for axis in 0..3 {
+                assert!((decoded[axis] - original[axis]).abs() <= color_step / 2.0 + 1e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn attributes_round_trip_within_the_quantization_step() {
+        let positions = [[0.0, 0.0, 0.0], [12.5, -3.0, 0.25], [100.0, 40.0, 2.0]];
+        let colors = [[1.0, 0.0, 0.0], [0.5, 0.5, 0.5], [0.0, 0.2, 1.0]];
+        let options = DracoOptions {
+            position_bits: 11,
+            color_bits: 8,
+        };
+        let bytes = encode_point_cloud(&positions, Some(&colors), options);
+        assert_eq!(&bytes[..11], b"DRACO\x02\x02\x00\x00\x00\x00");
+        assert_round_trip(&positions, &colors, options);
+
+        let (positions, colors) = scan_line(5000);
+        assert_round_trip(&positions, &colors, options);
+        assert_round_trip(&positions, &colors, DracoOptions::default());
+    }
+
+    #[test]
+    fn long_symbols_are_stored_uncompressed() {
+        let (positions, colors) = scan_line(500);
+        let options = DracoOptions {
+            position_bits: 24,
+            color_bits: 8,
+        };
+        let bytes = encode_point_cloud(&positions, Some(&colors), options);
+        // The position corrections need more than 18 bits
+        assert_eq!(bytes[15 + 1 + 1 + 2 * 5 + 2 + 2], 0);
+        assert_round_trip(&positions, &colors, options);
+    }
+
+    #[test]
+    fn entropy_coding_shrinks_coherent_points() {
+        let (positions, colors) = scan_line(5000);
+        let bytes = encode_point_cloud(&positions, Some(&colors), DracoOptions::default());
+        // 2 bytes per position component and 2 per color component without entropy coding
+        assert!(bytes.len() < 5000 * 12 / 2, "{} bytes", bytes.len());
+    }
+
+    #[test]
+    fn probabilities_sum_up_to_the_precision() {
+        let probabilities = rans_probabilities(&[1, 0, 1_000_000, 3, 0, 1], 4096);
+        assert_eq!(probabilities.iter().sum::<u32>(), 4096);
+        assert_eq!(probabilities[1], 0);
+        assert!(
+            probabilities
+                .iter()
+                .enumerate()
+                .all(|(i, &p)| p > 0 || i % 3 == 1)
+        );
+    }
+
+    /// Decodes a stream with `draco_decoder` of the Draco library, which has to be on `PATH`,
+    /// and compares the decoded attributes
+    #[test]
+    #[ignore = "needs draco_decoder on PATH"]
+    fn reference_decoder_reads_the_stream() {
+        let dir = std::env::temp_dir().join(format!("draco-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (positions, colors) = scan_line(2000);
+        let bytes = encode_point_cloud(&positions, Some(&colors), DracoOptions::default());
+        std::fs::write(dir.join("points.drc"), &bytes).unwrap();
+
+        let status = std::process::Command::new("draco_decoder")
+            .args(["-i", "points.drc", "-o", "points.ply"])
+            .current_dir(&dir)
+            .status()
+            .unwrap();
+        assert!(status.success());
+        // Binary PLY with x, y, z, red, green and blue as floats
+        let ply = std::fs::read(dir.join("points.ply")).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        let header_end = ply.windows(11).position(|w| w == b"end_header\n").unwrap() + 11;
+        let header = String::from_utf8_lossy(&ply[..header_end]);
+        assert!(header.contains("binary_little_endian"));
+        assert!(header.contains("element vertex 2000"));
+        assert_eq!(header.matches("property float").count(), 6);
+        let values = ply[header_end..]
+            .chunks_exact(4)
+            .map(LittleEndian::read_f32)
+            .collect::<Vec<_>>();
+        assert_eq!(values.len(), 2000 * 6);
+
+        let [expected_positions, expected_colors] = decode(&bytes);
+        for ((vertex, position), color) in values
+            .chunks_exact(6)
+            .zip(expected_positions)
+            .zip(expected_colors)
+        {
+            for (decoded, expected) in vertex.iter().zip(position.iter().chain(&color)) {
+                assert!((decoded - expected).abs() <= 1e-5, "{vertex:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn varints_use_seven_bits_per_byte() {
+        let mut bytes = Vec::new();
+        write_varint(&mut bytes, 1);
+        write_varint(&mut bytes, 300);
+        assert_eq!(bytes, [1, 0xac, 0x02]);
+    }
+}
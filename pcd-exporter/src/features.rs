@@ -0,0 +1,290 @@
+use std::{collections::BTreeMap, ops::Range, str::FromStr};
+
+use byteorder::{ByteOrder as _, LittleEndian};
+use cesiumtiles::gltf_extensions::gltf::ext_structural_metadata::{
+    Class, ClassProperty, ClassPropertyComponentType, ClassPropertyType,
+};
+use pcd_core::pointcloud::point::Point;
+use serde_json::{Value, json};
+
+/// glTF extension carrying the feature IDs of the points
+pub const EXT_MESH_FEATURES: &str = "EXT_mesh_features";
+
+/// Vertex attribute of the feature IDs
+pub const FEATURE_ID_SEMANTIC: &str = "_FEATURE_ID_0";
+
+/// Class of the property table rows in the GLB schema
+pub const SOURCE_CLASS: &str = "source";
+
+/// Property table buffer views must start at multiples of 8 bytes
+const PROPERTY_TABLE_ALIGNMENT: usize = 8;
+
+/// What a feature ID identifies, so that a picked point can be traced back to its input
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeatureIdSource {
+    /// One feature per input file
+    SourceFile,
+    /// One feature per flight line (LAS point source ID) of each input file
+    PointSourceId,
+    /// One feature per point, with the index of the point within its input file
+    Point,
+}
+
+impl FromStr for FeatureIdSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "source-file" => Ok(Self::SourceFile),
+            "point-source-id" => Ok(Self::PointSourceId),
+            "point" => Ok(Self::Point),
+            _ => Err(format!(
+                "unknown feature ID source {s:?} (expected source-file, point-source-id or point)"
+            )),
+        }
+    }
+}
+
+/// Source file, point source ID and point index identifying a feature
+type FeatureKey = (Option<u32>, Option<u16>, Option<u64>);
+
+impl FeatureIdSource {
+    fn key(self, point: &Point) -> FeatureKey {
+        let attributes = &point.attributes;
+        match self {
+            Self::SourceFile => (attributes.source_file, None, None),
+            Self::PointSourceId => (attributes.source_file, attributes.point_source_id, None),
+            Self::Point => (attributes.source_file, None, attributes.point_index),
+        }
+    }
+}
+
+/// Feature IDs of the points of one GLB, indexing the rows of its property table
+#[derive(Debug, Clone)]
+pub struct SourceFeatures {
+    source: FeatureIdSource,
+    /// Source file, point source ID and point index of each feature, in feature ID order
+    keys: Vec<FeatureKey>,
+    /// Feature ID of each point
+    pub ids: Vec<u32>,
+}
+
+impl SourceFeatures {
+    pub fn new<'a>(
+        source: FeatureIdSource,
+        points: impl Iterator<Item = &'a Point> + Clone,
+    ) -> Self {
+        let mut keys = points
+            .clone()
+            .map(|point| (source.key(point), 0))
+            .collect::<BTreeMap<_, u32>>();
+        for (id, feature_id) in keys.values_mut().enumerate() {
+            *feature_id = id as u32;
+        }
+        let ids = points.map(|point| keys[&source.key(point)]).collect();
+        Self {
+            source,
+            keys: keys.into_keys().collect(),
+            ids,
+        }
+    }
+
+    pub fn feature_count(&self) -> u32 {
+        self.keys.len() as u32
+    }
+
+    /// Schema class of the property table rows
+    pub fn class(&self) -> Class {
+        let mut properties = vec![(
+            "fileName",
+            ClassProperty {
+                description: Some("Name of the input file".to_string()),
+                type_: ClassPropertyType::String,
+                ..Default::default()
+            },
+        )];
+        if self.source == FeatureIdSource::PointSourceId {
+            properties.push((
+                "pointSourceId",
+                ClassProperty {
+                    description: Some("Source (flight line) of the points".to_string()),
+                    type_: ClassPropertyType::Scalar,
+                    component_type: Some(ClassPropertyComponentType::Uint16),
+                    ..Default::default()
+                },
+            ));
+        }
+        if self.source == FeatureIdSource::Point {
+            properties.push((
+                "pointIndex",
+                ClassProperty {
+                    description: Some("Index of the point within the input file".to_string()),
+                    type_: ClassPropertyType::Scalar,
+                    component_type: Some(ClassPropertyComponentType::Uint64),
+                    ..Default::default()
+                },
+            ));
+        }
+        Class {
+            name: Some("Source".to_string()),
+            properties: properties
+                .into_iter()
+                .map(|(id, property)| (id.to_string(), property))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Binary property table with one row per feature; returns its bytes, the byte range of each
+    /// buffer view, and the `propertyTables` entry referencing the views from
+    /// `first_buffer_view` on
+    pub fn property_table(
+        &self,
+        source_files: &[String],
+        first_buffer_view: u32,
+    ) -> (Vec<u8>, Vec<Range<usize>>, Value) {
+        let mut bytes = Vec::new();
+        let mut views = Vec::new();
+        let mut push_view = |data: &[u8]| {
+            bytes.resize(bytes.len().next_multiple_of(PROPERTY_TABLE_ALIGNMENT), 0);
+            views.push(bytes.len()..bytes.len() + data.len());
+            bytes.extend_from_slice(data);
+            first_buffer_view + views.len() as u32 - 1
+        };
+
+        let mut names = Vec::new();
+        let mut name_offsets = vec![0u8; 4];
+        for (file, _, _) in &self.keys {
+            let name = file
+                .and_then(|file| source_files.get(file as usize))
+                .map_or("", String::as_str);
+            names.extend_from_slice(name.as_bytes());
+            name_offsets.extend_from_slice(&(names.len() as u32).to_le_bytes());
+        }
+        let mut properties = serde_json::Map::new();
+        properties.insert(
+            "fileName".to_string(),
+            json!({
+                "values": push_view(&names),
+                "stringOffsets": push_view(&name_offsets),
+                "stringOffsetType": "UINT32",
+            }),
+        );
+
+        if self.source == FeatureIdSource::PointSourceId {
+            let mut ids = vec![0u8; self.keys.len() * 2];
+            let values = self
+                .keys
+                .iter()
+                .map(|(_, id, _)| id.unwrap_or(0))
+                .collect::<Vec<_>>();
+            LittleEndian::write_u16_into(&values, &mut ids);
+            properties.insert(
+                "pointSourceId".to_string(),
+                json!({ "values": push_view(&ids) }),
+            );
+        }
+
+        if self.source == FeatureIdSource::Point {
+            let mut indices = vec![0u8; self.keys.len() * 8];
+            let values = self
+                .keys
+                .iter()
+                .map(|(_, _, index)| index.unwrap_or(0))
+                .collect::<Vec<_>>();
+            LittleEndian::write_u64_into(&values, &mut indices);
+            properties.insert(
+                "pointIndex".to_string(),
+                json!({ "values": push_view(&indices) }),
+            );
+        }
+
+        let table = json!({
+            "class": SOURCE_CLASS,
+            "count": self.keys.len(),
+            "properties": properties,
+        });
+        (bytes, views, table)
+    }
+
+    /// Primitive `EXT_mesh_features` object reading the IDs from [`FEATURE_ID_SEMANTIC`]
+    pub fn mesh_features(&self) -> Value {
+        json!({
+            "featureIds": [{
+                "featureCount": self.feature_count(),
+                "attribute": 0,
+                "propertyTable": 0,
+                "label": SOURCE_CLASS,
+            }],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pcd_core::pointcloud::point::{Color, PointAttributes};
+
+    fn point(source_file: u32, point_source_id: u16) -> Point {
+        Point {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+            color: Color::default(),
+            attributes: PointAttributes {
+                point_source_id: Some(point_source_id),
+                source_file: Some(source_file),
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn features_index_the_property_table_rows() {
+        let points = [point(2, 7), point(0, 7), point(2, 3), point(2, 7)];
+        let files = ["a.las", "b.las", "c.las"].map(str::to_string);
+
+        let by_file = SourceFeatures::new(FeatureIdSource::SourceFile, points.iter());
+        assert_eq!(by_file.feature_count(), 2);
+        assert_eq!(by_file.ids, [1, 0, 1, 1]);
+        let (bytes, views, table) = by_file.property_table(&files, 1);
+        assert_eq!(table["count"], 2);
+        assert_eq!(table["properties"]["fileName"]["values"], 1);
+        assert_eq!(table["properties"]["fileName"]["stringOffsets"], 2);
+        assert_eq!(&bytes[views[0].clone()], b"a.lasc.las");
+        assert_eq!(views[1].start % PROPERTY_TABLE_ALIGNMENT, 0);
+        assert_eq!(
+            LittleEndian::read_u32(&bytes[views[1].end - 4..views[1].end]),
+            10
+        );
+
+        let by_line = SourceFeatures::new(FeatureIdSource::PointSourceId, points.iter());
+        assert_eq!(by_line.ids, [2, 0, 1, 2]);
+        let (bytes, views, table) = by_line.property_table(&files, 0);
+        assert_eq!(table["properties"]["pointSourceId"]["values"], 2);
+        assert_eq!(&bytes[views[2].clone()], [7, 0, 3, 0, 7, 0]);
+        assert!(by_line.class().properties.contains_key("pointSourceId"));
+    }
+
+    #[test]
+    fn point_features_carry_the_index_within_the_file() {
+        let mut points = [point(1, 7), point(0, 7), point(1, 7)];
+        for (point, index) in points.iter_mut().zip([5_000_000_000, 42, 3]) {
+            point.attributes.point_index = Some(index);
+        }
+        let files = ["a.las", "b.las"].map(str::to_string);
+
+        let by_point = SourceFeatures::new(FeatureIdSource::Point, points.iter());
+        assert_eq!(by_point.feature_count(), 3);
+        assert_eq!(by_point.ids, [2, 0, 1]);
+        let (bytes, views, table) = by_point.property_table(&files, 0);
+        assert_eq!(table["properties"]["pointIndex"]["values"], 2);
+        assert_eq!(&bytes[views[0].clone()], b"a.lasb.lasb.las");
+        assert_eq!(views[2].start % PROPERTY_TABLE_ALIGNMENT, 0);
+        let mut indices = [0; 3];
+        LittleEndian::read_u64_into(&bytes[views[2].clone()], &mut indices);
+        assert_eq!(indices, [42, 3, 5_000_000_000]);
+        assert!(!by_point.class().properties.contains_key("pointSourceId"));
+        assert!(by_point.class().properties.contains_key("pointIndex"));
+    }
+}
@@ -1,22 +1,214 @@
-use std::{collections::HashMap, error::Error, io::Write};
+use std::{
+    collections::HashMap,
+    error::Error,
+    ffi::{c_int, c_void},
+    io::Write,
+    ops::Range,
+    str::FromStr,
+};
 
 use byteorder::{ByteOrder as _, LittleEndian};
 use cesiumtiles_gltf_json::{
     Accessor, AccessorType, Buffer, BufferExtMeshoptCompression, BufferExtensions, BufferView,
     BufferViewTarget, ComponentType, Gltf, Mesh, MeshPrimitive, Node, Scene,
-    extensions::buffer_view::{
-        BufferViewExtensions, ExtMeshoptCompression, MeshoptCompressionFilter,
-        MeshoptCompressionMode,
+    extensions::{
+        self,
+        buffer_view::{
+            BufferViewExtensions, ExtMeshoptCompression, MeshoptCompressionFilter,
+            MeshoptCompressionMode,
+        },
     },
 };
-use pcd_core::pointcloud::point::PointCloud;
+use pcd_core::pointcloud::point::{Point, PointCloud};
+use serde_json::json;
+
+use crate::{
+    attributes::{
+        ATTRIBUTE_SLOT_BYTES, AttributeEncoding, EXT_STRUCTURAL_METADATA, PointAttribute,
+        attribute_encodings, structural_metadata,
+    },
+    draco::{COLOR_ID, DracoOptions, KHR_DRACO_MESH_COMPRESSION, POSITION_ID, encode_point_cloud},
+    features::{EXT_MESH_FEATURES, FEATURE_ID_SEMANTIC, FeatureIdSource, SourceFeatures},
+};
 
 /// Options for GLB generation
 #[derive(Debug, Clone, Default)]
 pub struct GlbOptions {
-    pub quantize: bool,
-    pub meshopt: bool,
+    /// Store positions as normalized integers with `KHR_mesh_quantization`
+    pub quantize: Option<QuantizeOptions>,
+    /// Compress the vertex streams with `EXT_meshopt_compression`
+    pub meshopt: Option<MeshoptOptions>,
+    /// Storage of `COLOR_0`, which is left out when the input has no color
+    pub color: ColorOptions,
+    /// Compress positions and colors with `KHR_draco_mesh_compression`; takes precedence over
+    /// `quantize` and must not be combined with `meshopt`
+    pub draco: Option<DracoOptions>,
     pub gzip_compress: bool,
+    /// Write the `normal` attribute of the points as `NORMAL`
+    pub normals: bool,
+    /// Point attributes written as `EXT_structural_metadata` property attributes
+    pub attributes: Vec<PointAttribute>,
+    /// Source of the `EXT_mesh_features` feature IDs, if any
+    pub feature_ids: Option<FeatureIdSource>,
+    /// Names of the input files, indexed by the `source_file` attribute of the points
+    pub source_files: Vec<String>,
+}
+
+/// Transfer function of the stored colors
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorSpace {
+    /// Linear, as glTF defines `COLOR_0`
+    #[default]
+    Linear,
+    /// The sRGB values of the input as they are, for viewers that do not convert `COLOR_0`
+    Srgb,
+}
+
+impl FromStr for ColorSpace {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "linear" => Ok(Self::Linear),
+            "srgb" => Ok(Self::Srgb),
+            _ => Err(format!(
+                "unknown color space {s:?} (expected linear or srgb)"
+            )),
+        }
+    }
+}
+
+/// Precision and components of `COLOR_0`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorOptions {
+    /// Bits per component: 8 are stored in bytes, 16 in shorts, both normalized
+    pub bits: u8,
+    pub space: ColorSpace,
+    /// Write RGBA with this constant alpha (0-1) instead of RGB
+    pub alpha: Option<f32>,
+}
+
+impl Default for ColorOptions {
+    fn default() -> Self {
+        Self {
+            bits: 8,
+            space: ColorSpace::Linear,
+            alpha: None,
+        }
+    }
+}
+
+impl ColorOptions {
+    /// Bytes of each color in the vertex, padded to 4 bytes
+    fn byte_length(&self) -> usize {
+        if self.bits > 8 { 8 } else { 4 }
+    }
+
+    fn component_type(&self) -> ComponentType {
+        if self.bits > 8 {
+            ComponentType::UnsignedShort
+        } else {
+            ComponentType::UnsignedByte
+        }
+    }
+
+    fn accessor_type(&self) -> AccessorType {
+        if self.alpha.is_some() {
+            AccessorType::Vec4
+        } else {
+            AccessorType::Vec3
+        }
+    }
+
+    /// RGB of the point in the chosen color space, from 0 to 1
+    fn rgb(&self, point: &Point) -> [f64; 3] {
+        match self.space {
+            ColorSpace::Linear => point.to_linear_rgb(),
+            ColorSpace::Srgb => point.to_srgb(),
+        }
+    }
+
+    /// Writes the color of the point to the first [`Self::byte_length`] bytes; without alpha,
+    /// the fourth component is padding
+    fn write(&self, point: &Point, bytes: &mut [u8]) {
+        let [r, g, b] = self.rgb(point);
+        let alpha = self.alpha.map_or(0.0, f64::from);
+        for (i, value) in [r, g, b, alpha].into_iter().enumerate() {
+            let value = value.clamp(0.0, 1.0);
+            if self.bits > 8 {
+                let value = (value * 65535.0).round() as u16;
+                LittleEndian::write_u16(&mut bytes[i * 2..], value);
+            } else {
+                bytes[i] = (value * 255.0).round() as u8;
+            }
+        }
+    }
+}
+
+/// Precision and layout of quantized positions
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QuantizeOptions {
+    /// Bits per axis: 8 are stored in bytes, 10 and 16 in shorts
+    pub bits: u8,
+    /// Store signed normalized positions centred on the tile instead of unsigned ones from its
+    /// minimum corner
+    pub signed: bool,
+    /// Largest position error in millimetres; tiles whose quantization error exceeds it keep
+    /// float positions
+    pub tolerance_mm: Option<f64>,
+}
+
+impl Default for QuantizeOptions {
+    fn default() -> Self {
+        Self {
+            bits: 16,
+            signed: false,
+            tolerance_mm: None,
+        }
+    }
+}
+
+/// Settings of the meshopt vertex codec
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MeshoptOptions {
+    /// Apply the exponential filter to float positions and the octahedral filter to normals
+    pub filters: bool,
+}
+
+/// Vertex codec version of `EXT_meshopt_compression`, which does not allow the newer version 1
+const MESHOPT_VERTEX_VERSION: c_int = 0;
+
+/// Default compression level of meshoptimizer; version 0 streams do not depend on it
+const MESHOPT_LEVEL: c_int = 2;
+
+/// Mantissa bits of the exponential filter on float positions; the exponent is shared by each
+/// axis of a tile, so this is the precision relative to the tile extent
+const POSITION_FILTER_BITS: c_int = 18;
+
+/// Bits of the octahedral filter on normals, matching their byte storage
+const NORMAL_FILTER_BITS: c_int = 8;
+
+/// `meshopt_EncodeExpSharedComponent`
+const ENCODE_EXP_SHARED_COMPONENT: c_int = 2;
+
+// The filter encoders are compiled into the vendored meshoptimizer, but `meshopt::ffi` does not
+// declare them
+unsafe extern "C" {
+    fn meshopt_encodeFilterOct(
+        destination: *mut c_void,
+        count: usize,
+        stride: usize,
+        bits: c_int,
+        data: *const f32,
+    );
+    fn meshopt_encodeFilterExp(
+        destination: *mut c_void,
+        count: usize,
+        stride: usize,
+        bits: c_int,
+        data: *const f32,
+        mode: c_int,
+    );
 }
 
 pub fn quantize_unsigned_norm(value: f32, bits: i32) -> i32 {
@@ -28,12 +220,39 @@ pub fn quantize_unsigned_norm(value: f32, bits: i32) -> i32 {
     (clamped * scale + 0.5) as i32
 }
 
-fn rcp(value: f32) -> f32 {
-    if value != 0.0 { 1.0 / value } else { 0.0 }
+pub fn quantize_signed_norm(value: f32, bits: i32) -> i32 {
+    let max_value = (1i32 << (bits - 1)) - 1i32;
+    let scale = max_value as f32;
+
+    let clamped = value.clamp(-1.0, 1.0);
+
+    (clamped * scale).round() as i32
+}
+
+/// Snaps a unit normal to the 8-bit octahedral encoding and returns it as normalized signed
+/// bytes; directions are spread evenly, unlike rounding each component to a byte
+pub fn quantize_octahedral_normal(normal: [f32; 3]) -> [i8; 3] {
+    let [x, y, z] = normal;
+    let l1 = x.abs() + y.abs() + z.abs();
+    if l1 == 0.0 {
+        return [0, 0, 127];
+    }
+    let (mut u, mut v) = (x / l1, y / l1);
+    if z < 0.0 {
+        (u, v) = ((1.0 - v.abs()) * u.signum(), (1.0 - u.abs()) * v.signum());
+    }
+    let (u, v) = ((u * 127.0).round() / 127.0, (v * 127.0).round() / 127.0);
+
+    let z = 1.0 - u.abs() - v.abs();
+    let t = (-z).max(0.0);
+    let decoded = [u - t * u.signum(), v - t * v.signum(), z];
+    let length = decoded.iter().map(|c| c * c).sum::<f32>().sqrt();
+    decoded.map(|c| (c / length * 127.0).round() as i8)
 }
 
-/// Encode vertex buffer using meshopt with version 0 (EXT_meshopt_compression compatible).
-fn encode_vertex_buffer_v0(
+/// Encode vertex buffer using meshopt, pinned to the codec version of `EXT_meshopt_compression`
+/// whatever the default of the linked meshoptimizer.
+fn encode_vertex_buffer(
     vertices: &[u8],
     vertex_count: usize,
     vertex_size: usize,
@@ -47,11 +266,11 @@ fn encode_vertex_buffer_v0(
         ffi::meshopt_encodeVertexBufferLevel(
             encoded.as_mut_ptr(),
             encoded.len(),
-            vertices.as_ptr() as *const std::ffi::c_void,
+            vertices.as_ptr() as *const c_void,
             vertex_count,
             vertex_size,
-            0, // level: 0 = fastest
-            0, // version: 0 = EXT_meshopt_compression compatible
+            MESHOPT_LEVEL,
+            MESHOPT_VERTEX_VERSION,
         )
     };
 
@@ -63,6 +282,48 @@ fn encode_vertex_buffer_v0(
     Ok(encoded)
 }
 
+/// Replaces the vertices of a stream in place with their `filter` encoding
+fn encode_filter(
+    bytes: &mut [u8],
+    vertex_count: usize,
+    stride: usize,
+    filter: &MeshoptCompressionFilter,
+) {
+    match filter {
+        MeshoptCompressionFilter::Exponential => {
+            let mut data = vec![0f32; bytes.len() / 4];
+            LittleEndian::read_f32_into(bytes, &mut data);
+            unsafe {
+                meshopt_encodeFilterExp(
+                    bytes.as_mut_ptr() as *mut c_void,
+                    vertex_count,
+                    stride,
+                    POSITION_FILTER_BITS,
+                    data.as_ptr(),
+                    ENCODE_EXP_SHARED_COMPONENT,
+                );
+            }
+        }
+        MeshoptCompressionFilter::Octahedral => {
+            // Normalized bytes; the filter keeps the padding byte as the fourth component
+            let data = bytes
+                .iter()
+                .map(|&b| f32::from(b as i8) / 127.0)
+                .collect::<Vec<_>>();
+            unsafe {
+                meshopt_encodeFilterOct(
+                    bytes.as_mut_ptr() as *mut c_void,
+                    vertex_count,
+                    stride,
+                    NORMAL_FILTER_BITS,
+                    data.as_ptr(),
+                );
+            }
+        }
+        MeshoptCompressionFilter::None | MeshoptCompressionFilter::Quaternion => {}
+    }
+}
+
 /// Intermediate representation of built vertex buffer data.
 struct VertexBufferInfo {
     /// Raw uncompressed vertex bytes
@@ -81,22 +342,40 @@ struct VertexBufferInfo {
     position_max: Vec<f64>,
     /// Color byte offset within vertex stride
     color_byte_offset: u32,
+    /// Storage of the colors, if written
+    color: Option<ColorOptions>,
+    /// Byte offset within vertex stride and component type of the normals, if written
+    normal: Option<(u32, ComponentType)>,
     /// Node translation
     translation: [f64; 3],
     /// Node scale (only for quantized positions)
     scale: Option<[f64; 3]>,
     /// Whether KHR_mesh_quantization extension is needed
     needs_quantization_extension: bool,
+    /// Point attributes appended to each vertex, one slot each
+    attributes: Vec<PointAttribute>,
+    /// Byte offset of the first attribute slot within vertex stride
+    attributes_byte_offset: u32,
+    /// How each attribute was written
+    attribute_encodings: Vec<AttributeEncoding>,
+    /// Feature IDs stored in the last slot of each vertex
+    features: Option<SourceFeatures>,
+    /// Draco-compressed positions and colors, which are then left out of `bytes`
+    draco: Option<Vec<u8>>,
 }
 
-fn build_vertex_buffer_f32(points: &PointCloud) -> Result<VertexBufferInfo, Box<dyn Error>> {
-    const BYTE_STRIDE: usize = (4 * 3) + (3 + 1); // 16
+fn build_vertex_buffer_f32(
+    points: &PointCloud,
+    color: Option<ColorOptions>,
+) -> Result<VertexBufferInfo, Box<dyn Error>> {
+    // 16, or 20 with 16-bit colors, or 12 without colors
+    let byte_stride = (4 * 3) + color.map_or(0, |color| color.byte_length());
 
     let offset = points.metadata.offset;
     let vertex_count = points.points.len();
 
-    let mut bytes: Vec<u8> = Vec::with_capacity(vertex_count * BYTE_STRIDE);
-    let mut buffer = [0u8; BYTE_STRIDE];
+    let mut bytes: Vec<u8> = Vec::with_capacity(vertex_count * byte_stride);
+    let mut buffer = vec![0u8; byte_stride];
     let mut min = [f64::MAX; 3];
     let mut max = [f64::MIN; 3];
 
@@ -117,113 +396,482 @@ fn build_vertex_buffer_f32(points: &PointCloud) -> Result<VertexBufferInfo, Box<
         max[1] = max[1].max(y - offset[1]);
         max[2] = max[2].max(z - offset[2]);
 
-        let rgb8 = point.to_rgb8_normalized();
-        buffer[12..15].copy_from_slice(&[rgb8[0], rgb8[1], rgb8[2]]);
-        buffer[15] = 0;
+        if let Some(color) = color {
+            color.write(point, &mut buffer[12..]);
+        }
 
         bytes.write_all(&buffer)?;
     }
 
     Ok(VertexBufferInfo {
         bytes,
-        byte_stride: BYTE_STRIDE,
+        byte_stride,
         vertex_count,
         position_component_type: ComponentType::Float,
         position_normalized: false,
         position_min: min.to_vec(),
         position_max: max.to_vec(),
         color_byte_offset: 4 * 3,
+        color,
+        normal: None,
         translation: offset,
         scale: None,
         needs_quantization_extension: false,
+        attributes: Vec::new(),
+        attributes_byte_offset: byte_stride as u32,
+        attribute_encodings: Vec::new(),
+        features: None,
+        draco: None,
     })
 }
 
-fn build_vertex_buffer_quantized(points: &PointCloud) -> Result<VertexBufferInfo, Box<dyn Error>> {
-    const BYTE_STRIDE: usize = (2 * 3 + 2) + (3 + 1); // 12
+/// Per-axis mapping of the positions of a tile onto quantized integers
+struct PositionQuantization {
+    options: QuantizeOptions,
+    /// Position, relative to the point cloud offset, that quantizes to zero
+    origin: [f64; 3],
+    /// Distance from `origin` that quantizes to the largest value, per axis
+    range: [f64; 3],
+}
+
+impl PositionQuantization {
+    fn new(points: &PointCloud, options: QuantizeOptions) -> Self {
+        let offset = points.metadata.offset;
+        let mut min = [f64::MAX; 3];
+        let mut max = [f64::MIN; 3];
+        for (x, y, z, _) in points.iter() {
+            for (axis, value) in [x, y, z].into_iter().enumerate() {
+                min[axis] = min[axis].min(value - offset[axis]);
+                max[axis] = max[axis].max(value - offset[axis]);
+            }
+        }
+        if points.points.is_empty() {
+            (min, max) = ([0.0; 3], [0.0; 3]);
+        }
+
+        // Axes without extent still need a non-zero node scale
+        let extent = std::array::from_fn::<f64, 3, _>(|axis| match max[axis] - min[axis] {
+            extent if extent > 0.0 => extent,
+            _ => 1.0,
+        });
+        if options.signed {
+            Self {
+                options,
+                origin: std::array::from_fn(|axis| (min[axis] + max[axis]) / 2.0),
+                range: extent.map(|extent| extent / 2.0),
+            }
+        } else {
+            Self {
+                options,
+                origin: min,
+                range: extent,
+            }
+        }
+    }
+
+    /// Largest integer of the quantized values
+    fn max_value(&self) -> i32 {
+        let bits = i32::from(self.options.bits);
+        if self.options.signed {
+            (1 << (bits - 1)) - 1
+        } else {
+            (1 << bits) - 1
+        }
+    }
+
+    /// Component type storing the quantized values, and its largest value
+    fn component_type(&self) -> (ComponentType, i32) {
+        match (self.options.bits <= 8, self.options.signed) {
+            (true, false) => (ComponentType::UnsignedByte, u8::MAX.into()),
+            (true, true) => (ComponentType::Byte, i8::MAX.into()),
+            (false, false) => (ComponentType::UnsignedShort, u16::MAX.into()),
+            (false, true) => (ComponentType::Short, i16::MAX.into()),
+        }
+    }
+
+    fn quantize(&self, position: [f64; 3]) -> [i32; 3] {
+        let bits = i32::from(self.options.bits);
+        std::array::from_fn(|axis| {
+            let value = ((position[axis] - self.origin[axis]) / self.range[axis]) as f32;
+            if self.options.signed {
+                quantize_signed_norm(value, bits)
+            } else {
+                quantize_unsigned_norm(value, bits)
+            }
+        })
+    }
+
+    fn dequantize(&self, quantized: [i32; 3]) -> [f64; 3] {
+        let max_value = f64::from(self.max_value());
+        std::array::from_fn(|axis| {
+            self.origin[axis] + f64::from(quantized[axis]) / max_value * self.range[axis]
+        })
+    }
+
+    /// Largest distance in metres between a point and its quantized position
+    fn max_error(&self, points: &PointCloud) -> f64 {
+        let offset = points.metadata.offset;
+        points
+            .iter()
+            .map(|(x, y, z, _)| {
+                let position = [x - offset[0], y - offset[1], z - offset[2]];
+                let decoded = self.dequantize(self.quantize(position));
+                (0..3)
+                    .map(|axis| (decoded[axis] - position[axis]).powi(2))
+                    .sum::<f64>()
+                    .sqrt()
+            })
+            .fold(0.0, f64::max)
+    }
+
+    /// Node scale that maps the stored integers back onto `range`
+    fn scale(&self) -> [f64; 3] {
+        let (_, storage_max) = self.component_type();
+        let ratio = f64::from(storage_max) / f64::from(self.max_value());
+        self.range.map(|range| range * ratio)
+    }
+}
+
+fn build_vertex_buffer_quantized(
+    points: &PointCloud,
+    quantization: &PositionQuantization,
+    color: Option<ColorOptions>,
+) -> Result<VertexBufferInfo, Box<dyn Error>> {
+    let (component_type, _) = quantization.component_type();
+    // Positions are padded to 4 bytes, followed by the color, if any
+    let position_bytes = if quantization.options.bits <= 8 {
+        4
+    } else {
+        2 * 3 + 2
+    };
+    let byte_stride = position_bytes + color.map_or(0, |color| color.byte_length());
 
     let offset = points.metadata.offset;
     let vertex_count = points.points.len();
 
-    let mut quantized_position_max = [u16::MIN; 3];
-    let mut quantized_position_min = [u16::MAX; 3];
-
-    let bits = 16;
-    let common_scale = points.iter().fold(0f32, |result, (x, y, z, _)| {
-        result
-            .max(x as f32 - offset[0] as f32)
-            .max(y as f32 - offset[1] as f32)
-            .max(z as f32 - offset[2] as f32)
-    });
-    let point_scale_inv = rcp(common_scale);
+    let mut quantized_position_max = [i32::MIN; 3];
+    let mut quantized_position_min = [i32::MAX; 3];
 
-    let mut bytes: Vec<u8> = Vec::with_capacity(vertex_count * BYTE_STRIDE);
-    let mut vertex_buf = [0u8; BYTE_STRIDE];
+    let mut bytes: Vec<u8> = Vec::with_capacity(vertex_count * byte_stride);
+    let mut vertex_buf = vec![0u8; byte_stride];
 
     for (raw_x, raw_y, raw_z, point) in points.iter() {
-        let quantized_x =
-            quantize_unsigned_norm((raw_x as f32 - offset[0] as f32) * point_scale_inv, bits)
-                as u16;
-        let quantized_y =
-            quantize_unsigned_norm((raw_y as f32 - offset[1] as f32) * point_scale_inv, bits)
-                as u16;
-        let quantized_z =
-            quantize_unsigned_norm((raw_z as f32 - offset[2] as f32) * point_scale_inv, bits)
-                as u16;
-
-        let rgb8 = point.to_rgb8_normalized();
-
-        quantized_position_max[0] = quantized_position_max[0].max(quantized_x);
-        quantized_position_max[1] = quantized_position_max[1].max(quantized_y);
-        quantized_position_max[2] = quantized_position_max[2].max(quantized_z);
-        quantized_position_min[0] = quantized_position_min[0].min(quantized_x);
-        quantized_position_min[1] = quantized_position_min[1].min(quantized_y);
-        quantized_position_min[2] = quantized_position_min[2].min(quantized_z);
-
-        LittleEndian::write_u16_into(
-            &[quantized_x, quantized_y, quantized_z],
-            &mut vertex_buf[0..6],
-        );
-        vertex_buf[6..8].copy_from_slice(&[0, 0]);
-        vertex_buf[8..11].copy_from_slice(&[rgb8[0], rgb8[1], rgb8[2]]);
-        vertex_buf[11] = 0;
+        let quantized =
+            quantization.quantize([raw_x - offset[0], raw_y - offset[1], raw_z - offset[2]]);
+
+        for axis in 0..3 {
+            quantized_position_max[axis] = quantized_position_max[axis].max(quantized[axis]);
+            quantized_position_min[axis] = quantized_position_min[axis].min(quantized[axis]);
+        }
+
+        if position_bytes == 4 {
+            // Two's complement bytes for signed values
+            for (byte, value) in vertex_buf.iter_mut().zip(quantized) {
+                *byte = value as u8;
+            }
+        } else {
+            for (bytes, value) in vertex_buf.chunks_exact_mut(2).zip(quantized) {
+                LittleEndian::write_u16(bytes, value as u16);
+            }
+        }
+
+        if let Some(color) = color {
+            color.write(point, &mut vertex_buf[position_bytes..]);
+        }
 
         bytes.write_all(&vertex_buf)?;
     }
 
     Ok(VertexBufferInfo {
         bytes,
-        byte_stride: BYTE_STRIDE,
+        byte_stride,
         vertex_count,
-        position_component_type: ComponentType::UnsignedShort,
+        position_component_type: component_type,
         position_normalized: true,
         position_min: quantized_position_min.iter().map(|&x| x as f64).collect(),
         position_max: quantized_position_max.iter().map(|&x| x as f64).collect(),
-        color_byte_offset: 2 * 3 + 2,
-        translation: offset,
-        scale: Some([
-            common_scale as f64,
-            common_scale as f64,
-            common_scale as f64,
-        ]),
+        color_byte_offset: position_bytes as u32,
+        color,
+        normal: None,
+        translation: std::array::from_fn(|axis| offset[axis] + quantization.origin[axis]),
+        scale: Some(quantization.scale()),
         needs_quantization_extension: true,
+        attributes: Vec::new(),
+        attributes_byte_offset: byte_stride as u32,
+        attribute_encodings: Vec::new(),
+        features: None,
+        draco: None,
     })
 }
 
+/// Widens each vertex with the normal, as floats or as octahedral-quantized normalized bytes
+/// padded to 4 bytes; points without a normal face +Z
+fn append_normals(
+    mut info: VertexBufferInfo,
+    points: &PointCloud,
+    quantize: bool,
+) -> VertexBufferInfo {
+    let (normal_bytes, component_type) = if quantize {
+        (4, ComponentType::Byte)
+    } else {
+        (4 * 3, ComponentType::Float)
+    };
+    let byte_stride = info.byte_stride + normal_bytes;
+
+    let mut bytes = Vec::with_capacity(info.vertex_count * byte_stride);
+    let mut buffer = [0u8; 4 * 3];
+    for (vertex, point) in info
+        .bytes
+        .chunks_exact(info.byte_stride)
+        .zip(&points.points)
+    {
+        bytes.extend_from_slice(vertex);
+        let normal = point.attributes.normal.unwrap_or([0.0, 0.0, 1.0]);
+        if quantize {
+            let [x, y, z] = quantize_octahedral_normal(normal);
+            bytes.extend_from_slice(&[x as u8, y as u8, z as u8, 0]);
+        } else {
+            LittleEndian::write_f32_into(&normal, &mut buffer);
+            bytes.extend_from_slice(&buffer);
+        }
+    }
+
+    info.normal = Some((info.byte_stride as u32, component_type));
+    // Normalized byte normals are only valid with KHR_mesh_quantization
+    info.needs_quantization_extension |= quantize;
+    info.bytes = bytes;
+    info.byte_stride = byte_stride;
+    info
+}
+
+/// Widens each vertex with one slot per attribute, after the position, color and normal
+fn append_attributes(
+    mut info: VertexBufferInfo,
+    points: &PointCloud,
+    attributes: &[PointAttribute],
+) -> VertexBufferInfo {
+    if attributes.is_empty() {
+        return info;
+    }
+    let encodings = attribute_encodings(attributes, points.points.iter());
+    info.attributes_byte_offset = info.byte_stride as u32;
+    let byte_stride = info.byte_stride + attributes.len() * ATTRIBUTE_SLOT_BYTES;
+
+    let mut bytes = Vec::with_capacity(info.vertex_count * byte_stride);
+    let mut slot = [0u8; ATTRIBUTE_SLOT_BYTES];
+    for (vertex, point) in info
+        .bytes
+        .chunks_exact(info.byte_stride)
+        .zip(&points.points)
+    {
+        bytes.extend_from_slice(vertex);
+        for (attribute, &encoding) in attributes.iter().zip(&encodings) {
+            attribute.write(point, encoding, &mut slot);
+            bytes.extend_from_slice(&slot);
+        }
+    }
+
+    info.bytes = bytes;
+    info.byte_stride = byte_stride;
+    info.attributes = attributes.to_vec();
+    info.attribute_encodings = encodings;
+    info
+}
+
+/// Widens each vertex with a slot holding its feature ID as a float
+fn append_feature_ids(
+    mut info: VertexBufferInfo,
+    points: &PointCloud,
+    source: Option<FeatureIdSource>,
+) -> VertexBufferInfo {
+    let Some(source) = source.filter(|_| info.vertex_count > 0) else {
+        return info;
+    };
+    let features = SourceFeatures::new(source, points.points.iter());
+    let byte_stride = info.byte_stride + ATTRIBUTE_SLOT_BYTES;
+
+    let mut bytes = Vec::with_capacity(info.vertex_count * byte_stride);
+    for (vertex, &id) in info.bytes.chunks_exact(info.byte_stride).zip(&features.ids) {
+        bytes.extend_from_slice(vertex);
+        bytes.extend_from_slice(&(id as f32).to_le_bytes());
+    }
+
+    info.bytes = bytes;
+    info.byte_stride = byte_stride;
+    info.features = Some(features);
+    info
+}
+
+/// Moves the positions and colors into a Draco point cloud, keeping the other attributes
+/// interleaved in `bytes`
+fn compress_draco(
+    mut info: VertexBufferInfo,
+    points: &PointCloud,
+    options: Option<DracoOptions>,
+) -> VertexBufferInfo {
+    let Some(options) = options.filter(|_| info.vertex_count > 0) else {
+        return info;
+    };
+    let offset = points.metadata.offset;
+    let positions = points
+        .iter()
+        .map(|(x, y, z, _)| {
+            [
+                (x - offset[0]) as f32,
+                (y - offset[1]) as f32,
+                (z - offset[2]) as f32,
+            ]
+        })
+        .collect::<Vec<_>>();
+    let colors = info.color.map(|color| {
+        points
+            .points
+            .iter()
+            .map(|point| color.rgb(point).map(|c| c as f32))
+            .collect::<Vec<_>>()
+    });
+    info.draco = Some(encode_point_cloud(&positions, colors.as_deref(), options));
+
+    // Position and color are the first two members of every vertex layout
+    let removed =
+        info.color_byte_offset as usize + info.color.map_or(0, |color| color.byte_length());
+    let byte_stride = info.byte_stride - removed;
+    info.bytes = info
+        .bytes
+        .chunks_exact(info.byte_stride)
+        .flat_map(|vertex| &vertex[removed..])
+        .copied()
+        .collect();
+    info.byte_stride = byte_stride;
+    info.normal = info
+        .normal
+        .map(|(byte_offset, component_type)| (byte_offset - removed as u32, component_type));
+    info.attributes_byte_offset -= removed as u32;
+    info
+}
+
+/// Vertex members stored in one buffer view
+struct VertexStream {
+    name: &'static str,
+    /// Bytes of each interleaved vertex that belong to the stream
+    range: Range<usize>,
+    filter: MeshoptCompressionFilter,
+}
+
+/// Splits the vertex layout into position, color, normal and attribute streams, so that each
+/// can use its own meshopt filter
+fn vertex_streams(info: &VertexBufferInfo, filters: bool) -> Vec<VertexStream> {
+    let color_byte_offset = info.color_byte_offset as usize;
+    let position_filter = if filters && info.position_component_type == ComponentType::Float {
+        MeshoptCompressionFilter::Exponential
+    } else {
+        MeshoptCompressionFilter::None
+    };
+    let mut streams = vec![VertexStream {
+        name: "positions",
+        range: 0..color_byte_offset,
+        filter: position_filter,
+    }];
+    if let Some(color) = info.color {
+        streams.push(VertexStream {
+            name: "colors",
+            range: color_byte_offset..color_byte_offset + color.byte_length(),
+            filter: MeshoptCompressionFilter::None,
+        });
+    }
+    if let Some((byte_offset, component_type)) = info.normal {
+        let (normal_bytes, filter) = if component_type == ComponentType::Byte {
+            let filter = if filters {
+                MeshoptCompressionFilter::Octahedral
+            } else {
+                MeshoptCompressionFilter::None
+            };
+            (4, filter)
+        } else {
+            (4 * 3, MeshoptCompressionFilter::None)
+        };
+        let byte_offset = byte_offset as usize;
+        streams.push(VertexStream {
+            name: "normals",
+            range: byte_offset..byte_offset + normal_bytes,
+            filter,
+        });
+    }
+    // Attribute slots and feature IDs come last
+    let rest = streams.last().unwrap().range.end;
+    if rest < info.byte_stride {
+        streams.push(VertexStream {
+            name: "attributes",
+            range: rest..info.byte_stride,
+            filter: MeshoptCompressionFilter::None,
+        });
+    }
+    streams
+}
+
 /// Assemble a GLB from vertex buffer info, optionally applying meshopt compression.
 ///
-/// When `meshopt` is true, uses a 2-buffer layout:
-///   Buffer 0: compressed vertex data (in BIN chunk)
+/// With `meshopt`, uses a 2-buffer layout:
+///   Buffer 0: compressed vertex streams (in BIN chunk)
 ///   Buffer 1: fallback buffer (byteLength = uncompressed size, no data)
-///   BufferView: points to Buffer 1, extension references Buffer 0
+///   BufferViews: one per vertex stream, point to Buffer 1, extension references Buffer 0
+///
+/// With Draco, buffer 0 holds the remaining interleaved attributes, if any, then the Draco data.
+/// The property table of the feature IDs follows the vertex data in buffer 0.
 fn assemble_glb<'a>(
     info: VertexBufferInfo,
-    meshopt: bool,
+    meshopt: Option<MeshoptOptions>,
+    source_files: &[String],
 ) -> Result<cesiumtiles_gltf::glb::Glb<'a>, Box<dyn Error>> {
     let uncompressed_len = info.bytes.len() as u32;
+    let streams = match meshopt {
+        Some(options) => vertex_streams(&info, options.filters),
+        None => vec![VertexStream {
+            name: "vertices",
+            range: 0..info.byte_stride,
+            filter: MeshoptCompressionFilter::None,
+        }],
+    };
 
-    let (gltf_buffers, gltf_buffer_views, bin_content) = if meshopt {
-        let compressed = encode_vertex_buffer_v0(&info.bytes, info.vertex_count, info.byte_stride)?;
+    let (mut gltf_buffers, mut gltf_buffer_views, mut bin_content) = if meshopt.is_some() {
+        let mut compressed = Vec::new();
+        let mut buffer_views = Vec::new();
+        let mut fallback_offset = 0;
+        for stream in &streams {
+            let stride = stream.range.len();
+            let mut bytes = info
+                .bytes
+                .chunks_exact(info.byte_stride)
+                .flat_map(|vertex| &vertex[stream.range.clone()])
+                .copied()
+                .collect::<Vec<_>>();
+            encode_filter(&mut bytes, info.vertex_count, stride, &stream.filter);
+            let encoded = encode_vertex_buffer(&bytes, info.vertex_count, stride)?;
+
+            buffer_views.push(BufferView {
+                name: Some(stream.name.to_string()),
+                buffer: 1, // fallback buffer
+                byte_offset: fallback_offset,
+                byte_length: bytes.len() as u32,
+                byte_stride: Some(stride as u8),
+                target: Some(BufferViewTarget::ArrayBuffer),
+                extensions: Some(BufferViewExtensions {
+                    ext_meshopt_compression: Some(ExtMeshoptCompression {
+                        buffer: 0,
+                        byte_offset: compressed.len() as u32,
+                        byte_length: encoded.len() as u32,
+                        byte_stride: stride as u32,
+                        count: info.vertex_count as u32,
+                        mode: MeshoptCompressionMode::Attributes,
+                        filter: stream.filter.clone(),
+                    }),
+                    ..Default::default()
+                }),
+            });
+            fallback_offset += bytes.len() as u32;
+            compressed.extend_from_slice(&encoded);
+            compressed.resize(compressed.len().next_multiple_of(4), 0);
+        }
         let compressed_len = compressed.len() as u32;
 
         let buffers = vec![
@@ -243,28 +891,35 @@ fn assemble_glb<'a>(
             },
         ];
 
-        let buffer_views = vec![BufferView {
-            name: Some("vertices".to_string()),
-            buffer: 1, // fallback buffer
-            byte_offset: 0,
-            byte_length: uncompressed_len,
-            byte_stride: Some(info.byte_stride as u8),
-            target: Some(BufferViewTarget::ArrayBuffer),
-            extensions: Some(BufferViewExtensions {
-                ext_meshopt_compression: Some(ExtMeshoptCompression {
-                    buffer: 0,
-                    byte_offset: 0,
-                    byte_length: compressed_len,
-                    byte_stride: info.byte_stride as u32,
-                    count: info.vertex_count as u32,
-                    mode: MeshoptCompressionMode::Attributes,
-                    filter: MeshoptCompressionFilter::None,
-                }),
+        (buffers, buffer_views, compressed)
+    } else if let Some(draco) = &info.draco {
+        let mut bin_content = info.bytes;
+        let mut buffer_views = Vec::new();
+        if uncompressed_len > 0 {
+            buffer_views.push(BufferView {
+                name: Some("vertices".to_string()),
+                byte_offset: 0,
+                byte_length: uncompressed_len,
+                byte_stride: Some(info.byte_stride as u8),
+                target: Some(BufferViewTarget::ArrayBuffer),
                 ..Default::default()
-            }),
-        }];
+            });
+        }
+        let draco_offset = bin_content.len().next_multiple_of(4);
+        bin_content.resize(draco_offset, 0);
+        bin_content.extend_from_slice(draco);
+        buffer_views.push(BufferView {
+            name: Some("draco".to_string()),
+            byte_offset: draco_offset as u32,
+            byte_length: draco.len() as u32,
+            ..Default::default()
+        });
 
-        (buffers, buffer_views, compressed)
+        let buffers = vec![Buffer {
+            byte_length: bin_content.len() as u32,
+            ..Default::default()
+        }];
+        (buffers, buffer_views, bin_content)
     } else {
         let buffers = if uncompressed_len > 0 {
             vec![Buffer {
@@ -286,38 +941,179 @@ fn assemble_glb<'a>(
 
         (buffers, buffer_views, info.bytes)
     };
+    let draco_buffer_view = info
+        .draco
+        .as_ref()
+        .map(|_| gltf_buffer_views.len() as u32 - 1);
+
+    let property_table = info.features.as_ref().map(|features| {
+        let (bytes, views, table) =
+            features.property_table(source_files, gltf_buffer_views.len() as u32);
+        let table_offset = bin_content.len().next_multiple_of(8);
+        bin_content.resize(table_offset, 0);
+        bin_content.extend_from_slice(&bytes);
+        gltf_buffers[0].byte_length = bin_content.len() as u32;
+        gltf_buffer_views.extend(views.into_iter().map(|view| BufferView {
+            name: Some("properties".to_string()),
+            byte_offset: (table_offset + view.start) as u32,
+            byte_length: view.len() as u32,
+            ..Default::default()
+        }));
+        (features.class(), table)
+    });
+
+    // Buffer view of the stream holding the vertex member at `byte_offset`, and its byte offset
+    // within the stream
+    let locate = |byte_offset: u32| {
+        let (view, stream) = streams
+            .iter()
+            .enumerate()
+            .find(|(_, stream)| stream.range.contains(&(byte_offset as usize)))
+            .expect("vertex members lie within the streams");
+        (Some(view as u32), byte_offset - stream.range.start as u32)
+    };
 
-    let gltf_accessors = vec![
-        Accessor {
-            name: Some("positions".to_string()),
-            buffer_view: Some(0),
-            component_type: info.position_component_type,
+    // Draco-compressed accessors have no buffer view; decoders fill them from the Draco data
+    // Draco colors are always RGB
+    let (positions, colors) = if info.draco.is_some() {
+        (
+            (None, 0),
+            info.color
+                .map(|_| (None, ComponentType::Float, AccessorType::Vec3, 0, false)),
+        )
+    } else {
+        (
+            locate(0),
+            info.color.map(|color| {
+                let (view, byte_offset) = locate(info.color_byte_offset);
+                (
+                    view,
+                    color.component_type(),
+                    color.accessor_type(),
+                    byte_offset,
+                    true,
+                )
+            }),
+        )
+    };
+    let mut gltf_accessors = vec![Accessor {
+        name: Some("positions".to_string()),
+        buffer_view: positions.0,
+        byte_offset: positions.1,
+        component_type: info.position_component_type,
+        count: info.vertex_count as u32,
+        min: Some(info.position_min),
+        max: Some(info.position_max),
+        type_: AccessorType::Vec3,
+        normalized: info.position_normalized,
+        ..Default::default()
+    }];
+    let mut attributes = HashMap::from([("POSITION".to_string(), 0)]);
+    if let Some(colors) = colors {
+        attributes.insert("COLOR_0".to_string(), gltf_accessors.len() as u32);
+        gltf_accessors.push(Accessor {
+            name: Some("colors".to_string()),
+            buffer_view: colors.0,
+            component_type: colors.1,
+            byte_offset: colors.3,
             count: info.vertex_count as u32,
-            min: Some(info.position_min),
-            max: Some(info.position_max),
-            type_: AccessorType::Vec3,
-            normalized: info.position_normalized,
+            type_: colors.2,
+            normalized: colors.4,
             ..Default::default()
-        },
-        Accessor {
-            name: Some("colors".to_string()),
-            buffer_view: Some(0),
-            component_type: ComponentType::UnsignedByte,
-            byte_offset: info.color_byte_offset,
+        });
+    }
+    if let Some((byte_offset, component_type)) = info.normal {
+        attributes.insert("NORMAL".to_string(), gltf_accessors.len() as u32);
+        let (buffer_view, byte_offset) = locate(byte_offset);
+        gltf_accessors.push(Accessor {
+            name: Some("normals".to_string()),
+            buffer_view,
+            component_type,
+            byte_offset,
             count: info.vertex_count as u32,
             type_: AccessorType::Vec3,
-            normalized: true,
+            normalized: component_type == ComponentType::Byte,
             ..Default::default()
-        },
-    ];
+        });
+    }
+    for (i, attribute) in info.attributes.iter().enumerate() {
+        attributes.insert(
+            attribute.semantic().to_string(),
+            gltf_accessors.len() as u32,
+        );
+        let (buffer_view, byte_offset) =
+            locate(info.attributes_byte_offset + (i * ATTRIBUTE_SLOT_BYTES) as u32);
+        gltf_accessors.push(Accessor {
+            name: Some(attribute.property_id().to_string()),
+            buffer_view,
+            component_type: attribute.component_type(),
+            byte_offset,
+            count: info.vertex_count as u32,
+            type_: AccessorType::Scalar,
+            ..Default::default()
+        });
+    }
+
+    if let Some(features) = &info.features {
+        attributes.insert(FEATURE_ID_SEMANTIC.to_string(), gltf_accessors.len() as u32);
+        let (buffer_view, byte_offset) = locate((info.byte_stride - ATTRIBUTE_SLOT_BYTES) as u32);
+        gltf_accessors.push(Accessor {
+            name: Some("featureIds".to_string()),
+            buffer_view,
+            component_type: ComponentType::Float,
+            byte_offset,
+            count: features.ids.len() as u32,
+            type_: AccessorType::Scalar,
+            ..Default::default()
+        });
+    }
+
+    // The mesh-level extension types of cesiumtiles do not serialize in camelCase, so the
+    // `EXT_structural_metadata`, `EXT_mesh_features` and `KHR_draco_mesh_compression` objects
+    // are written as plain JSON
+    let mut primitive_extensions = HashMap::new();
+    if !info.attributes.is_empty() {
+        primitive_extensions.insert(
+            EXT_STRUCTURAL_METADATA.to_string(),
+            json!({ "propertyAttributes": [0] }),
+        );
+    }
+    if let Some(features) = &info.features {
+        primitive_extensions.insert(EXT_MESH_FEATURES.to_string(), features.mesh_features());
+    }
+    if let Some(buffer_view) = draco_buffer_view {
+        let mut draco_attributes = json!({ "POSITION": POSITION_ID });
+        if info.color.is_some() {
+            draco_attributes["COLOR_0"] = json!(COLOR_ID);
+        }
+        primitive_extensions.insert(
+            KHR_DRACO_MESH_COMPRESSION.to_string(),
+            json!({
+                "bufferView": buffer_view,
+                "attributes": draco_attributes,
+            }),
+        );
+    }
+    let has_features = info.features.is_some();
+    let gltf_extensions =
+        (!info.attributes.is_empty() || has_features).then(|| extensions::gltf::Gltf {
+            others: HashMap::from([(
+                EXT_STRUCTURAL_METADATA.to_string(),
+                structural_metadata(&info.attributes, &info.attribute_encodings, property_table),
+            )]),
+            ..Default::default()
+        });
+    let primitive_extensions =
+        (!primitive_extensions.is_empty()).then(|| extensions::mesh::MeshPrimitive {
+            others: primitive_extensions,
+            ..Default::default()
+        });
 
     let gltf_meshes = vec![Mesh {
         primitives: vec![MeshPrimitive {
-            attributes: HashMap::from_iter(vec![
-                ("POSITION".to_string(), 0),
-                ("COLOR_0".to_string(), 1),
-            ]),
+            attributes,
             mode: cesiumtiles_gltf_json::PrimitiveMode::Points,
+            extensions: primitive_extensions,
             ..Default::default()
         }],
         ..Default::default()
@@ -329,10 +1125,21 @@ fn assemble_glb<'a>(
         extensions_used.push("KHR_mesh_quantization".to_string());
         extensions_required.push("KHR_mesh_quantization".to_string());
     }
-    if meshopt {
+    if meshopt.is_some() {
         extensions_used.push("EXT_meshopt_compression".to_string());
         extensions_required.push("EXT_meshopt_compression".to_string());
     }
+    if gltf_extensions.is_some() {
+        extensions_used.push(EXT_STRUCTURAL_METADATA.to_string());
+    }
+    if has_features {
+        extensions_used.push(EXT_MESH_FEATURES.to_string());
+    }
+    if draco_buffer_view.is_some() {
+        // The positions and colors exist only in the Draco data
+        extensions_used.push(KHR_DRACO_MESH_COMPRESSION.to_string());
+        extensions_required.push(KHR_DRACO_MESH_COMPRESSION.to_string());
+    }
 
     let mut node = Node {
         mesh: Some(0),
@@ -355,6 +1162,7 @@ fn assemble_glb<'a>(
         buffers: gltf_buffers,
         extensions_used,
         extensions_required,
+        extensions: gltf_extensions,
         ..Default::default()
     };
 
@@ -368,12 +1176,38 @@ pub fn generate_glb_with_options<'a>(
     points: PointCloud,
     options: &GlbOptions,
 ) -> Result<cesiumtiles_gltf::glb::Glb<'a>, Box<dyn Error>> {
-    let info = if options.quantize {
-        build_vertex_buffer_quantized(&points)?
+    // Tiles whose quantization error exceeds the tolerance keep float positions
+    let quantization = options
+        .quantize
+        .filter(|_| options.draco.is_none())
+        .map(|quantize| PositionQuantization::new(&points, quantize))
+        .filter(|quantization| match quantization.options.tolerance_mm {
+            Some(tolerance_mm) => quantization.max_error(&points) * 1000.0 <= tolerance_mm,
+            None => true,
+        });
+    let color = points.metadata.attributes.color.then_some(options.color);
+    let info = match &quantization {
+        Some(quantization) => build_vertex_buffer_quantized(&points, quantization, color)?,
+        None => build_vertex_buffer_f32(&points, color)?,
+    };
+    // The octahedral filter works on normals stored as bytes
+    let filters = options.meshopt.is_some_and(|meshopt| meshopt.filters);
+    let info = if options.normals {
+        append_normals(info, &points, options.quantize.is_some() || filters)
     } else {
-        build_vertex_buffer_f32(&points)?
+        info
     };
-    assemble_glb(info, options.meshopt)
+    // Attributes the inputs do not store would only hold the readers' defaults
+    let attributes = options
+        .attributes
+        .iter()
+        .copied()
+        .filter(|attribute| attribute.is_available(&points.metadata.attributes))
+        .collect::<Vec<_>>();
+    let info = append_attributes(info, &points, &attributes);
+    let info = append_feature_ids(info, &points, options.feature_ids);
+    let info = compress_draco(info, &points, options.draco);
+    assemble_glb(info, options.meshopt, &options.source_files)
 }
 
 #[cfg(test)]
@@ -402,6 +1236,9 @@ mod tests {
                         user_data: None,
                         point_source_id: None,
                         gps_time: None,
+                        source_file: None,
+                        point_index: None,
+                        normal: None,
                     },
                 },
                 Point {
@@ -422,6 +1259,9 @@ mod tests {
                         user_data: None,
                         point_source_id: None,
                         gps_time: None,
+                        source_file: None,
+                        point_index: None,
+                        normal: None,
                     },
                 },
                 Point {
@@ -442,6 +1282,9 @@ mod tests {
                         user_data: None,
                         point_source_id: None,
                         gps_time: None,
+                        source_file: None,
+                        point_index: None,
+                        normal: None,
                     },
                 },
             ],
@@ -502,7 +1345,7 @@ mod tests {
         let glb = generate_glb_with_options(
             points,
             &GlbOptions {
-                quantize: true,
+                quantize: Some(QuantizeOptions::default()),
                 ..Default::default()
             },
         )
@@ -542,13 +1385,221 @@ mod tests {
         assert_eq!(glb.bin.as_ref().unwrap().len(), 3 * 12);
     }
 
+    /// A flat tile, 300 m wide and 3 m tall
+    fn make_flat_points() -> PointCloud {
+        let mut points = make_test_points();
+        for (point, [x, y, z]) in points.points.iter_mut().zip([
+            [0.0, 0.0, 0.0],
+            [300.0, 150.0, 3.0],
+            [123.456, 300.0, 1.234],
+        ]) {
+            (point.x, point.y, point.z) = (10.0 + x, 20.0 + y, 30.0 + z);
+        }
+        points
+    }
+
+    #[test]
+    fn quantization_scales_each_axis() {
+        let points = make_flat_points();
+        let quantization = PositionQuantization::new(&points, QuantizeOptions::default());
+        assert_eq!(quantization.scale(), [300.0, 300.0, 3.0]);
+        // Half a step of the widest axis
+        let error = quantization.max_error(&points);
+        assert!(error < 300.0 / 65535.0, "{error}");
+
+        let quantization = PositionQuantization::new(
+            &points,
+            QuantizeOptions {
+                bits: 10,
+                signed: true,
+                tolerance_mm: None,
+            },
+        );
+        assert_eq!(quantization.origin, [150.0, 150.0, 1.5]);
+        assert_eq!(quantization.max_value(), 511);
+        // 10 bits in normalized shorts
+        assert_eq!(quantization.scale()[2], 1.5 * 32767.0 / 511.0);
+        assert_eq!(quantization.quantize([0.0, 300.0, 1.5]), [-511, 511, 0]);
+        let error = quantization.max_error(&points);
+        assert!(error > 0.01 && error < 150.0 / 511.0, "{error}");
+    }
+
+    #[test]
+    fn test_generate_glb_with_signed_byte_positions() {
+        let glb = generate_glb_with_options(
+            make_flat_points(),
+            &GlbOptions {
+                quantize: Some(QuantizeOptions {
+                    bits: 8,
+                    signed: true,
+                    tolerance_mm: None,
+                }),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let json = parse_glb_json(&glb);
+
+        assert_eq!(json["bufferViews"][0]["byteStride"], 8);
+        let accessors = json["accessors"].as_array().unwrap();
+        assert_eq!(accessors[0]["componentType"], 5120);
+        assert!(accessors[0]["normalized"].as_bool().unwrap());
+        assert_eq!(
+            accessors[0]["min"],
+            serde_json::json!([-127.0, -127.0, -127.0])
+        );
+        assert_eq!(accessors[1]["byteOffset"], 4);
+
+        // Centred on the tile
+        let node = &json["nodes"][0];
+        assert_eq!(node["translation"], serde_json::json!([160.0, 170.0, 31.5]));
+        assert_eq!(node["scale"], serde_json::json!([150.0, 150.0, 1.5]));
+        let bin = glb.bin.as_ref().unwrap();
+        assert_eq!(&bin[..3], [0x81, 0x81, 0x81]);
+    }
+
+    #[test]
+    fn quantization_falls_back_to_float_beyond_the_tolerance() {
+        let options = |bits| GlbOptions {
+            quantize: Some(QuantizeOptions {
+                bits,
+                signed: false,
+                tolerance_mm: Some(5.0),
+            }),
+            ..Default::default()
+        };
+
+        // 16 bits: about 2.3 mm on the 300 m axes
+        let glb = generate_glb_with_options(make_flat_points(), &options(16)).unwrap();
+        let json = parse_glb_json(&glb);
+        assert_eq!(json["accessors"][0]["componentType"], 5123);
+
+        // 8 bits: more than half a metre
+        let glb = generate_glb_with_options(make_flat_points(), &options(8)).unwrap();
+        let json = parse_glb_json(&glb);
+        assert_eq!(json["accessors"][0]["componentType"], 5126);
+        assert!(json["nodes"][0].get("scale").is_none());
+        assert!(json.get("extensionsUsed").is_none());
+    }
+
+    #[test]
+    fn test_generate_glb_with_16bit_rgba_colors() {
+        let mut points = make_test_points();
+        // Dark grays that share one linear 8-bit value
+        points.points[1].color = Color {
+            r: 3000,
+            g: 3000,
+            b: 3000,
+        };
+        points.points[2].color = Color {
+            r: 3300,
+            g: 3300,
+            b: 3300,
+        };
+        assert_eq!(
+            points.points[1].to_rgb8_normalized(),
+            points.points[2].to_rgb8_normalized()
+        );
+
+        let options = |space| GlbOptions {
+            color: ColorOptions {
+                bits: 16,
+                space,
+                alpha: Some(0.5),
+            },
+            ..Default::default()
+        };
+        let glb = generate_glb_with_options(points.clone(), &options(ColorSpace::Linear)).unwrap();
+        let json = parse_glb_json(&glb);
+        assert_eq!(json["bufferViews"][0]["byteStride"], 20);
+        let colors = &json["accessors"][1];
+        assert_eq!(colors["componentType"], 5123);
+        assert_eq!(colors["type"], "VEC4");
+        assert!(colors["normalized"].as_bool().unwrap());
+
+        let bin = glb.bin.as_ref().unwrap();
+        let color = |vertex: usize| {
+            let mut color = [0u16; 4];
+            LittleEndian::read_u16_into(&bin[vertex * 20 + 12..vertex * 20 + 20], &mut color);
+            color
+        };
+        assert_eq!(color(0), [65535, 0, 0, 32768]);
+        assert!(color(1)[0] < color(2)[0]);
+
+        // sRGB keeps the input values
+        let glb = generate_glb_with_options(points, &options(ColorSpace::Srgb)).unwrap();
+        let bin = glb.bin.as_ref().unwrap();
+        assert_eq!(LittleEndian::read_u16(&bin[20 + 12..]), 3000);
+    }
+
+    #[test]
+    fn test_generate_glb_without_colors() {
+        let mut points = make_test_points();
+        points.metadata.attributes.color = false;
+        let primitive_attributes = |json: &serde_json::Value| {
+            json["meshes"][0]["primitives"][0]["attributes"]
+                .as_object()
+                .unwrap()
+                .clone()
+        };
+
+        let glb = generate_glb_with_options(
+            points.clone(),
+            &GlbOptions {
+                normals: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let json = parse_glb_json(&glb);
+        let attributes = primitive_attributes(&json);
+        assert!(!attributes.contains_key("COLOR_0"));
+        assert_eq!(attributes["NORMAL"], 1);
+        assert_eq!(json["bufferViews"][0]["byteStride"], 24);
+        assert_eq!(json["accessors"][1]["byteOffset"], 12);
+        assert_eq!(glb.bin.as_ref().unwrap().len(), 3 * 24);
+
+        // Meshopt has no color stream to compress
+        let glb = generate_glb_with_options(
+            points.clone(),
+            &GlbOptions {
+                meshopt: Some(MeshoptOptions::default()),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let json = parse_glb_json(&glb);
+        let names = json["bufferViews"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|view| view["name"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["positions"]);
+        assert!(!primitive_attributes(&json).contains_key("COLOR_0"));
+
+        // Draco holds the positions only
+        let glb = generate_glb_with_options(
+            points,
+            &GlbOptions {
+                draco: Some(DracoOptions::default()),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let json = parse_glb_json(&glb);
+        assert_eq!(json["accessors"].as_array().unwrap().len(), 1);
+        let draco = &json["meshes"][0]["primitives"][0]["extensions"]["KHR_draco_mesh_compression"];
+        assert_eq!(draco["attributes"], serde_json::json!({ "POSITION": 0 }));
+    }
+
     #[test]
     fn test_generate_meshopt_glb() {
         let points = make_test_points();
         let glb = generate_glb_with_options(
             points,
             &GlbOptions {
-                meshopt: true,
+                meshopt: Some(MeshoptOptions::default()),
                 ..Default::default()
             },
         )
@@ -565,18 +1616,27 @@ mod tests {
                 .unwrap()
         );
 
-        // Buffer view should reference buffer 1 with meshopt extension
+        // Buffer views of the position and color streams should reference buffer 1 with meshopt
+        // extension
         let buffer_views = json["bufferViews"].as_array().unwrap();
+        assert_eq!(buffer_views.len(), 2);
         assert_eq!(buffer_views[0]["buffer"], 1);
         let meshopt_ext = &buffer_views[0]["extensions"]["EXT_meshopt_compression"];
         assert_eq!(meshopt_ext["buffer"], 0);
-        assert_eq!(meshopt_ext["byteStride"], 16);
+        assert_eq!(meshopt_ext["byteStride"], 12);
         assert_eq!(meshopt_ext["count"], 3);
         assert_eq!(meshopt_ext["mode"], "ATTRIBUTES");
+        assert!(meshopt_ext.get("filter").is_none());
+        assert_eq!(buffer_views[1]["byteOffset"], 3 * 12);
+        let colors_ext = &buffer_views[1]["extensions"]["EXT_meshopt_compression"];
+        assert_eq!(colors_ext["byteStride"], 4);
+        assert_eq!(colors_ext["byteOffset"].as_u64().unwrap() % 4, 0);
 
         // Position: Float (not quantized)
         let accessors = json["accessors"].as_array().unwrap();
         assert_eq!(accessors[0]["componentType"], 5126);
+        assert_eq!(accessors[1]["bufferView"], 1);
+        assert!(accessors[1].get("byteOffset").is_none());
 
         // EXT_meshopt_compression extension only
         let ext_used: Vec<&str> = json["extensionsUsed"]
@@ -603,8 +1663,8 @@ mod tests {
         let glb = generate_glb_with_options(
             points,
             &GlbOptions {
-                quantize: true,
-                meshopt: true,
+                quantize: Some(QuantizeOptions::default()),
+                meshopt: Some(MeshoptOptions::default()),
                 ..Default::default()
             },
         )
@@ -615,10 +1675,10 @@ mod tests {
         let buffers = json["buffers"].as_array().unwrap();
         assert_eq!(buffers.len(), 2);
 
-        // Buffer view: stride 12, meshopt extension
+        // Position buffer view: stride 8, meshopt extension
         let buffer_views = json["bufferViews"].as_array().unwrap();
         let meshopt_ext = &buffer_views[0]["extensions"]["EXT_meshopt_compression"];
-        assert_eq!(meshopt_ext["byteStride"], 12);
+        assert_eq!(meshopt_ext["byteStride"], 8);
 
         // Position: UnsignedShort, normalized
         let accessors = json["accessors"].as_array().unwrap();
@@ -640,14 +1700,321 @@ mod tests {
         assert!(nodes[0].get("scale").is_some());
     }
 
+    #[test]
+    fn test_generate_filtered_meshopt_glb() {
+        let mut points = make_test_points();
+        for point in &mut points.points {
+            point.attributes.normal = Some([0.0, 0.6, 0.8]);
+        }
+        let options = MeshoptOptions { filters: true };
+        let glb = generate_glb_with_options(
+            points,
+            &GlbOptions {
+                meshopt: Some(options),
+                normals: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let json = parse_glb_json(&glb);
+
+        let filters = json["bufferViews"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|view| {
+                let ext = &view["extensions"]["EXT_meshopt_compression"];
+                (
+                    view["name"].as_str().unwrap(),
+                    // NONE is the default and left out
+                    ext["filter"].as_str().unwrap_or("NONE"),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            filters,
+            [
+                ("positions", "EXPONENTIAL"),
+                ("colors", "NONE"),
+                ("normals", "OCTAHEDRAL")
+            ]
+        );
+
+        // Filtered normals are octahedral bytes, which need KHR_mesh_quantization
+        let normals = &json["accessors"][2];
+        assert_eq!(normals["componentType"], 5120);
+        assert_eq!(normals["bufferView"], 2);
+        let ext_required = json["extensionsRequired"].as_array().unwrap();
+        assert!(ext_required.contains(&serde_json::json!("KHR_mesh_quantization")));
+
+        // Every stream is written with vertex codec version 0, recorded in its header byte
+        let bin = glb.bin.as_ref().unwrap();
+        for view in json["bufferViews"].as_array().unwrap() {
+            let offset = view["extensions"]["EXT_meshopt_compression"]["byteOffset"]
+                .as_u64()
+                .unwrap_or(0);
+            assert_eq!(bin[offset as usize], 0xa0);
+        }
+    }
+
+    #[test]
+    fn filters_keep_positions_and_normals_within_their_precision() {
+        let positions = [[1.5f32, -20.25, 300.0], [0.001, 7.0, -0.5]];
+        let mut bytes = vec![0u8; 4 * 3 * positions.len()];
+        LittleEndian::write_f32_into(positions.as_flattened(), &mut bytes);
+        encode_filter(&mut bytes, 2, 12, &MeshoptCompressionFilter::Exponential);
+        // Each value is a 24-bit mantissa and an 8-bit exponent
+        for (value, encoded) in positions.as_flattened().iter().zip(bytes.chunks_exact(4)) {
+            let word = LittleEndian::read_u32(encoded);
+            let mantissa = ((word << 8) as i32 >> 8) as f32;
+            let exponent = (word as i32 >> 24) as f32;
+            let decoded = mantissa * exponent.exp2();
+            assert!(
+                (decoded - value).abs() <= 300.0 / (1 << 16) as f32,
+                "{decoded} {value}"
+            );
+        }
+
+        let normal = quantize_octahedral_normal([0.0, 0.6, 0.8]);
+        let mut bytes = vec![normal[0] as u8, normal[1] as u8, normal[2] as u8, 0];
+        encode_filter(&mut bytes, 1, 4, &MeshoptCompressionFilter::Octahedral);
+        // Decoded z is 127 - |x| - |y| in octahedral space, which is positive
+        assert_eq!(bytes[2], 127);
+        assert!((bytes[1] as i8) > (bytes[0] as i8));
+    }
+
+    #[test]
+    fn test_generate_glb_with_attributes() {
+        let mut points = make_test_points();
+        for (i, point) in points.points.iter_mut().enumerate() {
+            point.attributes.intensity = Some(100 * i as u16);
+            point.attributes.gps_time = Some(1000.0 + i as f64);
+        }
+        let glb = generate_glb_with_options(
+            points,
+            &GlbOptions {
+                quantize: Some(QuantizeOptions::default()),
+                attributes: vec![PointAttribute::Intensity, PointAttribute::GpsTime],
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let json = parse_glb_json(&glb);
+
+        // Two 4-byte attribute slots after the quantized position and color
+        let buffer_views = json["bufferViews"].as_array().unwrap();
+        assert_eq!(buffer_views[0]["byteStride"], 20);
+        let accessors = json["accessors"].as_array().unwrap();
+        assert_eq!(accessors.len(), 4);
+        assert_eq!(accessors[2]["componentType"], 5123);
+        assert_eq!(accessors[2]["byteOffset"], 12);
+        assert_eq!(accessors[3]["componentType"], 5126);
+        assert_eq!(accessors[3]["byteOffset"], 16);
+
+        let primitive = &json["meshes"][0]["primitives"][0];
+        assert_eq!(primitive["attributes"]["_INTENSITY"], 2);
+        assert_eq!(primitive["attributes"]["_GPS_TIME"], 3);
+        assert_eq!(
+            primitive["extensions"]["EXT_structural_metadata"]["propertyAttributes"],
+            serde_json::json!([0])
+        );
+        let metadata = &json["extensions"]["EXT_structural_metadata"];
+        let properties = &metadata["propertyAttributes"][0]["properties"];
+        assert_eq!(properties["gpsTime"]["offset"], 1000.0);
+        assert_eq!(json["extensionsUsed"][1], "EXT_structural_metadata");
+
+        // Third vertex: intensity 200, GPS time 1002 - 1000
+        let bin = glb.bin.as_ref().unwrap();
+        assert_eq!(bin.len(), 3 * 20);
+        assert_eq!(LittleEndian::read_u16(&bin[52..54]), 200);
+        assert_eq!(LittleEndian::read_f32(&bin[56..60]), 2.0);
+    }
+
+    #[test]
+    fn test_generate_glb_leaves_out_attributes_missing_from_the_input() {
+        let mut points = make_test_points();
+        for point in &mut points.points {
+            point.attributes.intensity = Some(100);
+        }
+        points.metadata.attributes.gps_time = false;
+        let glb = generate_glb_with_options(
+            points,
+            &GlbOptions {
+                attributes: vec![PointAttribute::Intensity, PointAttribute::GpsTime],
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let json = parse_glb_json(&glb);
+        let primitive_attributes = &json["meshes"][0]["primitives"][0]["attributes"];
+        assert!(primitive_attributes.get("_INTENSITY").is_some());
+        assert!(primitive_attributes.get("_GPS_TIME").is_none());
+        let properties = &json["extensions"]["EXT_structural_metadata"]["schema"]["classes"]["point"]
+            ["properties"];
+        assert!(properties.get("gpsTime").is_none());
+        assert!(properties["intensity"].get("noData").is_none());
+    }
+
+    #[test]
+    fn test_generate_glb_with_normals() {
+        let mut points = make_test_points();
+        points.points[0].attributes.normal = Some([0.0, 1.0, 0.0]);
+        points.points[1].attributes.normal = Some([0.6, 0.0, -0.8]);
+        for quantize in [false, true] {
+            let glb = generate_glb_with_options(
+                points.clone(),
+                &GlbOptions {
+                    quantize: quantize.then(QuantizeOptions::default),
+                    normals: true,
+                    attributes: vec![PointAttribute::Intensity],
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+            let json = parse_glb_json(&glb);
+            let accessors = json["accessors"].as_array().unwrap();
+            let primitive = &json["meshes"][0]["primitives"][0];
+            assert_eq!(primitive["attributes"]["NORMAL"], 2);
+            assert_eq!(primitive["attributes"]["_INTENSITY"], 3);
+            assert_eq!(accessors[2]["type"], "VEC3");
+
+            let bin = glb.bin.as_ref().unwrap();
+            if quantize {
+                // Normalized bytes after the 12-byte quantized vertex
+                assert_eq!(accessors[2]["componentType"], 5120);
+                assert!(accessors[2]["normalized"].as_bool().unwrap());
+                assert_eq!(accessors[2]["byteOffset"], 12);
+                assert_eq!(accessors[3]["byteOffset"], 16);
+                assert_eq!(json["bufferViews"][0]["byteStride"], 20);
+                assert_eq!(&bin[12..15], [0, 127, 0]);
+            } else {
+                assert_eq!(accessors[2]["componentType"], 5126);
+                assert_eq!(accessors[2]["byteOffset"], 16);
+                assert_eq!(accessors[3]["byteOffset"], 28);
+                assert_eq!(json["bufferViews"][0]["byteStride"], 32);
+                assert_eq!(LittleEndian::read_f32(&bin[32 + 24..32 + 28]), -0.8);
+            }
+        }
+    }
+
+    #[test]
+    fn octahedral_normals_stay_unit_length() {
+        for normal in [[0.0, 0.0, -1.0], [0.6, 0.0, -0.8], [-0.48, 0.6, 0.64]] {
+            let quantized = quantize_octahedral_normal(normal);
+            let decoded = quantized.map(|c| c as f32 / 127.0);
+            let length = decoded.iter().map(|c| c * c).sum::<f32>().sqrt();
+            assert!((length - 1.0).abs() < 0.02, "{normal:?}: {length}");
+            let cos = decoded.iter().zip(normal).map(|(a, b)| a * b).sum::<f32>();
+            assert!(cos > 0.999, "{normal:?}: {decoded:?}");
+        }
+    }
+
+    #[test]
+    fn test_generate_draco_glb() {
+        let points = make_test_points();
+        let glb = generate_glb_with_options(
+            points.clone(),
+            &GlbOptions {
+                draco: Some(DracoOptions::default()),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let json = parse_glb_json(&glb);
+
+        // Only the Draco data is stored
+        let buffer_views = json["bufferViews"].as_array().unwrap();
+        assert_eq!(buffer_views.len(), 1);
+        assert_eq!(buffer_views[0]["name"], "draco");
+        let accessors = json["accessors"].as_array().unwrap();
+        assert!(accessors[0].get("bufferView").is_none());
+        assert_eq!(accessors[0]["componentType"], 5126);
+        assert_eq!(accessors[1]["componentType"], 5126);
+        assert!(accessors[1].get("bufferView").is_none());
+
+        let draco = &json["meshes"][0]["primitives"][0]["extensions"]["KHR_draco_mesh_compression"];
+        assert_eq!(draco["bufferView"], 0);
+        assert_eq!(draco["attributes"]["POSITION"], 0);
+        assert_eq!(draco["attributes"]["COLOR_0"], 1);
+        assert_eq!(json["extensionsRequired"][0], "KHR_draco_mesh_compression");
+        assert_eq!(&glb.bin.as_ref().unwrap()[..5], b"DRACO");
+
+        // Other attributes stay interleaved next to the Draco data
+        let glb = generate_glb_with_options(
+            points,
+            &GlbOptions {
+                quantize: Some(QuantizeOptions::default()),
+                draco: Some(DracoOptions::default()),
+                attributes: vec![PointAttribute::Intensity],
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let json = parse_glb_json(&glb);
+        let buffer_views = json["bufferViews"].as_array().unwrap();
+        assert_eq!(buffer_views[0]["byteStride"], 4);
+        assert_eq!(buffer_views[0]["byteLength"], 3 * 4);
+        assert_eq!(buffer_views[1]["byteOffset"], 3 * 4);
+        assert_eq!(json["accessors"][2]["bufferView"], 0);
+        let ext_used = json["extensionsUsed"].as_array().unwrap();
+        assert!(!ext_used.contains(&serde_json::json!("KHR_mesh_quantization")));
+    }
+
+    #[test]
+    fn test_generate_glb_with_feature_ids() {
+        let mut points = make_test_points();
+        for (i, point) in points.points.iter_mut().enumerate() {
+            point.attributes.source_file = Some(1 - i.min(1) as u32);
+        }
+        let glb = generate_glb_with_options(
+            points,
+            &GlbOptions {
+                meshopt: Some(MeshoptOptions::default()),
+                feature_ids: Some(FeatureIdSource::SourceFile),
+                source_files: vec!["a.las".to_string(), "b.las".to_string()],
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let json = parse_glb_json(&glb);
+
+        let accessors = json["accessors"].as_array().unwrap();
+        assert_eq!(accessors[2]["componentType"], 5126);
+        assert_eq!(accessors[2]["bufferView"], 2);
+        let primitive = &json["meshes"][0]["primitives"][0];
+        assert_eq!(primitive["attributes"]["_FEATURE_ID_0"], 2);
+        let feature_ids = &primitive["extensions"]["EXT_mesh_features"]["featureIds"][0];
+        assert_eq!(feature_ids["featureCount"], 2);
+        assert_eq!(feature_ids["attribute"], 0);
+        assert!(
+            primitive["extensions"]
+                .get("EXT_structural_metadata")
+                .is_none()
+        );
+
+        // The property table follows the compressed vertices in buffer 0
+        let metadata = &json["extensions"]["EXT_structural_metadata"];
+        let file_name = &metadata["propertyTables"][0]["properties"]["fileName"];
+        let values = &json["bufferViews"][file_name["values"].as_u64().unwrap() as usize];
+        assert_eq!(values["buffer"], 0);
+        let start = values["byteOffset"].as_u64().unwrap() as usize;
+        let bin = glb.bin.as_ref().unwrap();
+        assert_eq!(&bin[start..start + 10], b"a.lasb.las");
+        assert_eq!(json["buffers"][0]["byteLength"], bin.len());
+        assert!(metadata["schema"]["classes"].get("point").is_none());
+
+        let ext_used = json["extensionsUsed"].as_array().unwrap();
+        assert!(ext_used.contains(&serde_json::json!("EXT_mesh_features")));
+    }
+
     #[test]
     fn test_glb_roundtrip_parseable() {
         // Verify all 4 variants produce parseable GLBs
         for (quantize, meshopt) in [(false, false), (true, false), (false, true), (true, true)] {
             let points = make_test_points();
             let options = GlbOptions {
-                quantize,
-                meshopt,
+                quantize: quantize.then(QuantizeOptions::default),
+                meshopt: meshopt.then(MeshoptOptions::default),
                 ..Default::default()
             };
             let glb = generate_glb_with_options(points, &options).unwrap();
@@ -1,3 +1,6 @@
+pub mod attributes;
 pub mod cesiumtiles;
+pub mod draco;
+pub mod features;
 pub mod gltf;
 pub mod tiling;
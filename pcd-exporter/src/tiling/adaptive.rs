@@ -0,0 +1,167 @@
+use std::collections::{HashMap, HashSet};
+
+use pcd_core::pointcloud::point::Point;
+
+use super::{Subdivision, TileZXYH, TilingScheme, calc_parent_zxyh};
+
+/// Number of zoom levels below the maximum zoom level that dense tiles may be split into. It
+/// bounds the depth where many points share a position and no split brings them under budget.
+pub const MAX_SPLIT_LEVELS: u8 = 6;
+
+/// Point budget for adaptive subdivision.
+///
+/// Leaf tiles at the maximum zoom level holding more points than the budget are split into
+/// children, down to [`MAX_SPLIT_LEVELS`] further levels. Tiles are then built bottom-up, and a
+/// parent absorbs its children and becomes a leaf itself when none of the children has been
+/// split further and their points fit into the budget. The leaf depth thus follows the local
+/// point density instead of always being the maximum zoom level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PointBudget {
+    pub max_points: usize,
+}
+
+impl PointBudget {
+    pub fn new(max_points: usize) -> Self {
+        Self { max_points }
+    }
+
+    /// Deepest zoom level that tiles at `max_zoom` may be split into
+    pub fn deepest_zoom(&self, max_zoom: u8) -> u8 {
+        max_zoom.saturating_add(MAX_SPLIT_LEVELS)
+    }
+
+    /// Splits a leaf tile into descendants until each of them fits into the budget or lies
+    /// [`MAX_SPLIT_LEVELS`] below the tile. Empty descendants are left out.
+    pub fn split_leaf(
+        &self,
+        scheme: &dyn TilingScheme,
+        subdivision: Subdivision,
+        tile: TileZXYH,
+        points: Vec<Point>,
+    ) -> Vec<(TileZXYH, Vec<Point>)> {
+        let deepest_zoom = self.deepest_zoom(tile.0);
+        let mut leaves = Vec::new();
+        let mut pending = vec![(tile, points)];
+        while let Some((tile, points)) = pending.pop() {
+            if points.len() <= self.max_points || tile.0 >= deepest_zoom {
+                leaves.push((tile, points));
+                continue;
+            }
+            let mut children = HashMap::<TileZXYH, Vec<Point>>::new();
+            for point in points {
+                let child = subdivision.zxyh_from_lng_lat_height(
+                    scheme,
+                    tile.0 + 1,
+                    point.x,
+                    point.y,
+                    point.z,
+                );
+                children.entry(child).or_default().push(point);
+            }
+            pending.extend(children);
+        }
+        leaves.sort_unstable_by_key(|(tile, _)| *tile);
+        leaves
+    }
+
+    /// Whether the children of a tile should be merged into it instead of being kept as
+    /// separate tiles
+    pub fn merges_children(&self, point_count: usize, children_are_leaves: bool) -> bool {
+        children_are_leaves && point_count <= self.max_points
+    }
+}
+
+/// Parents of the given tiles, which therefore cannot be merged into their own parents
+pub fn parent_tiles(
+    scheme: &dyn TilingScheme,
+    tiles: impl IntoIterator<Item = TileZXYH>,
+) -> HashSet<TileZXYH> {
+    tiles
+        .into_iter()
+        .map(|(z, x, y, h)| calc_parent_zxyh(scheme, z, x, y, h))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use pcd_core::pointcloud::point::{Color, PointAttributes};
+
+    use super::*;
+    use crate::tiling::Geographic;
+
+    fn point(lng: f64, lat: f64) -> Point {
+        Point {
+            x: lng,
+            y: lat,
+            z: 0.0,
+            color: Color::default(),
+            attributes: PointAttributes::default(),
+        }
+    }
+
+    #[test]
+    fn only_small_leaf_children_are_merged() {
+        let budget = PointBudget::new(100);
+        assert!(budget.merges_children(100, true));
+        assert!(!budget.merges_children(101, true));
+        assert!(!budget.merges_children(10, false));
+    }
+
+    #[test]
+    fn dense_leaves_are_split_until_they_fit() {
+        let scheme = Geographic;
+        let tile = scheme.zxy_from_lng_lat(18, 139.7, 35.6);
+        let (west, south, east, north) = scheme.bounds(tile.0, tile.1, tile.2);
+        let (width, height) = (east - west, north - south);
+        // Three points in the south-west quarter of the tile and one in the north-east quarter
+        let points = [(0.1, 0.1), (0.2, 0.2), (0.3, 0.1), (0.9, 0.9)]
+            .map(|(u, v)| point(west + width * u, south + height * v));
+        let tile = (tile.0, tile.1, tile.2, 0);
+
+        let budget = PointBudget::new(4);
+        let leaves = budget.split_leaf(&scheme, Subdivision::Quadtree, tile, points.to_vec());
+        assert_eq!(leaves.len(), 1);
+        assert_eq!(leaves[0].0, tile);
+
+        let budget = PointBudget::new(2);
+        let leaves = budget.split_leaf(&scheme, Subdivision::Quadtree, tile, points.to_vec());
+        let counts = leaves
+            .iter()
+            .map(|((z, _, _, _), points)| (*z, points.len()))
+            .collect::<Vec<_>>();
+        // The south-west child is split once more, the north-east child fits
+        assert_eq!(counts.iter().map(|(_, n)| n).sum::<usize>(), 4);
+        assert!(counts.iter().all(|&(z, n)| n <= 2 && z > 18 && z <= 20));
+        assert!(counts.contains(&(19, 1)));
+        for (leaf, _) in &leaves {
+            let mut ancestor = *leaf;
+            while ancestor.0 > tile.0 {
+                let (z, x, y, h) = ancestor;
+                ancestor = calc_parent_zxyh(&scheme, z, x, y, h);
+            }
+            assert_eq!(ancestor, tile);
+        }
+    }
+
+    #[test]
+    fn splitting_stops_below_the_maximum_depth() {
+        let scheme = Geographic;
+        let (z, x, y) = scheme.zxy_from_lng_lat(18, 139.7, 35.6);
+        // Points at the same position can never be separated
+        let points = vec![point(139.7, 35.6); 10];
+        let leaves =
+            PointBudget::new(1).split_leaf(&scheme, Subdivision::Quadtree, (z, x, y, 0), points);
+        assert_eq!(leaves.len(), 1);
+        assert_eq!(leaves[0].0.0, 18 + MAX_SPLIT_LEVELS);
+        assert_eq!(leaves[0].1.len(), 10);
+    }
+
+    #[test]
+    fn parents_of_split_children_are_collected() {
+        let parents = parent_tiles(
+            &Geographic,
+            [(18, 10, 20, 0), (18, 11, 21, 0), (18, 8, 20, 0)],
+        );
+        assert_eq!(parents, HashSet::from([(17, 5, 10, 0), (17, 4, 10, 0)]));
+    }
+}
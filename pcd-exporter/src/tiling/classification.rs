@@ -0,0 +1,124 @@
+use std::str::FromStr;
+
+use pcd_core::pointcloud::point::Point;
+
+/// Groups of ASPRS LAS classes written as separate tile contents
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ClassificationGroup {
+    Ground,
+    Vegetation,
+    Building,
+    /// Every class not covered by the other groups, and unclassified points
+    Other,
+}
+
+impl ClassificationGroup {
+    /// All groups, in the order of the tileset `groups` array
+    pub const ALL: [Self; 4] = [Self::Ground, Self::Vegetation, Self::Building, Self::Other];
+
+    pub fn from_class(class: u8) -> Self {
+        match class {
+            2 => Self::Ground,
+            3..=5 => Self::Vegetation,
+            6 => Self::Building,
+            _ => Self::Other,
+        }
+    }
+
+    /// Group of a point by its `classification` attribute
+    pub fn of_point(point: &Point) -> Self {
+        point
+            .attributes
+            .classification
+            .as_deref()
+            .and_then(|class| u8::from_str(class).ok())
+            .map_or(Self::Other, Self::from_class)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Ground => "ground",
+            Self::Vegetation => "vegetation",
+            Self::Building => "building",
+            Self::Other => "other",
+        }
+    }
+
+    /// ASPRS classes of the group; empty for [`Self::Other`]
+    pub fn classes(self) -> &'static [u8] {
+        match self {
+            Self::Ground => &[2],
+            Self::Vegetation => &[3, 4, 5],
+            Self::Building => &[6],
+            Self::Other => &[],
+        }
+    }
+
+    /// Index of the group in the tileset `groups` array
+    pub fn index(self) -> u32 {
+        self as u32
+    }
+
+    /// Content path of the group's layer, derived from the path of the whole tile
+    pub fn content_path(self, tile_path: &str) -> String {
+        let stem = tile_path.strip_suffix(".glb").unwrap_or(tile_path);
+        format!("{stem}_{}.glb", self.name())
+    }
+
+    /// Splits points into non-empty groups, in group order
+    pub fn split(points: Vec<Point>) -> Vec<(Self, Vec<Point>)> {
+        let mut groups: [Vec<Point>; 4] = Default::default();
+        for point in points {
+            groups[Self::of_point(&point).index() as usize].push(point);
+        }
+        Self::ALL
+            .into_iter()
+            .zip(groups)
+            .filter(|(_, points)| !points.is_empty())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pcd_core::pointcloud::point::{Color, PointAttributes};
+
+    fn point(classification: Option<&str>) -> Point {
+        Point {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+            color: Color::default(),
+            attributes: PointAttributes {
+                classification: classification.map(str::to_string),
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn points_are_split_by_asprs_class() {
+        let points = ["2", "5", "1", "2", "9"]
+            .map(|class| point(Some(class)))
+            .into_iter()
+            .chain([point(None)])
+            .collect();
+        let groups = ClassificationGroup::split(points)
+            .into_iter()
+            .map(|(group, points)| (group, points.len()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            groups,
+            [
+                (ClassificationGroup::Ground, 2),
+                (ClassificationGroup::Vegetation, 1),
+                (ClassificationGroup::Other, 3),
+            ]
+        );
+        assert_eq!(
+            ClassificationGroup::Building.content_path("18/1/2/3.glb"),
+            "18/1/2/3_building.glb"
+        );
+    }
+}
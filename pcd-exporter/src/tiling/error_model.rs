@@ -0,0 +1,113 @@
+use std::collections::BTreeMap;
+
+use tinymvt::TileZXY;
+
+use super::scheme::TilingScheme;
+
+/// Default ratio between the decimation voxel size and the tile's geometric error
+pub const DEFAULT_VOXEL_FACTOR: f64 = 0.1;
+
+/// How the geometric error written for each tile is derived
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeometricErrorModel {
+    /// Scales computed errors; per-level overrides are used as is
+    pub multiplier: f64,
+    /// Fixed errors by zoom level
+    pub overrides: BTreeMap<u8, f64>,
+    /// Derive errors from the point spacing of each tile instead of its zoom level
+    pub data_driven: bool,
+    /// Ratio between the decimation voxel size and the zoom-level geometric error
+    pub voxel_factor: f64,
+}
+
+impl Default for GeometricErrorModel {
+    fn default() -> Self {
+        Self {
+            multiplier: 1.0,
+            overrides: BTreeMap::new(),
+            data_driven: false,
+            voxel_factor: DEFAULT_VOXEL_FACTOR,
+        }
+    }
+}
+
+impl GeometricErrorModel {
+    /// Geometric error of tile `zxy` in `scheme`. `point_spacing` is the average spacing
+    /// (metres) of the tile's points and is only used in data-driven mode.
+    ///
+    /// Data-driven errors are at least the zoom-level error of the next level. A single point,
+    /// or points along a line, have no area to spread over and thus no spacing, and a tile with
+    /// an error of zero would never be refined into its children.
+    pub fn tile_error(
+        &self,
+        scheme: &dyn TilingScheme,
+        zxy: TileZXY,
+        point_spacing: Option<f64>,
+    ) -> f64 {
+        let (z, x, y) = zxy;
+        if let Some(&error) = self.overrides.get(&z) {
+            return error;
+        }
+        let zoom_error = scheme.geometric_error(z, x, y);
+        let spacing = point_spacing.filter(|spacing| self.data_driven && spacing.is_finite());
+        let error = match spacing {
+            // Decimated tiles keep about one point per voxel, so this matches the zoom-level
+            // error when the spacing equals the voxel size
+            Some(spacing) => (spacing / self.voxel_factor).max(zoom_error / 2.0),
+            None => zoom_error,
+        };
+        error * self.multiplier
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tiling::scheme::{Geographic, geometric_error};
+
+    #[test]
+    fn default_model_uses_zoom_level_error() {
+        let model = GeometricErrorModel::default();
+        assert_eq!(
+            model.tile_error(&Geographic, (16, 10, 20), Some(0.5)),
+            geometric_error(16, 20)
+        );
+    }
+
+    #[test]
+    fn overrides_take_precedence_over_multiplier_and_data() {
+        let model = GeometricErrorModel {
+            multiplier: 2.0,
+            overrides: BTreeMap::from([(16, 5.0)]),
+            data_driven: true,
+            voxel_factor: 0.5,
+        };
+        assert_eq!(model.tile_error(&Geographic, (16, 10, 20), Some(1.0)), 5.0);
+        assert_eq!(model.tile_error(&Geographic, (17, 20, 40), Some(5.0)), 20.0);
+        assert_eq!(
+            model.tile_error(&Geographic, (17, 20, 40), None),
+            geometric_error(17, 40) * 2.0
+        );
+    }
+
+    #[test]
+    fn data_driven_errors_are_floored_at_the_next_level() {
+        let model = GeometricErrorModel {
+            data_driven: true,
+            ..Default::default()
+        };
+        // A single point or a line of points has a spacing of zero
+        assert_eq!(
+            model.tile_error(&Geographic, (16, 10, 20), Some(0.0)),
+            geometric_error(16, 20) / 2.0
+        );
+        for spacing in [f64::NAN, f64::INFINITY] {
+            let error = model.tile_error(&Geographic, (16, 10, 20), Some(spacing));
+            assert_eq!(error, geometric_error(16, 20));
+        }
+        assert_eq!(
+            model.tile_error(&Geographic, (16, 10, 20), Some(100.0)),
+            1000.0
+        );
+    }
+}
@@ -0,0 +1,591 @@
+use std::collections::{HashMap, HashSet};
+
+use cesiumtiles::{subtree, tileset};
+
+use super::error_model::GeometricErrorModel;
+use super::scheme::{
+    Geographic, Subdivision, TileZXYH, h_slice_range, x_slice_range, x_step, y_slice_range,
+};
+use super::tree::TileContent;
+
+/// Zoom level of the implicit tiling roots.
+///
+/// The geographic scheme is irregular at z=0 and z=1. From z=2 on, tiles between 45°S and
+/// 45°N split into exactly 2x2 children, so each z=2 tile can act as the root of a uniform
+/// quadtree (or octree) there. Beyond ±45°, tiles deeper than z=2 are merged along longitude
+/// (see [`x_step`]) and do not fit a uniform quadtree; [`check_implicit_tile`] refuses them.
+pub const IMPLICIT_ROOT_ZOOM: u8 = 2;
+
+/// Fails for tiles that implicit tiling cannot address, which are those the geographic scheme
+/// merges along longitude
+pub fn check_implicit_tile((z, x, y): (u8, u32, u32)) -> Result<(), String> {
+    if z > IMPLICIT_ROOT_ZOOM && x_step(z, y) > 1 {
+        return Err(format!(
+            "tile {z}/{x}/{y} lies beyond 45° latitude, where the geographic tiles are merged \
+             along longitude and cannot be written with --implicit"
+        ));
+    }
+    Ok(())
+}
+
+/// Tile coordinates relative to an implicit root: (level, x, y, z)
+type ImplicitCoord = (u32, u32, u32, u32);
+
+/// A binary `.subtree` file and its path relative to the output directory
+#[derive(Debug)]
+pub struct SubtreeFile {
+    pub path: String,
+    pub data: Vec<u8>,
+}
+
+/// 3D Tiles 1.1 implicit tiling of the [`Geographic`] scheme, rooted at [`IMPLICIT_ROOT_ZOOM`]
+#[derive(Debug, Clone)]
+pub struct ImplicitTiling {
+    pub subdivision: Subdivision,
+    pub subtree_levels: u32,
+    pub refine: tileset::Refine,
+    /// Only the errors of the implicit roots are used; they halve at every level below
+    pub error_model: GeometricErrorModel,
+}
+
+impl ImplicitTiling {
+    pub fn new(subdivision: Subdivision, subtree_levels: u32) -> Self {
+        Self {
+            subdivision,
+            subtree_levels: subtree_levels.max(1),
+            refine: tileset::Refine::Replace,
+            error_model: GeometricErrorModel::default(),
+        }
+    }
+
+    /// Path of the tile content, following the URI template of its implicit root
+    pub fn content_path(&self, tile: TileZXYH) -> String {
+        let (root, (level, x, y, z)) = split_tile(tile);
+        let root_dir = self.root_dir(root);
+        match self.subdivision {
+            Subdivision::Quadtree => format!("{root_dir}/{level}/{x}/{y}.glb"),
+            Subdivision::Octree => format!("{root_dir}/{level}/{x}/{y}/{z}.glb"),
+        }
+    }
+
+    /// Builds the explicit root tile pointing to one implicit tile per z=2 tile, together
+    /// with the subtree files describing tile and content availability below them.
+    ///
+    /// Content must have been exported with translations baked into the GLB, since implicit
+    /// tiles cannot carry their own transform.
+    pub fn build(&self, contents: Vec<TileContent>) -> (tileset::Tile, Vec<SubtreeFile>) {
+        let mut roots: HashMap<TileZXYH, Vec<TileContent>> = HashMap::new();
+        for content in contents {
+            let (z, x, y) = content.zxy;
+            assert!(
+                z >= IMPLICIT_ROOT_ZOOM,
+                "implicit tiling requires contents at z >= {IMPLICIT_ROOT_ZOOM}"
+            );
+            if let Err(message) = check_implicit_tile(content.zxy) {
+                panic!("{message}");
+            }
+            let (root, _) = split_tile((z, x, y, content.h));
+            roots.entry(root).or_default().push(content);
+        }
+
+        let mut roots = roots.into_iter().collect::<Vec<_>>();
+        roots.sort_by_key(|(root, _)| *root);
+
+        let mut children = Vec::with_capacity(roots.len());
+        let mut subtree_files = Vec::new();
+        for (root, contents) in roots {
+            let (tile, files) = self.build_root(root, &contents);
+            children.push(tile);
+            subtree_files.extend(files);
+        }
+
+        let mut region = [f64::MAX, f64::MAX, f64::MIN, f64::MIN, f64::MAX, f64::MIN];
+        for child in &children {
+            let child_region = child.bounding_volume.region.unwrap();
+            for i in [0, 1, 4] {
+                region[i] = region[i].min(child_region[i]);
+            }
+            for i in [2, 3, 5] {
+                region[i] = region[i].max(child_region[i]);
+            }
+        }
+
+        let root = tileset::Tile {
+            geometric_error: self.error_model.tile_error(&Geographic, (0, 0, 0), None),
+            refine: Some(self.refine),
+            bounding_volume: tileset::BoundingVolume::new_region(region),
+            children: Some(children),
+            ..Default::default()
+        };
+        (root, subtree_files)
+    }
+
+    fn build_root(
+        &self,
+        root: TileZXYH,
+        contents: &[TileContent],
+    ) -> (tileset::Tile, Vec<SubtreeFile>) {
+        let (z, x, y, h) = root;
+
+        let mut content_available = HashSet::new();
+        let mut tile_available = HashSet::new();
+        let mut min_height = f64::MAX;
+        let mut max_height = f64::MIN;
+        for content in contents {
+            let (cz, cx, cy) = content.zxy;
+            let (_, coord) = split_tile((cz, cx, cy, content.h));
+            content_available.insert(coord);
+
+            // Every ancestor up to the implicit root has to be available
+            let (mut level, mut i, mut j, mut k) = coord;
+            while tile_available.insert((level, i, j, k)) && level > 0 {
+                (level, i, j, k) = (level - 1, i / 2, j / 2, k / 2);
+            }
+
+            min_height = min_height.min(content.min_height);
+            max_height = max_height.max(content.max_height);
+        }
+
+        let available_levels = tile_available.iter().map(|c| c.0).max().unwrap() + 1;
+        let subtree_levels = self.subtree_levels.min(available_levels);
+
+        // Octree subdivision splits the height range, so it has to be the exact cell
+        if self.subdivision == Subdivision::Octree {
+            (min_height, max_height) = h_slice_range(z, h);
+        }
+        let (west, east) = x_slice_range(z, x as i32, 1);
+        let (south, north) = y_slice_range(z, y);
+
+        let root_dir = self.root_dir(root);
+        let (content_uri, subtree_uri) = match self.subdivision {
+            Subdivision::Quadtree => (
+                format!("{root_dir}/{{level}}/{{x}}/{{y}}.glb"),
+                format!("{root_dir}/subtrees/{{level}}/{{x}}/{{y}}.subtree"),
+            ),
+            Subdivision::Octree => (
+                format!("{root_dir}/{{level}}/{{x}}/{{y}}/{{z}}.glb"),
+                format!("{root_dir}/subtrees/{{level}}/{{x}}/{{y}}/{{z}}.subtree"),
+            ),
+        };
+
+        let subtrees = self.build_subtrees(subtree_levels, &tile_available, &content_available);
+        let subtree_files = subtrees
+            .into_iter()
+            .map(|((level, i, j, k), subtree)| {
+                let path = match self.subdivision {
+                    Subdivision::Quadtree => format!("{root_dir}/subtrees/{level}/{i}/{j}.subtree"),
+                    Subdivision::Octree => {
+                        format!("{root_dir}/subtrees/{level}/{i}/{j}/{k}.subtree")
+                    }
+                };
+                SubtreeFile {
+                    path,
+                    data: subtree.to_bytes(),
+                }
+            })
+            .collect();
+
+        let tile = tileset::Tile {
+            geometric_error: self.error_model.tile_error(&Geographic, (z, x, y), None),
+            refine: Some(self.refine),
+            bounding_volume: tileset::BoundingVolume::new_region([
+                west.to_radians(),
+                south.to_radians(),
+                east.to_radians(),
+                north.to_radians(),
+                min_height,
+                max_height,
+            ]),
+            content: Some(tileset::Content {
+                uri: content_uri,
+                ..Default::default()
+            }),
+            implicit_tiling: Some(tileset::ImplicitTiling {
+                subdivision_scheme: match self.subdivision {
+                    Subdivision::Quadtree => tileset::SubdivisionScheme::Quadtree,
+                    Subdivision::Octree => tileset::SubdivisionScheme::Octree,
+                },
+                subtree_levels,
+                available_levels,
+                subtrees: tileset::Subtrees {
+                    uri: subtree_uri,
+                    ..Default::default()
+                },
+                extra: None,
+                extensions: None,
+            }),
+            ..Default::default()
+        };
+        (tile, subtree_files)
+    }
+
+    fn build_subtrees(
+        &self,
+        subtree_levels: u32,
+        tile_available: &HashSet<ImplicitCoord>,
+        content_available: &HashSet<ImplicitCoord>,
+    ) -> HashMap<ImplicitCoord, SubtreeAvailability> {
+        let branching = self.branching();
+        let new_subtree = || SubtreeAvailability::new(branching, subtree_levels);
+        let subtree_of = |(level, i, j, k): ImplicitCoord| {
+            let root_level = level - level % subtree_levels;
+            let shift = level - root_level;
+            let root = (root_level, i >> shift, j >> shift, k >> shift);
+            let local = (
+                shift,
+                i - (root.1 << shift),
+                j - (root.2 << shift),
+                k - (root.3 << shift),
+            );
+            (root, local)
+        };
+
+        let mut subtrees: HashMap<ImplicitCoord, SubtreeAvailability> = HashMap::new();
+        for &coord in tile_available {
+            let (root, local) = subtree_of(coord);
+            let index = self.availability_index(local);
+            subtrees
+                .entry(root)
+                .or_insert_with(new_subtree)
+                .tiles
+                .set(index);
+            if content_available.contains(&coord) {
+                subtrees
+                    .entry(root)
+                    .or_insert_with(new_subtree)
+                    .content
+                    .set(index);
+            }
+
+            // Subtree roots below the top one are children of the subtree above them
+            if coord.0 > 0 && coord.0 % subtree_levels == 0 {
+                let (level, i, j, k) = coord;
+                let (parent, (_, li, lj, lk)) = subtree_of((level - 1, i / 2, j / 2, k / 2));
+                let index = self.morton_index(li * 2 + i % 2, lj * 2 + j % 2, lk * 2 + k % 2);
+                subtrees
+                    .entry(parent)
+                    .or_insert_with(new_subtree)
+                    .child_subtrees
+                    .set(index);
+            }
+        }
+        subtrees
+    }
+
+    fn root_dir(&self, (z, x, y, h): TileZXYH) -> String {
+        match self.subdivision {
+            Subdivision::Quadtree => format!("{z}/{x}/{y}"),
+            Subdivision::Octree => format!("{z}/{x}/{y}/{h}"),
+        }
+    }
+
+    fn branching(&self) -> usize {
+        match self.subdivision {
+            Subdivision::Quadtree => 4,
+            Subdivision::Octree => 8,
+        }
+    }
+
+    /// Position of a tile in the availability bitstream of its subtree: levels are stored
+    /// one after another and tiles are in Morton order within a level.
+    fn availability_index(&self, (level, x, y, z): ImplicitCoord) -> usize {
+        let n = self.branching();
+        let level_offset = (n.pow(level) - 1) / (n - 1);
+        level_offset + self.morton_index(x, y, z)
+    }
+
+    fn morton_index(&self, x: u32, y: u32, z: u32) -> usize {
+        let (x, y, z) = (x as usize, y as usize, z as usize);
+        let mut index = 0;
+        let mut bit = 0;
+        while (x | y | z) >> bit != 0 {
+            match self.subdivision {
+                Subdivision::Quadtree => {
+                    index |= ((x >> bit) & 1) << (2 * bit);
+                    index |= ((y >> bit) & 1) << (2 * bit + 1);
+                }
+                Subdivision::Octree => {
+                    index |= ((x >> bit) & 1) << (3 * bit);
+                    index |= ((y >> bit) & 1) << (3 * bit + 1);
+                    index |= ((z >> bit) & 1) << (3 * bit + 2);
+                }
+            }
+            bit += 1;
+        }
+        index
+    }
+}
+
+/// Splits a tile into its implicit root at [`IMPLICIT_ROOT_ZOOM`] and its coordinates
+/// relative to that root.
+fn split_tile((z, x, y, h): TileZXYH) -> (TileZXYH, ImplicitCoord) {
+    let level = (z - IMPLICIT_ROOT_ZOOM) as u32;
+    let root = (IMPLICIT_ROOT_ZOOM, x >> level, y >> level, h >> level);
+    let coord = (
+        level,
+        x - (root.1 << level),
+        y - (root.2 << level),
+        h - (root.3 << level),
+    );
+    (root, coord)
+}
+
+#[derive(Debug)]
+struct Bitstream {
+    bits: Vec<u8>,
+    len: usize,
+}
+
+impl Bitstream {
+    fn new(len: usize) -> Self {
+        Self {
+            bits: vec![0; len.div_ceil(8)],
+            len,
+        }
+    }
+
+    fn set(&mut self, index: usize) {
+        self.bits[index / 8] |= 1 << (index % 8);
+    }
+
+    fn count(&self) -> usize {
+        self.bits.iter().map(|b| b.count_ones() as usize).sum()
+    }
+}
+
+#[derive(Debug)]
+struct SubtreeAvailability {
+    tiles: Bitstream,
+    content: Bitstream,
+    child_subtrees: Bitstream,
+}
+
+impl SubtreeAvailability {
+    fn new(branching: usize, subtree_levels: u32) -> Self {
+        let tile_count = (branching.pow(subtree_levels) - 1) / (branching - 1);
+        let child_count = branching.pow(subtree_levels);
+        Self {
+            tiles: Bitstream::new(tile_count),
+            content: Bitstream::new(tile_count),
+            child_subtrees: Bitstream::new(child_count),
+        }
+    }
+
+    /// Encodes the subtree in the binary `.subtree` format
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut binary: Vec<u8> = Vec::new();
+        let mut buffer_views = Vec::new();
+        let mut availability = |bitstream: &Bitstream| {
+            let count = bitstream.count();
+            if count == 0 || count == bitstream.len {
+                return subtree::Availability {
+                    constant: Some((count != 0) as u8),
+                    ..Default::default()
+                };
+            }
+            buffer_views.push(subtree::BufferView {
+                buffer: 0,
+                byte_offset: binary.len() as u32,
+                byte_length: bitstream.bits.len() as u32,
+                ..Default::default()
+            });
+            binary.extend(&bitstream.bits);
+            binary.resize(binary.len().next_multiple_of(8), 0);
+            subtree::Availability {
+                bitstream: Some(buffer_views.len() as u32 - 1),
+                available_count: Some(count as u32),
+                ..Default::default()
+            }
+        };
+
+        let tile_availability = availability(&self.tiles);
+        let content_availability = availability(&self.content);
+        let child_subtree_availability = availability(&self.child_subtrees);
+
+        let subtree = subtree::Subtree {
+            buffers: (!binary.is_empty()).then(|| {
+                vec![subtree::Buffer {
+                    byte_length: binary.len() as u32,
+                    ..Default::default()
+                }]
+            }),
+            buffer_views: (!buffer_views.is_empty()).then_some(buffer_views),
+            tile_availability,
+            content_availability: Some(vec![content_availability]),
+            child_subtree_availability,
+            ..Default::default()
+        };
+
+        let mut json = serde_json::to_vec(&subtree).unwrap();
+        json.resize(json.len().next_multiple_of(8), b' ');
+
+        let mut bytes = Vec::with_capacity(24 + json.len() + binary.len());
+        bytes.extend(b"subt");
+        bytes.extend(1u32.to_le_bytes());
+        bytes.extend((json.len() as u64).to_le_bytes());
+        bytes.extend((binary.len() as u64).to_le_bytes());
+        bytes.extend(json);
+        bytes.extend(binary);
+        bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tiling::scheme::TilingScheme;
+
+    fn content(tile: TileZXYH) -> TileContent {
+        let (z, x, y, h) = tile;
+        TileContent {
+            zxy: (z, x, y),
+            h,
+            min_height: 0.0,
+            max_height: 10.0,
+            ..Default::default()
+        }
+    }
+
+    fn read_subtree(data: &[u8]) -> (serde_json::Value, Vec<u8>) {
+        assert_eq!(&data[0..4], b"subt");
+        let json_len = u64::from_le_bytes(data[8..16].try_into().unwrap()) as usize;
+        let bin_len = u64::from_le_bytes(data[16..24].try_into().unwrap()) as usize;
+        assert_eq!(json_len % 8, 0);
+        let json = serde_json::from_slice(&data[24..24 + json_len]).unwrap();
+        let bin = data[24 + json_len..24 + json_len + bin_len].to_vec();
+        (json, bin)
+    }
+
+    /// Tiles containing Tokyo, whose z=2 tile (3, 0) lies between the equator and 45°N
+    fn tokyo(z: u8, h: u32) -> TileZXYH {
+        let (z, x, y) = Geographic.zxy_from_lng_lat(z, 139.7, 35.7);
+        (z, x, y, h)
+    }
+
+    #[test]
+    fn content_path_is_relative_to_implicit_root() {
+        assert_eq!(tokyo(2, 0), (2, 3, 0, 0));
+        assert_eq!(tokyo(3, 0), (3, 7, 1, 0));
+
+        let implicit = ImplicitTiling::new(Subdivision::Quadtree, 4);
+        // z=3 tile (7, 1) is the child (1, 1) of the z=2 tile (3, 0)
+        assert_eq!(implicit.content_path(tokyo(3, 0)), "2/3/0/1/1/1.glb");
+        assert_eq!(implicit.content_path(tokyo(2, 0)), "2/3/0/0/0/0.glb");
+
+        let implicit = ImplicitTiling::new(Subdivision::Octree, 4);
+        assert_eq!(implicit.content_path(tokyo(3, 1)), "2/3/0/0/1/1/1/1.glb");
+    }
+
+    #[test]
+    fn tiles_merged_along_longitude_are_refused() {
+        // z=3 rows 0 and 3 lie beyond ±45° and are two tiles wide
+        let arctic = Geographic.zxy_from_lng_lat(3, -170.0, 80.0);
+        let antarctic = Geographic.zxy_from_lng_lat(3, 100.0, -60.0);
+        assert_eq!((arctic, antarctic), ((3, 0, 0), (3, 6, 3)));
+        assert!(check_implicit_tile(arctic).is_err());
+        assert!(check_implicit_tile(antarctic).is_err());
+        let (z, x, y, _) = tokyo(3, 0);
+        assert!(check_implicit_tile((z, x, y)).is_ok());
+        // Tokyo and Sapporo (43°N) are fine, Oslo (60°N) is not
+        for ((lng, lat), ok) in [
+            ((139.7, 35.7), true),
+            ((141.35, 43.06), true),
+            ((10.75, 59.9), false),
+        ] {
+            let tile = Geographic.zxy_from_lng_lat(18, lng, lat);
+            assert_eq!(check_implicit_tile(tile).is_ok(), ok);
+        }
+        // The z=2 roots themselves are never merged
+        assert!(check_implicit_tile(Geographic.zxy_from_lng_lat(2, -170.0, 80.0)).is_ok());
+    }
+
+    #[test]
+    fn morton_index_interleaves_bits() {
+        let quadtree = ImplicitTiling::new(Subdivision::Quadtree, 4);
+        assert_eq!(quadtree.morton_index(1, 0, 0), 1);
+        assert_eq!(quadtree.morton_index(0, 1, 0), 2);
+        assert_eq!(quadtree.morton_index(3, 1, 0), 7);
+        assert_eq!(quadtree.morton_index(2, 2, 0), 12);
+        assert_eq!(quadtree.availability_index((2, 1, 0, 0)), 5 + 1);
+
+        // The z=5 tile over Tokyo is (4, 4) at level 3 below its root
+        let (root, coord) = split_tile(tokyo(5, 0));
+        assert_eq!(root, tokyo(2, 0));
+        assert_eq!(coord, (3, 4, 4, 0));
+        assert_eq!(quadtree.morton_index(4, 4, 0), 0b11_0000);
+        assert_eq!(quadtree.availability_index(coord), 21 + 48);
+
+        let octree = ImplicitTiling::new(Subdivision::Octree, 4);
+        assert_eq!(octree.morton_index(0, 0, 1), 4);
+        assert_eq!(octree.morton_index(1, 1, 1), 7);
+        assert_eq!(octree.availability_index((1, 0, 0, 1)), 1 + 4);
+    }
+
+    #[test]
+    fn build_writes_availability_for_each_subtree() {
+        let implicit = ImplicitTiling::new(Subdivision::Quadtree, 2);
+        // A single branch from z=3 down to z=5 under the z=2 tile (3, 0)
+        let contents = (3..=5).map(|z| content(tokyo(z, 0))).collect();
+        let (root, subtrees) = implicit.build(contents);
+
+        let children = root.children.unwrap();
+        assert_eq!(children.len(), 1);
+        let implicit_root = &children[0];
+        let implicit_tiling = implicit_root.implicit_tiling.as_ref().unwrap();
+        assert_eq!(implicit_tiling.available_levels, 4);
+        assert_eq!(implicit_tiling.subtree_levels, 2);
+        assert_eq!(
+            implicit_root.content.as_ref().unwrap().uri,
+            "2/3/0/{level}/{x}/{y}.glb"
+        );
+        assert_eq!(
+            implicit_tiling.subtrees.uri,
+            "2/3/0/subtrees/{level}/{x}/{y}.subtree"
+        );
+
+        let mut paths = subtrees.iter().map(|s| s.path.as_str()).collect::<Vec<_>>();
+        paths.sort();
+        // The z=4 tile (14, 2) is (2, 2) at level 2 and roots the second subtree
+        assert_eq!(tokyo(4, 0), (4, 14, 2, 0));
+        assert_eq!(
+            paths,
+            vec![
+                "2/3/0/subtrees/0/0/0.subtree",
+                "2/3/0/subtrees/2/2/2.subtree"
+            ]
+        );
+
+        let top = subtrees
+            .iter()
+            .find(|s| s.path.ends_with("0/0/0.subtree"))
+            .unwrap();
+        let (json, bin) = read_subtree(&top.data);
+        // Root tile (no content) and level 1 tile (1, 1)
+        assert_eq!(json["tileAvailability"]["availableCount"], 2);
+        assert_eq!(json["contentAvailability"][0]["availableCount"], 1);
+        assert_eq!(json["childSubtreeAvailability"]["availableCount"], 1);
+        let view = json["bufferViews"][0]["byteOffset"].as_u64().unwrap() as usize;
+        assert_eq!(bin[view], 0b0001_0001);
+        // Level 2 tile (2, 2) is child subtree 12 in Morton order
+        let view = json["bufferViews"][2]["byteOffset"].as_u64().unwrap() as usize;
+        assert_eq!(bin[view..view + 2], [0, 0b0001_0000]);
+
+        let bottom = subtrees
+            .iter()
+            .find(|s| s.path.ends_with("2/2/2.subtree"))
+            .unwrap();
+        let (json, _) = read_subtree(&bottom.data);
+        assert_eq!(json["tileAvailability"]["availableCount"], 2);
+        assert_eq!(json["contentAvailability"][0]["availableCount"], 2);
+        assert_eq!(json["childSubtreeAvailability"]["constant"], 0);
+    }
+
+    #[test]
+    fn octree_root_covers_the_height_cell() {
+        let implicit = ImplicitTiling::new(Subdivision::Octree, 4);
+        let (root, _) = implicit.build(vec![content(tokyo(3, 0))]);
+        let implicit_root = &root.children.unwrap()[0];
+        let region = implicit_root.bounding_volume.region.unwrap();
+        assert_eq!((region[4], region[5]), h_slice_range(2, 0));
+    }
+}
@@ -0,0 +1,268 @@
+use std::collections::HashMap;
+
+use cesiumtiles::gltf_extensions::gltf::ext_structural_metadata::{
+    Class, ClassProperty, ClassPropertyComponentType, ClassPropertyType, Schema,
+};
+use cesiumtiles::tileset::{GroupMetadata, MetadataEntity};
+use serde_json::{Value, json};
+
+use super::classification::ClassificationGroup;
+
+/// ID of the metadata schema embedded in the tilesets
+pub const METADATA_SCHEMA_ID: &str = "ptiler";
+
+/// Class of the tileset-level metadata
+pub const TILESET_CLASS: &str = "tileset";
+
+/// Class of the per-tile metadata
+pub const TILE_CLASS: &str = "tile";
+
+/// Class of the content group metadata
+pub const CONTENT_GROUP_CLASS: &str = "contentGroup";
+
+fn scalar(component_type: ClassPropertyComponentType, description: &str) -> ClassProperty {
+    ClassProperty {
+        description: Some(description.to_string()),
+        type_: ClassPropertyType::Scalar,
+        component_type: Some(component_type),
+        ..Default::default()
+    }
+}
+
+fn string(description: &str) -> ClassProperty {
+    ClassProperty {
+        description: Some(description.to_string()),
+        type_: ClassPropertyType::String,
+        ..Default::default()
+    }
+}
+
+/// 3D Tiles 1.1 metadata schema declaring the [`TILESET_CLASS`], [`TILE_CLASS`] and
+/// [`CONTENT_GROUP_CLASS`] classes
+pub fn metadata_schema() -> Schema {
+    use ClassPropertyComponentType::{Float64, Uint8, Uint64};
+
+    let tileset = Class {
+        name: Some("Tileset".to_string()),
+        properties: [
+            ("pointCount", scalar(Uint64, "Number of input points")),
+            (
+                "sourceFiles",
+                ClassProperty {
+                    array: true,
+                    ..string("Names of the input files")
+                },
+            ),
+            (
+                "inputCrs",
+                string("Coordinate reference system of the input"),
+            ),
+            (
+                "generator",
+                string("Name and version of the generating tool"),
+            ),
+            ("createdAt", string("Creation time (RFC 3339)")),
+        ]
+        .into_iter()
+        .map(|(id, property)| (id.to_string(), property))
+        .collect(),
+        ..Default::default()
+    };
+    let tile = Class {
+        name: Some("Tile".to_string()),
+        properties: [
+            (
+                "pointCount",
+                scalar(Uint64, "Number of points in the tile contents"),
+            ),
+            (
+                "minimumHeight",
+                ClassProperty {
+                    semantic: Some("TILE_MINIMUM_HEIGHT".to_string()),
+                    ..scalar(Float64, "Minimum height (metres) of the tile")
+                },
+            ),
+            (
+                "maximumHeight",
+                ClassProperty {
+                    semantic: Some("TILE_MAXIMUM_HEIGHT".to_string()),
+                    ..scalar(Float64, "Maximum height (metres) of the tile")
+                },
+            ),
+            (
+                "pointSpacing",
+                scalar(Float64, "Average horizontal point spacing (metres)"),
+            ),
+        ]
+        .into_iter()
+        .map(|(id, property)| (id.to_string(), property))
+        .collect(),
+        ..Default::default()
+    };
+    let content_group = Class {
+        name: Some("Content group".to_string()),
+        properties: [
+            ("name", string("Name of the layer")),
+            (
+                "classes",
+                ClassProperty {
+                    array: true,
+                    ..scalar(
+                        Uint8,
+                        "ASPRS classes of the layer; empty for all other classes",
+                    )
+                },
+            ),
+        ]
+        .into_iter()
+        .map(|(id, property)| (id.to_string(), property))
+        .collect(),
+        ..Default::default()
+    };
+
+    Schema {
+        id: METADATA_SCHEMA_ID.to_string(),
+        classes: HashMap::from([
+            (TILESET_CLASS.to_string(), tileset),
+            (TILE_CLASS.to_string(), tile),
+            (CONTENT_GROUP_CLASS.to_string(), content_group),
+        ]),
+        ..Default::default()
+    }
+}
+
+/// Values of the [`TILESET_CLASS`] metadata
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TilesetMetadata {
+    pub point_count: u64,
+    pub source_files: Vec<String>,
+    pub input_crs: String,
+    pub generator: String,
+    pub created_at: String,
+}
+
+impl TilesetMetadata {
+    pub fn to_entity(&self) -> MetadataEntity {
+        entity(
+            TILESET_CLASS,
+            [
+                ("pointCount", json!(self.point_count)),
+                ("sourceFiles", json!(self.source_files)),
+                ("inputCrs", json!(self.input_crs)),
+                ("generator", json!(self.generator)),
+                ("createdAt", json!(self.created_at)),
+            ],
+        )
+    }
+}
+
+/// [`TILE_CLASS`] metadata; the point spacing is omitted when unknown
+pub(crate) fn tile_metadata(
+    point_count: u64,
+    min_height: f64,
+    max_height: f64,
+    point_spacing: Option<f64>,
+) -> MetadataEntity {
+    let mut metadata = entity(
+        TILE_CLASS,
+        [
+            ("pointCount", json!(point_count)),
+            ("minimumHeight", json!(min_height)),
+            ("maximumHeight", json!(max_height)),
+        ],
+    );
+    if let (Some(properties), Some(spacing)) = (&mut metadata.properties, point_spacing) {
+        properties.insert("pointSpacing".to_string(), json!(spacing));
+    }
+    metadata
+}
+
+/// Tileset `groups` for contents split by [`ClassificationGroup`], indexed by
+/// [`ClassificationGroup::index`]
+pub fn classification_groups() -> Vec<GroupMetadata> {
+    ClassificationGroup::ALL
+        .into_iter()
+        .map(|group| {
+            let entity = entity(
+                CONTENT_GROUP_CLASS,
+                [
+                    ("name", json!(group.name())),
+                    ("classes", json!(group.classes())),
+                ],
+            );
+            GroupMetadata {
+                class_: entity.class_,
+                properties: entity.properties,
+                extensions: None,
+                extra: None,
+            }
+        })
+        .collect()
+}
+
+fn entity<const N: usize>(class: &str, properties: [(&str, Value); N]) -> MetadataEntity {
+    MetadataEntity {
+        class_: class.to_string(),
+        properties: Some(
+            properties
+                .into_iter()
+                .map(|(id, value)| (id.to_string(), value))
+                .collect(),
+        ),
+        extensions: None,
+        extra: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entities_only_use_schema_properties() {
+        let schema = metadata_schema();
+        let tileset = TilesetMetadata {
+            point_count: 3,
+            source_files: vec!["a.las".to_string()],
+            ..Default::default()
+        }
+        .to_entity();
+        let tile = tile_metadata(3, 1.0, 2.0, Some(0.5));
+        let groups = classification_groups()
+            .into_iter()
+            .map(|group| MetadataEntity {
+                class_: group.class_,
+                properties: group.properties,
+                extensions: None,
+                extra: None,
+            });
+
+        for entity in [tileset, tile].into_iter().chain(groups) {
+            let class = &schema.classes[&entity.class_];
+            let properties = entity.properties.unwrap();
+            assert_eq!(properties.len(), class.properties.len());
+            for id in properties.keys() {
+                assert!(class.properties.contains_key(id), "{id} is not declared");
+            }
+        }
+        assert!(
+            !tile_metadata(3, 1.0, 2.0, None)
+                .properties
+                .unwrap()
+                .contains_key("pointSpacing")
+        );
+    }
+
+    #[test]
+    fn schema_serializes_as_3d_tiles_metadata() {
+        let schema = serde_json::to_value(metadata_schema()).unwrap();
+        let property = &schema["classes"]["tile"]["properties"]["minimumHeight"];
+        assert_eq!(property["type"], "SCALAR");
+        assert_eq!(property["componentType"], "FLOAT64");
+        assert_eq!(property["semantic"], "TILE_MINIMUM_HEIGHT");
+        assert_eq!(
+            schema["classes"]["tileset"]["properties"]["sourceFiles"]["array"],
+            true
+        );
+    }
+}
@@ -1,5 +1,14 @@
+pub mod adaptive;
+pub mod classification;
+pub mod error_model;
+pub mod implicit;
+pub mod metadata;
+pub mod projected;
+pub mod s2;
 pub mod scheme;
 pub mod tree;
+pub mod volume;
+pub mod web_mercator;
 
 pub use scheme::*;
 pub use tree::*;
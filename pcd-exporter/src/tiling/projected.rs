@@ -0,0 +1,256 @@
+use tinymvt::TileZXY;
+
+use super::scheme::{GEOMETRIC_ERROR_PER_METRE, TilingScheme};
+
+/// GRS80 semi-major axis (metres)
+const GRS80_A: f64 = 6_378_137.0;
+
+/// GRS80 flattening
+const GRS80_F: f64 = 1.0 / 298.257_222_101;
+
+/// Transverse Mercator projection on the GRS80 ellipsoid (Krüger series to the third order)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TransverseMercator {
+    pub origin_lng: f64,
+    pub origin_lat: f64,
+    pub scale_factor: f64,
+}
+
+impl TransverseMercator {
+    /// Projects a point to (easting, northing) in metres from the origin
+    pub fn forward(&self, lng: f64, lat: f64) -> (f64, f64) {
+        let series = Series::new();
+        let (xi, eta) = series.conformal(lng - self.origin_lng, lat);
+        let northing = series.a * (xi + sum_sin_cosh(&series.alpha, xi, eta));
+        let easting = series.a * (eta + sum_cos_sinh(&series.alpha, xi, eta));
+        (
+            self.scale_factor * easting,
+            self.scale_factor * (northing - self.meridian_arc(&series)),
+        )
+    }
+
+    /// Inverse of [`Self::forward`], returning (longitude, latitude) in degrees
+    pub fn inverse(&self, easting: f64, northing: f64) -> (f64, f64) {
+        let series = Series::new();
+        let xi = (northing / self.scale_factor + self.meridian_arc(&series)) / series.a;
+        let eta = easting / self.scale_factor / series.a;
+        let xi_p = xi - sum_sin_cosh(&series.beta, xi, eta);
+        let eta_p = eta - sum_cos_sinh(&series.beta, xi, eta);
+        let chi = (xi_p.sin() / eta_p.cosh()).asin();
+        let lat = chi
+            + (1..=3)
+                .map(|j| series.delta[j - 1] * (2.0 * j as f64 * chi).sin())
+                .sum::<f64>();
+        let lng = eta_p.sinh().atan2(xi_p.cos());
+        (self.origin_lng + lng.to_degrees(), lat.to_degrees())
+    }
+
+    /// Northing of the origin latitude along the central meridian, before scaling
+    fn meridian_arc(&self, series: &Series) -> f64 {
+        let (xi, eta) = series.conformal(0.0, self.origin_lat);
+        series.a * (xi + sum_sin_cosh(&series.alpha, xi, eta))
+    }
+}
+
+struct Series {
+    a: f64,
+    e: f64,
+    alpha: [f64; 3],
+    beta: [f64; 3],
+    delta: [f64; 3],
+}
+
+impl Series {
+    fn new() -> Self {
+        let n = GRS80_F / (2.0 - GRS80_F);
+        let (n2, n3) = (n * n, n * n * n);
+        Self {
+            a: GRS80_A / (1.0 + n) * (1.0 + n2 / 4.0 + n2 * n2 / 64.0),
+            e: (GRS80_F * (2.0 - GRS80_F)).sqrt(),
+            alpha: [
+                n / 2.0 - 2.0 * n2 / 3.0 + 5.0 * n3 / 16.0,
+                13.0 * n2 / 48.0 - 3.0 * n3 / 5.0,
+                61.0 * n3 / 240.0,
+            ],
+            beta: [
+                n / 2.0 - 2.0 * n2 / 3.0 + 37.0 * n3 / 96.0,
+                n2 / 48.0 + n3 / 15.0,
+                17.0 * n3 / 480.0,
+            ],
+            delta: [
+                2.0 * n - 2.0 * n2 / 3.0 - 2.0 * n3,
+                7.0 * n2 / 3.0 - 8.0 * n3 / 5.0,
+                56.0 * n3 / 15.0,
+            ],
+        }
+    }
+
+    /// Conformal coordinates (ξ', η') of a point `d_lng` degrees from the central meridian
+    fn conformal(&self, d_lng: f64, lat: f64) -> (f64, f64) {
+        let sin_lat = lat.to_radians().sin();
+        let t = (sin_lat.atanh() - self.e * (self.e * sin_lat).atanh()).sinh();
+        let (sin_lng, cos_lng) = d_lng.to_radians().sin_cos();
+        (t.atan2(cos_lng), (sin_lng / (1.0 + t * t).sqrt()).atanh())
+    }
+}
+
+fn sum_sin_cosh(coefficients: &[f64; 3], xi: f64, eta: f64) -> f64 {
+    (1..=3)
+        .map(|j| {
+            let k = 2.0 * j as f64;
+            coefficients[j - 1] * (k * xi).sin() * (k * eta).cosh()
+        })
+        .sum()
+}
+
+fn sum_cos_sinh(coefficients: &[f64; 3], xi: f64, eta: f64) -> f64 {
+    (1..=3)
+        .map(|j| {
+            let k = 2.0 * j as f64;
+            coefficients[j - 1] * (k * xi).cos() * (k * eta).sinh()
+        })
+        .sum()
+}
+
+/// Origins (latitude, longitude in degrees) of the 19 zones of the Japan Plane Rectangular
+/// Coordinate System
+const JAPAN_PLANE_RECTANGULAR_ORIGINS: [(f64, f64); 19] = [
+    (33.0, 129.5),
+    (33.0, 131.0),
+    (36.0, 132.0 + 10.0 / 60.0),
+    (33.0, 133.5),
+    (36.0, 134.0 + 20.0 / 60.0),
+    (36.0, 136.0),
+    (36.0, 137.0 + 10.0 / 60.0),
+    (36.0, 138.5),
+    (36.0, 139.0 + 50.0 / 60.0),
+    (40.0, 140.0 + 50.0 / 60.0),
+    (44.0, 140.25),
+    (44.0, 142.25),
+    (44.0, 144.25),
+    (26.0, 142.0),
+    (26.0, 127.5),
+    (26.0, 124.0),
+    (26.0, 131.0),
+    (20.0, 136.0),
+    (26.0, 154.0),
+];
+
+/// Square-ish tiles on a projected plane: the z=0 tile has its north-west corner at `origin`
+/// and every level splits tiles into 2x2 children. Points outside the z=0 tile are clamped to
+/// the edge tiles.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProjectedGrid {
+    pub projection: TransverseMercator,
+    /// North-west corner of the grid as (easting, northing) in metres
+    pub origin: [f64; 2],
+    /// Size of the z=0 tile as (width, height) in metres
+    pub root_size: [f64; 2],
+}
+
+impl ProjectedGrid {
+    /// Grid aligned with the 1:5000 map sheets (図郭) of the Japan Plane Rectangular
+    /// Coordinate System. Sheets are 4 km wide and 3 km tall and fall on z=8, so z=9 tiles
+    /// match the 1:2500 sheets.
+    ///
+    /// Accepts the zone EPSG codes of JGD2011 (6669-6687) and JGD2000 (2443-2461).
+    pub fn japan_map_sheets(epsg: u16) -> Option<Self> {
+        let zone = match epsg {
+            6669..=6687 => epsg - 6669,
+            2443..=2461 => epsg - 2443,
+            _ => return None,
+        };
+        let (origin_lat, origin_lng) = JAPAN_PLANE_RECTANGULAR_ORIGINS[zone as usize];
+        Some(Self {
+            projection: TransverseMercator {
+                origin_lng,
+                origin_lat,
+                scale_factor: 0.9999,
+            },
+            // Map sheets cover 300 km north and south and 160 km east and west of the origin
+            origin: [-160_000.0, 300_000.0],
+            root_size: [4_000.0 * 256.0, 3_000.0 * 256.0],
+        })
+    }
+
+    fn tile_size(&self, z: u8) -> [f64; 2] {
+        let n = (1u64 << z) as f64;
+        [self.root_size[0] / n, self.root_size[1] / n]
+    }
+}
+
+impl TilingScheme for ProjectedGrid {
+    fn zxy_from_lng_lat(&self, z: u8, lng: f64, lat: f64) -> TileZXY {
+        let (easting, northing) = self.projection.forward(lng, lat);
+        let [width, height] = self.tile_size(z);
+        let max_index = ((1u64 << z) - 1) as f64;
+        let x = ((easting - self.origin[0]) / width).floor();
+        let y = ((self.origin[1] - northing) / height).floor();
+        (
+            z,
+            x.clamp(0.0, max_index) as u32,
+            y.clamp(0.0, max_index) as u32,
+        )
+    }
+
+    fn parent_zxy(&self, z: u8, x: u32, y: u32) -> TileZXY {
+        assert!(z > 0, "z=0 has no parent");
+        (z - 1, x / 2, y / 2)
+    }
+
+    fn bounds(&self, z: u8, x: u32, y: u32) -> (f64, f64, f64, f64) {
+        let [width, height] = self.tile_size(z);
+        let west = self.origin[0] + width * x as f64;
+        let north = self.origin[1] - height * y as f64;
+        // Grid lines are not parallel to meridians and parallels, so check every corner
+        let corners = [
+            (west, north),
+            (west + width, north),
+            (west, north - height),
+            (west + width, north - height),
+        ]
+        .map(|(easting, northing)| self.projection.inverse(easting, northing));
+        corners.iter().fold(
+            (f64::MAX, f64::MAX, f64::MIN, f64::MIN),
+            |(west, south, east, north), &(lng, lat)| {
+                (west.min(lng), south.min(lat), east.max(lng), north.max(lat))
+            },
+        )
+    }
+
+    fn geometric_error(&self, z: u8, _x: u32, _y: u32) -> f64 {
+        let [width, height] = self.tile_size(z);
+        width.max(height) * GEOMETRIC_ERROR_PER_METRE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transverse_mercator_round_trips() {
+        let grid = ProjectedGrid::japan_map_sheets(6677).unwrap();
+        let (easting, northing) = grid.projection.forward(139.7671, 35.6812);
+        // Tokyo Station in JGD2011 / Japan Plane Rectangular CS IX
+        assert!((easting - -5_995.185).abs() < 0.001);
+        assert!((northing - -35_367.230).abs() < 0.001);
+        let (lng, lat) = grid.projection.inverse(easting, northing);
+        assert!((lng - 139.7671).abs() < 1e-8);
+        assert!((lat - 35.6812).abs() < 1e-8);
+    }
+
+    #[test]
+    fn map_sheets_fall_on_zoom_8() {
+        let grid = ProjectedGrid::japan_map_sheets(6677).unwrap();
+        assert_eq!(grid.tile_size(8), [4_000.0, 3_000.0]);
+        // 38 sheets east of the west edge, 111 sheets south of the north edge
+        assert_eq!(grid.zxy_from_lng_lat(8, 139.7671, 35.6812), (8, 38, 111));
+        assert_eq!(grid.parent_zxy(8, 38, 111), (7, 19, 55));
+
+        let (west, south, east, north) = grid.bounds(8, 38, 111);
+        assert!(west <= 139.7671 && 139.7671 <= east);
+        assert!(south <= 35.6812 && 35.6812 <= north);
+        assert!(ProjectedGrid::japan_map_sheets(4326).is_none());
+    }
+}
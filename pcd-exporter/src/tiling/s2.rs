@@ -0,0 +1,272 @@
+use std::collections::HashMap;
+
+use serde_json::json;
+use tinymvt::TileZXY;
+
+use super::scheme::{TilingScheme, Z2_GEOMETRIC_ERROR};
+
+/// Name of the 3D Tiles extension carrying S2 bounding volumes
+pub const S2_EXTENSION: &str = "3DTILES_bounding_volume_S2";
+
+/// Zoom level of the six S2 face cells
+pub const S2_FACE_ZOOM: u8 = 2;
+
+/// Deepest S2 cell level
+const S2_MAX_LEVEL: u8 = 30;
+
+/// Hilbert curve position of each (i, j) child for the four curve orientations
+const IJ_TO_POS: [[u64; 4]; 4] = [[0, 1, 3, 2], [0, 3, 1, 2], [2, 3, 1, 0], [2, 1, 3, 0]];
+
+/// Orientation change after descending into the child at each curve position
+const POS_TO_ORIENTATION: [usize; 4] = [1, 0, 0, 3];
+
+/// S2 cells: zoom level `z` holds the S2 cells of level `z - 2`, so that cells are about as
+/// large as geographic tiles at the same zoom. The six faces are laid out as a 3x2 grid, with
+/// `x = (face % 3) * 2^level + i` and `y = (face / 3) * 2^level + j`.
+///
+/// z=1 holds two tiles grouping faces 0-2 and 3-5, and z=0 is the whole globe.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct S2;
+
+impl S2 {
+    /// S2 cell (face, level, i, j) of a tile at z >= [`S2_FACE_ZOOM`]
+    fn cell(z: u8, x: u32, y: u32) -> (u8, u8, u32, u32) {
+        let level = z - S2_FACE_ZOOM;
+        let face = (x >> level) + 3 * (y >> level);
+        let mask = (1u32 << level) - 1;
+        (face as u8, level, x & mask, y & mask)
+    }
+
+    /// 64-bit S2 cell id of a tile at z >= [`S2_FACE_ZOOM`]
+    pub fn cell_id(z: u8, x: u32, y: u32) -> u64 {
+        let (face, level, i, j) = Self::cell(z, x, y);
+        let mut orientation = (face & 1) as usize;
+        let mut pos = 0u64;
+        for k in (0..level).rev() {
+            let ij = (((i >> k) & 1) << 1 | ((j >> k) & 1)) as usize;
+            let child_pos = IJ_TO_POS[orientation][ij];
+            pos = (pos << 2) | child_pos;
+            orientation ^= POS_TO_ORIENTATION[child_pos as usize];
+        }
+        let level = level as u32;
+        ((face as u64) << 61) | (pos << (61 - 2 * level)) | (1 << (60 - 2 * level))
+    }
+
+    /// Cell id as an S2 token: hexadecimal without trailing zeros
+    pub fn token(z: u8, x: u32, y: u32) -> String {
+        let id = Self::cell_id(z, x, y);
+        let hex = format!("{id:016x}");
+        hex.trim_end_matches('0').to_string()
+    }
+}
+
+impl TilingScheme for S2 {
+    fn zxy_from_lng_lat(&self, z: u8, lng: f64, lat: f64) -> TileZXY {
+        let (face, s, t) = face_st_from_lng_lat(lng, lat);
+        match z {
+            0 => (0, 0, 0),
+            1 => (1, 0, face as u32 / 3),
+            _ => {
+                let level = (z - S2_FACE_ZOOM).min(S2_MAX_LEVEL);
+                let n = (1u64 << level) as f64;
+                let i = (s * n).floor().clamp(0.0, n - 1.0) as u32;
+                let j = (t * n).floor().clamp(0.0, n - 1.0) as u32;
+                let face = face as u32;
+                (z, ((face % 3) << level) + i, ((face / 3) << level) + j)
+            }
+        }
+    }
+
+    fn parent_zxy(&self, z: u8, x: u32, y: u32) -> TileZXY {
+        match z {
+            0 => panic!("z=0 has no parent"),
+            1 => (0, 0, 0),
+            2 => (1, 0, y),
+            _ => (z - 1, x / 2, y / 2),
+        }
+    }
+
+    fn child_slot(&self, z: u8, x: u32, y: u32) -> u32 {
+        match z {
+            // The three faces of a z=1 tile
+            S2_FACE_ZOOM => x,
+            _ => (x % 2) | ((y % 2) << 1),
+        }
+    }
+
+    fn bounds(&self, z: u8, x: u32, y: u32) -> (f64, f64, f64, f64) {
+        if z < S2_FACE_ZOOM {
+            return (-180.0, -90.0, 180.0, 90.0);
+        }
+        let (face, level, i, j) = Self::cell(z, x, y);
+        let n = (1u64 << level) as f64;
+
+        // Cell edges are not parallel to meridians and parallels, so sample along them
+        const STEPS: u32 = 8;
+        let mut lngs = Vec::new();
+        let (mut south, mut north) = (f64::MAX, f64::MIN);
+        for k in 0..=STEPS {
+            let f = k as f64 / STEPS as f64;
+            for (s, t) in [(f, 0.0), (f, 1.0), (0.0, f), (1.0, f)] {
+                let (lng, lat) = lng_lat_from_face_st(face, (i as f64 + s) / n, (j as f64 + t) / n);
+                lngs.push(lng);
+                south = south.min(lat);
+                north = north.max(lat);
+            }
+        }
+
+        // Cells around the poles contain every longitude
+        let (pole_s, pole_t) = (0.5 * n, 0.5 * n);
+        let contains_pole = (face == 2 || face == 5)
+            && (i as f64..=i as f64 + 1.0).contains(&pole_s)
+            && (j as f64..=j as f64 + 1.0).contains(&pole_t);
+        if contains_pole {
+            return match face {
+                2 => (-180.0, south, 180.0, 90.0),
+                _ => (-180.0, -90.0, 180.0, north),
+            };
+        }
+
+        let west = lngs.iter().copied().fold(f64::MAX, f64::min);
+        let east = lngs.iter().copied().fold(f64::MIN, f64::max);
+        if east - west > 180.0 {
+            // Crosses the antimeridian
+            return (-180.0, south, 180.0, north);
+        }
+        (west, south, east, north)
+    }
+
+    fn geometric_error(&self, z: u8, _x: u32, _y: u32) -> f64 {
+        if z < S2_FACE_ZOOM {
+            return 1e+100;
+        }
+        Z2_GEOMETRIC_ERROR / (1u64 << (z - S2_FACE_ZOOM)) as f64
+    }
+
+    fn bounding_volume_extensions(
+        &self,
+        zxy: TileZXY,
+        min_height: f64,
+        max_height: f64,
+    ) -> Option<HashMap<String, serde_json::Value>> {
+        let (z, x, y) = zxy;
+        (z >= S2_FACE_ZOOM).then(|| {
+            HashMap::from([(
+                S2_EXTENSION.to_string(),
+                json!({
+                    "token": Self::token(z, x, y),
+                    "minimumHeight": min_height,
+                    "maximumHeight": max_height,
+                }),
+            )])
+        })
+    }
+
+    fn extensions_used(&self) -> Vec<String> {
+        vec![S2_EXTENSION.to_string()]
+    }
+}
+
+/// Cube face and quadratic (s, t) coordinates in [0, 1] of a point (degrees)
+fn face_st_from_lng_lat(lng: f64, lat: f64) -> (u8, f64, f64) {
+    let (sin_lat, cos_lat) = lat.to_radians().sin_cos();
+    let (sin_lng, cos_lng) = lng.to_radians().sin_cos();
+    let p = [cos_lat * cos_lng, cos_lat * sin_lng, sin_lat];
+
+    let axis = (0..3)
+        .max_by(|&a, &b| p[a].abs().total_cmp(&p[b].abs()))
+        .unwrap();
+    let face = if p[axis] < 0.0 { axis + 3 } else { axis };
+    let (u, v) = match face {
+        0 => (p[1] / p[0], p[2] / p[0]),
+        1 => (-p[0] / p[1], p[2] / p[1]),
+        2 => (-p[0] / p[2], -p[1] / p[2]),
+        3 => (p[2] / p[0], p[1] / p[0]),
+        4 => (p[2] / p[1], -p[0] / p[1]),
+        _ => (-p[1] / p[2], -p[0] / p[2]),
+    };
+    (face as u8, st_from_uv(u), st_from_uv(v))
+}
+
+fn lng_lat_from_face_st(face: u8, s: f64, t: f64) -> (f64, f64) {
+    let (u, v) = (uv_from_st(s), uv_from_st(t));
+    let p = match face {
+        0 => [1.0, u, v],
+        1 => [-u, 1.0, v],
+        2 => [-u, -v, 1.0],
+        3 => [-1.0, -v, -u],
+        4 => [v, -1.0, -u],
+        _ => [v, u, -1.0],
+    };
+    let lng = p[1].atan2(p[0]).to_degrees();
+    let lat = p[2].atan2(p[0].hypot(p[1])).to_degrees();
+    (lng, lat)
+}
+
+fn st_from_uv(u: f64) -> f64 {
+    if u >= 0.0 {
+        0.5 * (1.0 + 3.0 * u).sqrt()
+    } else {
+        1.0 - 0.5 * (1.0 - 3.0 * u).sqrt()
+    }
+}
+
+fn uv_from_st(s: f64) -> f64 {
+    if s >= 0.5 {
+        (4.0 * s * s - 1.0) / 3.0
+    } else {
+        (1.0 - 4.0 * (1.0 - s) * (1.0 - s)) / 3.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn face_cells_have_s2_tokens() {
+        // Face cells are "1", "3", "5", "7", "9" and "b"
+        let tokens = [(0, 0), (1, 0), (2, 0), (0, 1), (1, 1), (2, 1)]
+            .map(|(x, y)| S2::token(S2_FACE_ZOOM, x, y));
+        assert_eq!(tokens, ["1", "3", "5", "7", "9", "b"]);
+    }
+
+    #[test]
+    fn children_follow_the_hilbert_curve() {
+        // Children of face 0 in curve order, and of face 1 whose curve starts swapped
+        let children = [(0, 0), (0, 1), (1, 1), (1, 0)].map(|(x, y)| S2::token(3, x, y));
+        assert_eq!(children, ["04", "0c", "14", "1c"]);
+        let children = [(2, 0), (3, 0), (3, 1), (2, 1)].map(|(x, y)| S2::token(3, x, y));
+        assert_eq!(children, ["24", "2c", "34", "3c"]);
+    }
+
+    #[test]
+    fn points_fall_into_their_cell() {
+        // Tokyo Station is on face 3, the -X face
+        let (z, x, y) = S2.zxy_from_lng_lat(8, 139.7671, 35.6812);
+        assert_eq!(S2::cell(z, x, y).0, 3);
+        let (west, south, east, north) = S2.bounds(z, x, y);
+        assert!(west <= 139.7671 && 139.7671 <= east);
+        assert!(south <= 35.6812 && 35.6812 <= north);
+        assert_eq!(S2.zxy_from_lng_lat(1, 139.7671, 35.6812), (1, 0, 1));
+
+        // Parent cell ids clear the last two position bits, as in the S2 library
+        let (mut z, mut x, mut y) = (z, x, y);
+        while z > S2_FACE_ZOOM {
+            let id = S2::cell_id(z, x, y);
+            let parent_lsb = (id & id.wrapping_neg()) << 2;
+            (z, x, y) = S2.parent_zxy(z, x, y);
+            assert_eq!(
+                S2::cell_id(z, x, y),
+                (id & parent_lsb.wrapping_neg()) | parent_lsb
+            );
+        }
+    }
+
+    #[test]
+    fn polar_cells_cover_every_longitude() {
+        let (z, x, y) = S2.zxy_from_lng_lat(6, 0.0, 89.9);
+        let (west, _, east, north) = S2.bounds(z, x, y);
+        assert_eq!((west, east, north), (-180.0, 180.0, 90.0));
+    }
+}
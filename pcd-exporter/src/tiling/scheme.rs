@@ -1,4 +1,150 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
 use std::ops::Range;
+use std::str::FromStr;
+
+use tinymvt::TileZXY;
+
+/// Tile coordinates with the vertical index used by octree subdivision.
+/// The vertical index is always 0 for quadtree tiles.
+pub type TileZXYH = (u8, u32, u32, u32);
+
+/// Bottom of the vertical range split by octree subdivision (metres)
+pub const OCTREE_MIN_HEIGHT: f64 = -10_000.0;
+
+/// Equatorial circumference of the WGS84 ellipsoid (metres)
+pub(crate) const EARTH_CIRCUMFERENCE: f64 = 40_075_016.685_578_5;
+
+/// Height of the z=0 octree cell. It is halved at every zoom level so that cells stay
+/// roughly as tall as the geographic tiles at the same zoom are wide.
+const OCTREE_ROOT_HEIGHT: f64 = EARTH_CIRCUMFERENCE;
+
+/// Geometric error of a z=2 geographic tile, which is a quarter of the equator wide
+pub(crate) const Z2_GEOMETRIC_ERROR: f64 = 525957.5361033019;
+
+/// Geometric error per metre of tile width, so that all schemes refine alike
+pub(crate) const GEOMETRIC_ERROR_PER_METRE: f64 = Z2_GEOMETRIC_ERROR / (EARTH_CIRCUMFERENCE / 4.0);
+
+/// A grid of tiles addressed by zoom level and x/y indices, where every tile below z=0 has
+/// exactly one parent.
+pub trait TilingScheme: Debug + Send + Sync {
+    /// Tile containing the given point (degrees) at zoom level `z`
+    fn zxy_from_lng_lat(&self, z: u8, lng: f64, lat: f64) -> TileZXY;
+
+    /// Tile at zoom level `z - 1` containing the given tile
+    fn parent_zxy(&self, z: u8, x: u32, y: u32) -> TileZXY;
+
+    /// Position (below 4) of the tile among the children of its parent in the horizontal plane
+    fn child_slot(&self, _z: u8, x: u32, y: u32) -> u32 {
+        (x % 2) | ((y % 2) << 1)
+    }
+
+    /// Extent of the tile as (west, south, east, north) in degrees
+    fn bounds(&self, z: u8, x: u32, y: u32) -> (f64, f64, f64, f64);
+
+    /// Geometric error (metres) of the tile before any [`super::error_model`] adjustment
+    fn geometric_error(&self, z: u8, x: u32, y: u32) -> f64;
+
+    /// Shallowest zoom level (up to `max_zoom`) whose geometric error at the given point is not
+    /// larger than `error`
+    fn zoom_for_geometric_error(&self, lng: f64, lat: f64, error: f64, max_zoom: u8) -> u8 {
+        (0..=max_zoom)
+            .find(|&z| {
+                let (z, x, y) = self.zxy_from_lng_lat(z, lng, lat);
+                self.geometric_error(z, x, y) <= error
+            })
+            .unwrap_or(max_zoom)
+    }
+
+    /// Deepest zoom level (up to `max_zoom`) whose tiles are at least as wide and as tall as the
+    /// extent, so that the extent spans at most 2x2 tiles wherever the tile boundaries fall
+    fn zoom_for_extent(&self, west: f64, south: f64, east: f64, north: f64, max_zoom: u8) -> u8 {
+        let (lng, lat) = ((west + east) / 2.0, (south + north) / 2.0);
+        (1..=max_zoom)
+            .take_while(|&z| {
+                let (z, x, y) = self.zxy_from_lng_lat(z, lng, lat);
+                let (tile_west, tile_south, tile_east, tile_north) = self.bounds(z, x, y);
+                tile_east - tile_west >= east - west && tile_north - tile_south >= north - south
+            })
+            .last()
+            .unwrap_or(0)
+    }
+
+    /// Bounding volume extensions written next to the region of the tile, if the scheme has
+    /// a native bounding volume
+    fn bounding_volume_extensions(
+        &self,
+        _zxy: TileZXY,
+        _min_height: f64,
+        _max_height: f64,
+    ) -> Option<HashMap<String, serde_json::Value>> {
+        None
+    }
+
+    /// Extensions to declare in `extensionsUsed` of the tilesets
+    fn extensions_used(&self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+/// Tiling schemes selectable by name
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TilingSchemeKind {
+    /// [`Geographic`]
+    #[default]
+    Geographic,
+    /// [`super::web_mercator::WebMercator`]
+    WebMercator,
+    /// [`super::projected::ProjectedGrid`]
+    Projected,
+    /// [`super::s2::S2`]
+    S2,
+}
+
+impl FromStr for TilingSchemeKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "geographic" => Ok(Self::Geographic),
+            "web-mercator" => Ok(Self::WebMercator),
+            "projected" => Ok(Self::Projected),
+            "s2" => Ok(Self::S2),
+            _ => Err(format!(
+                "unknown tiling scheme {s:?} (expected geographic, web-mercator, projected or s2)"
+            )),
+        }
+    }
+}
+
+/// The geographic grid: 2x1 tiles at z=1, with tiles merged along longitude towards the poles
+/// (see [`x_step`])
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Geographic;
+
+impl TilingScheme for Geographic {
+    fn zxy_from_lng_lat(&self, z: u8, lng: f64, lat: f64) -> TileZXY {
+        zxy_from_lng_lat(z, lng, lat)
+    }
+
+    fn parent_zxy(&self, z: u8, x: u32, y: u32) -> TileZXY {
+        calc_parent_zxy(z, x, y)
+    }
+
+    fn bounds(&self, z: u8, x: u32, y: u32) -> (f64, f64, f64, f64) {
+        let (south, north) = y_slice_range(z, y);
+        let (west, east) = x_slice_range(z, x as i32, x_step(z, y));
+        (west, south, east, north)
+    }
+
+    fn geometric_error(&self, z: u8, _x: u32, y: u32) -> f64 {
+        geometric_error(z, y)
+    }
+
+    fn zoom_for_geometric_error(&self, _lng: f64, lat: f64, error: f64, max_zoom: u8) -> u8 {
+        zoom_for_geometric_error(lat, error, max_zoom)
+    }
+}
 
 // Get the position of the most significant bit
 fn msb(d: u32) -> u32 {
@@ -87,14 +233,80 @@ pub fn geometric_error(z: u8, y: u32) -> f64 {
         return 1e+100;
     }
     use std::f64::consts::PI;
-    const Q: f64 = 525957.5361033019;
     let zz = (1 << z) as f64;
-    let error1 = Q / (1 << (z - 2)) as f64;
+    let error1 = Z2_GEOMETRIC_ERROR / (1 << (z - 2)) as f64;
     let lat = (1.0 - (y as f64 + 0.5) * 4.0 / zz) * PI / 2.0;
     let error2 = lat.cos() * x_step(z, y) as f64 * error1;
     f64::max(error1, error2)
 }
 
+/// Shallowest zoom level (up to `max_zoom`) whose geometric error at `lat` is not larger than
+/// `error`
+pub fn zoom_for_geometric_error(lat: f64, error: f64, max_zoom: u8) -> u8 {
+    let lat = lat.clamp(-89.999_999, 89.999_999);
+    (2..=max_zoom)
+        .find(|&z| {
+            let (_, _, y) = zxy_from_lng_lat(z, 0.0, lat);
+            geometric_error(z, y) <= error
+        })
+        .unwrap_or(max_zoom)
+}
+
+pub fn height_step(z: u8) -> f64 {
+    OCTREE_ROOT_HEIGHT / (1u64 << z) as f64
+}
+
+pub fn h_from_height(z: u8, height: f64) -> u32 {
+    let h = ((height - OCTREE_MIN_HEIGHT) / height_step(z)).floor();
+    h.clamp(0.0, ((1u64 << z) - 1) as f64) as u32
+}
+
+pub fn h_slice_range(z: u8, h: u32) -> (f64, f64) {
+    let step = height_step(z);
+    let bottom = OCTREE_MIN_HEIGHT + step * h as f64;
+    (bottom, bottom + step)
+}
+
+/// How a tile is split into children at the next zoom level
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Subdivision {
+    /// Split on longitude and latitude only (up to 4 children)
+    #[default]
+    Quadtree,
+    /// Also split on height (up to 8 children)
+    Octree,
+}
+
+impl Subdivision {
+    pub fn zxyh_from_lng_lat_height(
+        &self,
+        scheme: &dyn TilingScheme,
+        z: u8,
+        lng: f64,
+        lat: f64,
+        height: f64,
+    ) -> TileZXYH {
+        let (z, x, y) = scheme.zxy_from_lng_lat(z, lng, lat);
+        match self {
+            Subdivision::Quadtree => (z, x, y, 0),
+            Subdivision::Octree => (z, x, y, h_from_height(z, height)),
+        }
+    }
+
+    pub fn content_path(&self, tile: TileZXYH) -> String {
+        let (z, x, y, h) = tile;
+        match self {
+            Subdivision::Quadtree => format!("{z}/{x}/{y}.glb"),
+            Subdivision::Octree => format!("{z}/{x}/{y}/{h}.glb"),
+        }
+    }
+}
+
+pub fn calc_parent_zxyh(scheme: &dyn TilingScheme, z: u8, x: u32, y: u32, h: u32) -> TileZXYH {
+    let (pz, px, py) = scheme.parent_zxy(z, x, y);
+    (pz, px, py, h / 2)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -256,4 +468,88 @@ mod tests {
         assert_eq!(calc_parent_zxy(4, 4, 2), (3, 2, 1));
         assert_eq!(calc_parent_zxy(4, 0, 2), (3, 0, 1));
     }
+
+    #[test]
+    fn test_zoom_for_geometric_error() {
+        // About 8 m at z=18 and 1 m at z=21 around Tokyo
+        assert_eq!(zoom_for_geometric_error(35.6, 8.1, 24), 18);
+        assert_eq!(zoom_for_geometric_error(35.6, 8.0, 24), 19);
+        assert_eq!(zoom_for_geometric_error(35.6, 1.1, 24), 21);
+        assert_eq!(zoom_for_geometric_error(35.6, 1e-6, 24), 24);
+        assert_eq!(zoom_for_geometric_error(35.6, 1e+9, 24), 2);
+    }
+
+    #[test]
+    fn test_zoom_for_extent() {
+        let (west, east) = x_slice_range(16, 58000, 1);
+        let (south, north) = y_slice_range(16, 10000);
+        let inset = 1e-9;
+        assert_eq!(
+            Geographic.zoom_for_extent(
+                west + inset,
+                south + inset,
+                east - inset,
+                north - inset,
+                24
+            ),
+            16
+        );
+        assert_eq!(Geographic.zoom_for_extent(139.0, 35.0, 139.0, 35.0, 18), 18);
+        // 20° wide: z=4 tiles are 22.5° wide
+        assert_eq!(Geographic.zoom_for_extent(-10.0, 35.0, 10.0, 36.0, 18), 4);
+    }
+
+    #[test]
+    fn test_zoom_for_extent_across_tile_boundaries() {
+        // 135°E is a tile boundary from z=3 down, which must not pull the zoom up to z=2
+        let (west, south, east, north) = (134.95, 34.6, 135.05, 34.7);
+        assert_ne!(
+            zxy_from_lng_lat(3, west, north),
+            zxy_from_lng_lat(3, east, south)
+        );
+        let z = Geographic.zoom_for_extent(west, south, east, north, 24);
+        assert_eq!(z, 11);
+        let (_, x0, y0) = zxy_from_lng_lat(z, west, north);
+        let (_, x1, y1) = zxy_from_lng_lat(z, east, south);
+        assert!(x1 - x0 <= 1 && y1 - y0 <= 1);
+
+        assert_eq!(Geographic.zoom_for_extent(139.0, 35.0, 139.0, 35.0, 18), 18);
+        assert_eq!(Geographic.zoom_for_extent(-100.0, 35.0, 100.0, 36.0, 18), 0);
+    }
+
+    #[test]
+    fn test_h_from_height() {
+        assert_eq!(h_from_height(0, 100.0), 0);
+        assert_eq!(h_from_height(18, OCTREE_MIN_HEIGHT), 0);
+        // Cells at z=18 are about 153 m tall
+        assert!((height_step(18) - 152.87).abs() < 0.01);
+        let ground = h_from_height(18, 10.0);
+        let roof = h_from_height(18, 300.0);
+        assert_eq!(roof - ground, 2);
+        // Parent cells contain their children
+        for z in 1..=20 {
+            let h = h_from_height(z, 634.0);
+            assert_eq!(h / 2, h_from_height(z - 1, 634.0));
+            let (bottom, top) = h_slice_range(z, h);
+            assert!(bottom <= 634.0 && 634.0 < top);
+        }
+    }
+
+    #[test]
+    fn test_subdivision_content_path() {
+        assert_eq!(
+            Subdivision::Quadtree.content_path((18, 10, 20, 0)),
+            "18/10/20.glb"
+        );
+        assert_eq!(
+            Subdivision::Octree.content_path((18, 10, 20, 65)),
+            "18/10/20/65.glb"
+        );
+        let (_, _, _, h) =
+            Subdivision::Quadtree.zxyh_from_lng_lat_height(&Geographic, 18, 139.7, 35.6, 500.0);
+        assert_eq!(h, 0);
+        let (_, _, _, h) =
+            Subdivision::Octree.zxyh_from_lng_lat_height(&Geographic, 18, 139.7, 35.6, 500.0);
+        assert_eq!(h, h_from_height(18, 500.0));
+    }
 }
@@ -1,11 +1,18 @@
 use cesiumtiles::tileset;
 use tinymvt::TileZXY;
 
-use super::scheme::{calc_parent_zxy, geometric_error};
+use super::error_model::GeometricErrorModel;
+use super::metadata::{classification_groups, metadata_schema, tile_metadata};
+use super::scheme::{Geographic, Subdivision, TilingScheme, calc_parent_zxyh};
+use super::volume::EcefVolume;
+
+/// Path of the root tileset JSON, relative to the output directory
+pub const ROOT_TILESET_PATH: &str = "tileset.json";
 
 #[derive(Debug)]
 pub struct TileContent {
     pub zxy: TileZXY,
+    pub h: u32,
     pub content_path: String,
     pub min_lng: f64,
     pub max_lng: f64,
@@ -14,12 +21,21 @@ pub struct TileContent {
     pub min_height: f64,
     pub max_height: f64,
     pub translation: [f64; 3],
+    /// Tighter volume than the region, if one was fitted to the points
+    pub bounding_volume: Option<EcefVolume>,
+    /// Average horizontal spacing of the points (metres), for metadata and data-driven
+    /// geometric errors
+    pub point_spacing: Option<f64>,
+    pub point_count: u64,
+    /// Index of the content group in the tileset `groups`, if contents are layered
+    pub group: Option<u32>,
 }
 
 impl Default for TileContent {
     fn default() -> Self {
         TileContent {
             zxy: (0, u32::MAX, u32::MAX),
+            h: 0,
             content_path: String::new(),
             min_lng: f64::MAX,
             max_lng: f64::MIN,
@@ -28,6 +44,10 @@ impl Default for TileContent {
             min_height: f64::MAX,
             max_height: f64::MIN,
             translation: [0.0; 3],
+            bounding_volume: None,
+            point_spacing: None,
+            point_count: 0,
+            group: None,
         }
     }
 }
@@ -35,27 +55,25 @@ impl Default for TileContent {
 #[derive(Debug)]
 pub struct Tile {
     zxy: TileZXY,
+    h: u32,
     contents: Vec<TileContent>,
-    child00: Option<Box<Tile>>,
-    child01: Option<Box<Tile>>,
-    child10: Option<Box<Tile>>,
-    child11: Option<Box<Tile>>,
+    /// Indexed by the [`TilingScheme::child_slot`] of the child, plus `(h % 2) << 2` in octrees
+    children: [Option<Box<Tile>>; 8],
     pub min_lng: f64,
     pub max_lng: f64,
     pub min_lat: f64,
     pub max_lat: f64,
     pub min_height: f64,
     pub max_height: f64,
+    pub bounding_volume: Option<EcefVolume>,
 }
 
 impl Default for Tile {
     fn default() -> Self {
         Tile {
             zxy: (0, u32::MAX, u32::MAX),
-            child00: None,
-            child01: None,
-            child10: None,
-            child11: None,
+            h: 0,
+            children: Default::default(),
             contents: vec![],
             min_lng: f64::MAX,
             max_lng: f64::MIN,
@@ -63,21 +81,15 @@ impl Default for Tile {
             max_lat: f64::MIN,
             min_height: f64::MAX,
             max_height: f64::MIN,
+            bounding_volume: None,
         }
     }
 }
 
 impl Tile {
     fn update_boundary(&mut self) {
-        for child in [
-            &mut self.child00,
-            &mut self.child01,
-            &mut self.child10,
-            &mut self.child11,
-        ]
-        .into_iter()
-        .flatten()
-        {
+        let mut volumes = Vec::new();
+        for child in self.children.iter_mut().flatten() {
             child.update_boundary();
             self.min_lng = self.min_lng.min(child.min_lng);
             self.max_lng = self.max_lng.max(child.max_lng);
@@ -85,6 +97,7 @@ impl Tile {
             self.max_lat = self.max_lat.max(child.max_lat);
             self.min_height = self.min_height.min(child.min_height);
             self.max_height = self.max_height.max(child.max_height);
+            volumes.extend(child.bounding_volume);
         }
         for content in &self.contents {
             self.min_lng = self.min_lng.min(content.min_lng);
@@ -93,23 +106,63 @@ impl Tile {
             self.max_lat = self.max_lat.max(content.max_lat);
             self.min_height = self.min_height.min(content.min_height);
             self.max_height = self.max_height.max(content.max_height);
+            volumes.extend(content.bounding_volume);
         }
+        self.bounding_volume = EcefVolume::merge(&volumes);
     }
 
-    fn into_tileset_tile(mut self, parent_ecef: [f64; 3]) -> tileset::Tile {
-        self.update_boundary();
+    fn ecef(&self, parent_ecef: [f64; 3]) -> [f64; 3] {
+        match self.contents.first() {
+            Some(content) => content.translation,
+            None => parent_ecef,
+        }
+    }
 
-        let self_ecef = if !self.contents.is_empty() {
-            self.contents[0].translation
-        } else {
-            parent_ecef
+    /// Tile bounding volume; the boundary must be up to date
+    fn tileset_bounding_volume(
+        &self,
+        self_ecef: [f64; 3],
+        scheme: &dyn TilingScheme,
+    ) -> tileset::BoundingVolume {
+        let mut bounding_volume = match self.bounding_volume {
+            Some(volume) => volume.to_tileset(self_ecef),
+            None => tileset::BoundingVolume::new_region([
+                self.min_lng.to_radians(),
+                self.min_lat.to_radians(),
+                self.max_lng.to_radians(),
+                self.max_lat.to_radians(),
+                self.min_height,
+                self.max_height,
+            ]),
         };
+        bounding_volume.extensions =
+            scheme.bounding_volume_extensions(self.zxy, self.min_height, self.max_height);
+        bounding_volume
+    }
+
+    /// `file` is the tileset JSON (relative to the output directory) the tile is written to
+    fn into_tileset_tile(
+        mut self,
+        parent_ecef: [f64; 3],
+        file: &str,
+        ctx: &mut TilesetContext,
+    ) -> tileset::Tile {
+        self.update_boundary();
+
+        let self_ecef = self.ecef(parent_ecef);
+        let bounding_volume = self.tileset_bounding_volume(self_ecef, ctx.scheme.as_ref());
 
         let children = {
-            let children: Vec<_> = [self.child00, self.child01, self.child10, self.child11]
+            let children: Vec<_> = self
+                .children
                 .into_iter()
                 .flatten()
-                .map(|child| child.into_tileset_tile(self_ecef))
+                .map(
+                    |child| match ctx.split.filter(|split| split.splits_at(child.zxy.0)) {
+                        Some(split) => child.into_external_tileset(self_ecef, file, split, ctx),
+                        None => child.into_tileset_tile(self_ecef, file, ctx),
+                    },
+                )
                 .collect();
             if children.is_empty() {
                 None
@@ -123,7 +176,8 @@ impl Tile {
                 0 => (None, None),
                 1 => {
                     let content = tileset::Content {
-                        uri: self.contents[0].content_path.clone(),
+                        uri: relative_uri(file, &self.contents[0].content_path),
+                        group: self.contents[0].group,
                         ..Default::default()
                     };
                     (Some(content), None)
@@ -131,9 +185,10 @@ impl Tile {
                 _ => {
                     let contents: Vec<_> = self
                         .contents
-                        .into_iter()
+                        .iter()
                         .map(|content| tileset::Content {
-                            uri: content.content_path,
+                            uri: relative_uri(file, &content.content_path),
+                            group: content.group,
                             ..Default::default()
                         })
                         .collect();
@@ -142,46 +197,165 @@ impl Tile {
             }
         };
 
-        let rel = [
-            self_ecef[0] - parent_ecef[0],
-            self_ecef[1] - parent_ecef[1],
-            self_ecef[2] - parent_ecef[2],
-        ];
-        let transform = [
-            1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, rel[0], rel[1], rel[2], 1.0,
-        ];
+        let point_spacing = self
+            .contents
+            .iter()
+            .filter_map(|content| content.point_spacing)
+            .reduce(f64::max);
+        let mut geometric_error =
+            ctx.error_model
+                .tile_error(ctx.scheme.as_ref(), self.zxy, point_spacing);
+        if ctx.error_model.data_driven {
+            // Measured errors are not monotonic by zoom, but refinement requires parents to
+            // have an error at least as large as their children
+            for child in children.iter().flatten() {
+                geometric_error = geometric_error.max(child.geometric_error);
+            }
+        }
+
+        let point_count = self
+            .contents
+            .iter()
+            .map(|content| content.point_count)
+            .sum();
+        let metadata = tile_metadata(point_count, self.min_height, self.max_height, point_spacing);
 
-        let (z, _, y) = self.zxy;
         tileset::Tile {
-            geometric_error: geometric_error(z, y),
-            refine: Some(tileset::Refine::Replace),
-            bounding_volume: tileset::BoundingVolume::new_region([
-                self.min_lng.to_radians(),
-                self.min_lat.to_radians(),
-                self.max_lng.to_radians(),
-                self.max_lat.to_radians(),
-                self.min_height,
-                self.max_height,
-            ]),
-            transform,
+            geometric_error,
+            refine: Some(ctx.refine),
+            bounding_volume,
+            transform: translation(parent_ecef, self_ecef),
             content,
             contents,
+            metadata: Some(metadata),
             children,
             ..Default::default()
         }
     }
 
-    fn new(zxy: TileZXY) -> Self {
+    /// Moves the subtree below this tile into its own tileset and returns the tile that
+    /// references it from `file`.
+    fn into_external_tileset(
+        mut self,
+        parent_ecef: [f64; 3],
+        file: &str,
+        split: ExternalSplit,
+        ctx: &mut TilesetContext,
+    ) -> tileset::Tile {
+        self.update_boundary();
+
+        let self_ecef = self.ecef(parent_ecef);
+        let path = split.tileset_path(self.zxy, self.h);
+        let bounding_volume = self.tileset_bounding_volume(self_ecef, ctx.scheme.as_ref());
+
+        // The referencing tile's transform applies to the external root
+        let root = self.into_tileset_tile(self_ecef, &path, ctx);
+        let tile = tileset::Tile {
+            geometric_error: root.geometric_error,
+            refine: Some(ctx.refine),
+            bounding_volume,
+            transform: translation(parent_ecef, self_ecef),
+            content: Some(tileset::Content {
+                uri: relative_uri(file, &path),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        ctx.externals.push(ExternalTileset {
+            path,
+            tileset: tileset::Tileset {
+                asset: tileset::Asset {
+                    version: "1.1".to_string(),
+                    ..Default::default()
+                },
+                schema: Some(metadata_schema()),
+                groups: ctx.grouped.then(classification_groups),
+                geometric_error: root.geometric_error,
+                root,
+                extensions_used: ctx.scheme.extensions_used(),
+                ..Default::default()
+            },
+        });
+        tile
+    }
+
+    fn new(zxy: TileZXY, h: u32) -> Self {
         Tile {
             zxy,
+            h,
             ..Default::default()
         }
     }
 }
 
+fn translation(parent_ecef: [f64; 3], self_ecef: [f64; 3]) -> [f64; 16] {
+    let rel = [
+        self_ecef[0] - parent_ecef[0],
+        self_ecef[1] - parent_ecef[1],
+        self_ecef[2] - parent_ecef[2],
+    ];
+    [
+        1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, rel[0], rel[1], rel[2], 1.0,
+    ]
+}
+
+/// URI of `path` as seen from the tileset JSON at `file`, both relative to the output directory
+fn relative_uri(file: &str, path: &str) -> String {
+    "../".repeat(file.matches('/').count()) + path
+}
+
+/// Zoom levels at which a [`TileTree`] is split into external tilesets: `min_zoom`, and then
+/// every `interval` levels below it if set.
+#[derive(Debug, Clone, Copy)]
+pub struct ExternalSplit {
+    pub subdivision: Subdivision,
+    pub min_zoom: u8,
+    pub interval: Option<u8>,
+}
+
+impl ExternalSplit {
+    pub fn splits_at(&self, z: u8) -> bool {
+        match self.interval.filter(|&interval| interval > 0) {
+            _ if z < self.min_zoom => false,
+            Some(interval) => (z - self.min_zoom).is_multiple_of(interval),
+            None => z == self.min_zoom,
+        }
+    }
+
+    /// Path of the external tileset rooted at the given tile
+    pub fn tileset_path(&self, zxy: TileZXY, h: u32) -> String {
+        let (z, x, y) = zxy;
+        match self.subdivision {
+            Subdivision::Quadtree => format!("tilesets/{z}/{x}/{y}.json"),
+            Subdivision::Octree => format!("tilesets/{z}/{x}/{y}/{h}.json"),
+        }
+    }
+}
+
+/// An external tileset split off a [`TileTree`] and its path relative to the output directory
+#[derive(Debug)]
+pub struct ExternalTileset {
+    pub path: String,
+    pub tileset: tileset::Tileset,
+}
+
+struct TilesetContext {
+    refine: tileset::Refine,
+    error_model: GeometricErrorModel,
+    scheme: Box<dyn TilingScheme>,
+    split: Option<ExternalSplit>,
+    externals: Vec<ExternalTileset>,
+    /// Whether contents are tagged with classification groups
+    grouped: bool,
+}
+
 #[derive(Debug)]
 pub struct TileTree {
     root: Tile,
+    refine: tileset::Refine,
+    error_model: GeometricErrorModel,
+    scheme: Box<dyn TilingScheme>,
+    grouped: bool,
 }
 
 impl Default for TileTree {
@@ -191,34 +365,289 @@ impl Default for TileTree {
                 zxy: (0, 0, 0),
                 ..Default::default()
             },
+            refine: tileset::Refine::Replace,
+            error_model: GeometricErrorModel::default(),
+            scheme: Box::new(Geographic),
+            grouped: false,
         }
     }
 }
 
 impl TileTree {
+    /// Creates an empty tree of `scheme` tiles using the given refinement and geometric errors.
+    /// With [`tileset::Refine::Add`], contents must not repeat the points of their ancestors.
+    pub fn new(
+        refine: tileset::Refine,
+        error_model: GeometricErrorModel,
+        scheme: Box<dyn TilingScheme>,
+    ) -> Self {
+        Self {
+            refine,
+            error_model,
+            scheme,
+            ..Default::default()
+        }
+    }
+
     pub fn into_tileset_root(self) -> tileset::Tile {
-        self.root.into_tileset_tile([0.0, 0.0, 0.0])
+        let mut ctx = TilesetContext {
+            refine: self.refine,
+            error_model: self.error_model,
+            scheme: self.scheme,
+            split: None,
+            externals: Vec::new(),
+            grouped: self.grouped,
+        };
+        self.root
+            .into_tileset_tile([0.0, 0.0, 0.0], ROOT_TILESET_PATH, &mut ctx)
+    }
+
+    /// Like [`Self::into_tileset_root`], but the subtrees below the zoom levels chosen by
+    /// `split` are written as external tilesets referenced from their parent tileset.
+    pub fn into_tileset_root_with_externals(
+        self,
+        split: ExternalSplit,
+    ) -> (tileset::Tile, Vec<ExternalTileset>) {
+        let mut ctx = TilesetContext {
+            refine: self.refine,
+            error_model: self.error_model,
+            scheme: self.scheme,
+            split: Some(split),
+            externals: Vec::new(),
+            grouped: self.grouped,
+        };
+        let root = self
+            .root
+            .into_tileset_tile([0.0, 0.0, 0.0], ROOT_TILESET_PATH, &mut ctx);
+        (root, ctx.externals)
     }
 
     pub fn add_content(&mut self, content: TileContent) {
-        let node = self.get_node(content.zxy);
+        self.grouped |= content.group.is_some();
+        let node = self.get_node(content.zxy, content.h);
         node.contents.push(content);
     }
 
-    fn get_node(&mut self, zxy: TileZXY) -> &mut Tile {
+    fn get_node(&mut self, zxy: TileZXY, h: u32) -> &mut Tile {
         let (zoom, x, y) = zxy;
         if zoom == 0 {
             &mut self.root
         } else {
-            let parent = self.get_node(calc_parent_zxy(zoom, x, y));
+            let (pz, px, py, ph) = calc_parent_zxyh(self.scheme.as_ref(), zoom, x, y, h);
+            let index = (self.scheme.child_slot(zoom, x, y) | ((h % 2) << 2)) as usize;
+            let parent = self.get_node((pz, px, py), ph);
+
+            parent.children[index].get_or_insert_with(|| Tile::new(zxy, h).into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tiling::s2::{S2, S2_EXTENSION};
+
+    fn content(zxy: TileZXY, h: u32, min_height: f64, max_height: f64) -> TileContent {
+        TileContent {
+            zxy,
+            h,
+            content_path: format!("{}/{}/{}/{}.glb", zxy.0, zxy.1, zxy.2, h),
+            min_lng: 139.0,
+            max_lng: 139.1,
+            min_lat: 35.0,
+            max_lat: 35.1,
+            min_height,
+            max_height,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn octree_children_are_split_by_height() {
+        let mut tree = TileTree::default();
+        tree.add_content(content((18, 10, 20), 64, 0.0, 100.0));
+        tree.add_content(content((18, 10, 20), 65, 100.0, 200.0));
+
+        let parent = tree.get_node((17, 5, 10), 32);
+        let children = parent.children.iter().flatten().collect::<Vec<_>>();
+        assert_eq!(children.len(), 2);
+
+        let root = tree.into_tileset_root();
+        let mut tile = &root;
+        for _ in 0..17 {
+            tile = &tile.children.as_ref().unwrap()[0];
+        }
+        assert_eq!(tile.children.as_ref().unwrap().len(), 2);
+        let region = tile.bounding_volume.region.unwrap();
+        assert_eq!((region[4], region[5]), (0.0, 200.0));
+    }
+
+    #[test]
+    fn tiles_carry_point_count_and_height_metadata() {
+        let mut tree = TileTree::default();
+        tree.add_content(TileContent {
+            point_count: 10,
+            point_spacing: Some(0.5),
+            ..content((1, 1, 0), 0, 5.0, 50.0)
+        });
+        tree.add_content(TileContent {
+            point_count: 30,
+            ..content((2, 2, 0), 0, 0.0, 100.0)
+        });
+
+        let root = tree.into_tileset_root();
+        let tile = &root.children.as_ref().unwrap()[0];
+        let metadata = tile.metadata.as_ref().unwrap();
+        assert_eq!(metadata.class_, "tile");
+        let properties = metadata.properties.as_ref().unwrap();
+        assert_eq!(properties["pointCount"], 10);
+        assert_eq!(properties["minimumHeight"], 0.0);
+        assert_eq!(properties["maximumHeight"], 100.0);
+        assert_eq!(properties["pointSpacing"], 0.5);
+    }
+
+    #[test]
+    fn additive_tree_sets_refine_on_every_tile() {
+        let mut tree = TileTree::new(
+            tileset::Refine::Add,
+            GeometricErrorModel::default(),
+            Box::new(Geographic),
+        );
+        tree.add_content(content((18, 10, 20), 0, 0.0, 100.0));
+        let mut tile = &tree.into_tileset_root();
+        loop {
+            assert!(matches!(tile.refine, Some(tileset::Refine::Add)));
+            match &tile.children {
+                Some(children) => tile = &children[0],
+                None => break,
+            }
+        }
+    }
+
+    #[test]
+    fn external_tilesets_are_split_at_min_zoom_and_interval() {
+        let mut tree = TileTree::default();
+        tree.add_content(content((15, 1, 2), 0, 0.0, 100.0));
+        tree.add_content(content((18, 8, 16), 0, 0.0, 100.0));
+        let split = ExternalSplit {
+            subdivision: Subdivision::Quadtree,
+            min_zoom: 15,
+            interval: Some(2),
+        };
+        let (root, externals) = tree.into_tileset_root_with_externals(split);
+
+        let paths = externals
+            .iter()
+            .map(|e| e.path.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(paths, ["tilesets/17/4/8.json", "tilesets/15/1/2.json"]);
+
+        let mut tile = &root;
+        for _ in 0..15 {
+            tile = &tile.children.as_ref().unwrap()[0];
+        }
+        assert_eq!(tile.content.as_ref().unwrap().uri, "tilesets/15/1/2.json");
+        assert!(tile.children.is_none());
+
+        // Content and nested tilesets are resolved relative to the external tileset
+        let shard = &externals[1].tileset.root;
+        assert_eq!(shard.content.as_ref().unwrap().uri, "../../../15/1/2/0.glb");
+        let child = &shard.children.as_ref().unwrap()[0];
+        assert_eq!(
+            child.children.as_ref().unwrap()[0]
+                .content
+                .as_ref()
+                .unwrap()
+                .uri,
+            "../../../tilesets/17/4/8.json"
+        );
+        let leaf = &externals[0].tileset.root.children.as_ref().unwrap()[0];
+        assert_eq!(leaf.content.as_ref().unwrap().uri, "../../../18/8/16/0.glb");
+    }
+
+    #[test]
+    fn grouped_contents_share_a_tile() {
+        let mut tree = TileTree::default();
+        for group in [0, 3] {
+            tree.add_content(TileContent {
+                content_path: format!("1/1/0_{group}.glb"),
+                group: Some(group),
+                ..content((1, 1, 0), 0, 0.0, 100.0)
+            });
+        }
+        let split = ExternalSplit {
+            subdivision: Subdivision::Quadtree,
+            min_zoom: 1,
+            interval: None,
+        };
+        let (_, externals) = tree.into_tileset_root_with_externals(split);
+
+        let tileset = &externals[0].tileset;
+        assert_eq!(tileset.groups.as_ref().unwrap().len(), 4);
+        let contents = tileset.root.contents.as_ref().unwrap();
+        let groups = contents.iter().map(|c| c.group).collect::<Vec<_>>();
+        assert_eq!(groups, [Some(0), Some(3)]);
+    }
+
+    #[test]
+    fn s2_faces_of_a_row_are_separate_children() {
+        let mut tree = TileTree::new(
+            tileset::Refine::Replace,
+            GeometricErrorModel::default(),
+            Box::new(S2),
+        );
+        // Faces 0 and 2 share the first z=1 tile, face 3 is in the second one
+        for zxy in [(2, 0, 0), (2, 2, 0), (2, 0, 1)] {
+            tree.add_content(content(zxy, 0, 0.0, 100.0));
+        }
+
+        let root = tree.into_tileset_root();
+        let rows = root.children.as_ref().unwrap();
+        assert_eq!(rows.len(), 2);
+        let uris = rows[0]
+            .children
+            .as_ref()
+            .unwrap()
+            .iter()
+            .map(|face| face.content.as_ref().unwrap().uri.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(uris, ["2/0/0/0.glb", "2/2/0/0.glb"]);
+        let tokens = rows[0]
+            .children
+            .as_ref()
+            .unwrap()
+            .iter()
+            .map(|face| {
+                face.bounding_volume.extensions.as_ref().unwrap()[S2_EXTENSION]["token"].clone()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(tokens, [S2::token(2, 0, 0), S2::token(2, 2, 0)]);
+    }
+
+    #[test]
+    fn data_driven_errors_never_grow_towards_the_leaves() {
+        let mut tree = TileTree::new(
+            tileset::Refine::Replace,
+            GeometricErrorModel {
+                data_driven: true,
+                ..Default::default()
+            },
+            Box::new(Geographic),
+        );
+        tree.add_content(TileContent {
+            point_spacing: Some(0.5),
+            ..content((17, 5, 10), 0, 0.0, 100.0)
+        });
+        tree.add_content(TileContent {
+            point_spacing: Some(2.0),
+            ..content((18, 10, 20), 0, 0.0, 100.0)
+        });
 
-            (match (x % 2, y % 2) {
-                (0, 0) => parent.child00.get_or_insert_with(|| Tile::new(zxy).into()),
-                (0, 1) => parent.child01.get_or_insert_with(|| Tile::new(zxy).into()),
-                (1, 0) => parent.child10.get_or_insert_with(|| Tile::new(zxy).into()),
-                (1, 1) => parent.child11.get_or_insert_with(|| Tile::new(zxy).into()),
-                _ => unreachable!(),
-            }) as _
+        let mut tile = &tree.into_tileset_root();
+        for _ in 0..17 {
+            tile = &tile.children.as_ref().unwrap()[0];
         }
+        assert_eq!(tile.geometric_error, 20.0);
+        assert_eq!(tile.children.as_ref().unwrap()[0].geometric_error, 20.0);
     }
 }
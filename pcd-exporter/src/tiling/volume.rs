@@ -0,0 +1,370 @@
+use std::str::FromStr;
+
+use cesiumtiles::tileset;
+
+/// WGS84 semi-major axis (metres)
+const WGS84_A: f64 = 6_378_137.0;
+
+/// WGS84 semi-minor axis (metres)
+const WGS84_B: f64 = 6_356_752.314_245_179;
+
+/// Boxes are never flatter than this (metres), so that viewers can always invert them
+const MIN_HALF_SIZE: f64 = 0.005;
+
+/// Shape of the tile bounding volumes written to the tileset
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BoundingVolumeKind {
+    /// `region` from the longitude, latitude and height extremes
+    #[default]
+    Region,
+    /// `box` aligned with the local east, north and up axes
+    Box,
+    /// `box` aligned with the principal axes of the points
+    PcaBox,
+    /// `sphere`
+    Sphere,
+}
+
+impl FromStr for BoundingVolumeKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "region" => Ok(Self::Region),
+            "box" => Ok(Self::Box),
+            "pca-box" => Ok(Self::PcaBox),
+            "sphere" => Ok(Self::Sphere),
+            _ => Err(format!(
+                "unknown bounding volume {s:?} (expected region, box, pca-box or sphere)"
+            )),
+        }
+    }
+}
+
+/// Bounding volume in ECEF (EPSG:4978) coordinates
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EcefVolume {
+    Box {
+        center: [f64; 3],
+        half_axes: [[f64; 3]; 3],
+    },
+    Sphere {
+        center: [f64; 3],
+        radius: f64,
+    },
+}
+
+impl EcefVolume {
+    /// Fits a volume of the given kind around ECEF points.
+    /// Returns `None` for [`BoundingVolumeKind::Region`] or when there are no points.
+    pub fn from_points(kind: BoundingVolumeKind, points: &[[f64; 3]]) -> Option<Self> {
+        if points.is_empty() {
+            return None;
+        }
+        match kind {
+            BoundingVolumeKind::Region => None,
+            BoundingVolumeKind::Box => Some(fit_box(points, enu_axes(mean(points)))),
+            BoundingVolumeKind::PcaBox => Some(fit_box(points, principal_axes(points))),
+            BoundingVolumeKind::Sphere => {
+                let Self::Box { center, .. } = fit_box(points, enu_axes(mean(points))) else {
+                    unreachable!()
+                };
+                let radius = points
+                    .iter()
+                    .map(|p| length(sub(*p, center)))
+                    .fold(0.0, f64::max);
+                Some(Self::Sphere { center, radius })
+            }
+        }
+    }
+
+    /// Encloses all `volumes`. Spheres are merged into a sphere, anything else is fitted
+    /// with an east/north/up box around the corners of the volumes.
+    pub fn merge(volumes: &[Self]) -> Option<Self> {
+        match volumes {
+            [] => None,
+            [volume] => Some(*volume),
+            _ if volumes.iter().all(|v| matches!(v, Self::Sphere { .. })) => {
+                volumes.iter().copied().reduce(merge_spheres)
+            }
+            _ => {
+                let corners = volumes.iter().flat_map(Self::corners).collect::<Vec<_>>();
+                Some(fit_box(&corners, enu_axes(mean(&corners))))
+            }
+        }
+    }
+
+    /// Converts to a tileset bounding volume in the frame of a tile whose (accumulated)
+    /// transform translates by `origin`
+    pub fn to_tileset(&self, origin: [f64; 3]) -> tileset::BoundingVolume {
+        match self {
+            Self::Box { center, half_axes } => {
+                let c = sub(*center, origin);
+                let [x, y, z] = half_axes;
+                tileset::BoundingVolume::new_box([
+                    c[0], c[1], c[2], x[0], x[1], x[2], y[0], y[1], y[2], z[0], z[1], z[2],
+                ])
+            }
+            Self::Sphere { center, radius } => {
+                let c = sub(*center, origin);
+                tileset::BoundingVolume::new_sphere([c[0], c[1], c[2], *radius])
+            }
+        }
+    }
+
+    fn corners(&self) -> [[f64; 3]; 8] {
+        let (center, half_axes) = match *self {
+            Self::Box { center, half_axes } => (center, half_axes),
+            Self::Sphere { center, radius } => (
+                center,
+                [[radius, 0.0, 0.0], [0.0, radius, 0.0], [0.0, 0.0, radius]],
+            ),
+        };
+        std::array::from_fn(|i| {
+            let mut corner = center;
+            for (axis, half_axis) in half_axes.iter().enumerate() {
+                let sign = if i >> axis & 1 == 0 { -1.0 } else { 1.0 };
+                corner = add(corner, scale(*half_axis, sign));
+            }
+            corner
+        })
+    }
+}
+
+fn fit_box(points: &[[f64; 3]], axes: [[f64; 3]; 3]) -> EcefVolume {
+    let mut center = [0.0; 3];
+    let mut half_axes = [[0.0; 3]; 3];
+    for (axis, half_axis) in axes.iter().zip(half_axes.iter_mut()) {
+        let (min, max) = points.iter().fold((f64::MAX, f64::MIN), |(min, max), p| {
+            let d = dot(*p, *axis);
+            (min.min(d), max.max(d))
+        });
+        center = add(center, scale(*axis, (min + max) * 0.5));
+        *half_axis = scale(*axis, ((max - min) * 0.5).max(MIN_HALF_SIZE));
+    }
+    EcefVolume::Box { center, half_axes }
+}
+
+fn merge_spheres(a: EcefVolume, b: EcefVolume) -> EcefVolume {
+    let (
+        EcefVolume::Sphere {
+            center: c1,
+            radius: r1,
+        },
+        EcefVolume::Sphere {
+            center: c2,
+            radius: r2,
+        },
+    ) = (a, b)
+    else {
+        unreachable!()
+    };
+    let d = length(sub(c2, c1));
+    if d + r2 <= r1 {
+        return a;
+    }
+    if d + r1 <= r2 {
+        return b;
+    }
+    let radius = (d + r1 + r2) * 0.5;
+    let center = add(c1, scale(sub(c2, c1), (radius - r1) / d));
+    EcefVolume::Sphere { center, radius }
+}
+
+/// East, north and up unit vectors at an ECEF position
+fn enu_axes(p: [f64; 3]) -> [[f64; 3]; 3] {
+    let up = normalize([
+        p[0] / (WGS84_A * WGS84_A),
+        p[1] / (WGS84_A * WGS84_A),
+        p[2] / (WGS84_B * WGS84_B),
+    ]);
+    let east = if p[0] == 0.0 && p[1] == 0.0 {
+        [1.0, 0.0, 0.0]
+    } else {
+        normalize([-p[1], p[0], 0.0])
+    };
+    let north = cross(up, east);
+    [east, north, up]
+}
+
+/// Eigenvectors of the covariance matrix of the points (cyclic Jacobi method)
+fn principal_axes(points: &[[f64; 3]]) -> [[f64; 3]; 3] {
+    let m = mean(points);
+    let mut a = [[0.0; 3]; 3];
+    for p in points {
+        let d = sub(*p, m);
+        for i in 0..3 {
+            for j in 0..3 {
+                a[i][j] += d[i] * d[j];
+            }
+        }
+    }
+
+    let mut v = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+    for _ in 0..32 {
+        let off_diagonal = a[0][1].abs() + a[0][2].abs() + a[1][2].abs();
+        if off_diagonal < 1e-12 * (a[0][0].abs() + a[1][1].abs() + a[2][2].abs()) {
+            break;
+        }
+        for (p, q) in [(0, 1), (0, 2), (1, 2)] {
+            if a[p][q] == 0.0 {
+                continue;
+            }
+            let theta = (a[q][q] - a[p][p]) / (2.0 * a[p][q]);
+            let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+            let c = 1.0 / (t * t + 1.0).sqrt();
+            let s = t * c;
+            for row in a.iter_mut() {
+                let (akp, akq) = (row[p], row[q]);
+                row[p] = c * akp - s * akq;
+                row[q] = s * akp + c * akq;
+            }
+            let (row_p, row_q) = (a[p], a[q]);
+            a[p] = std::array::from_fn(|k| c * row_p[k] - s * row_q[k]);
+            a[q] = std::array::from_fn(|k| s * row_p[k] + c * row_q[k]);
+            for row in v.iter_mut() {
+                let (vkp, vkq) = (row[p], row[q]);
+                row[p] = c * vkp - s * vkq;
+                row[q] = s * vkp + c * vkq;
+            }
+        }
+    }
+
+    // Columns of `v` are the eigenvectors
+    std::array::from_fn(|j| [v[0][j], v[1][j], v[2][j]])
+}
+
+fn mean(points: &[[f64; 3]]) -> [f64; 3] {
+    let sum = points.iter().fold([0.0; 3], |acc, p| add(acc, *p));
+    scale(sum, 1.0 / points.len() as f64)
+}
+
+fn add(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
+}
+
+fn sub(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn scale(a: [f64; 3], s: f64) -> [f64; 3] {
+    [a[0] * s, a[1] * s, a[2] * s]
+}
+
+fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn length(a: [f64; 3]) -> f64 {
+    dot(a, a).sqrt()
+}
+
+fn normalize(a: [f64; 3]) -> [f64; 3] {
+    scale(a, 1.0 / length(a))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Points on a slanted 100 m × 10 m × 1 m slab near Tokyo
+    fn slab() -> Vec<[f64; 3]> {
+        let origin = [-3_959_000.0, 3_350_000.0, 3_698_000.0];
+        let [east, north, up] = enu_axes(origin);
+        let along = normalize(add(east, up));
+        let mut points = Vec::new();
+        for i in 0..=10 {
+            for j in 0..=2 {
+                for k in 0..=1 {
+                    let p = add(origin, scale(along, i as f64 * 10.0));
+                    let p = add(p, scale(north, j as f64 * 5.0));
+                    points.push(add(p, scale(cross(along, north), k as f64)));
+                }
+            }
+        }
+        points
+    }
+
+    fn contains(volume: &EcefVolume, p: [f64; 3]) -> bool {
+        match *volume {
+            EcefVolume::Box { center, half_axes } => half_axes.iter().all(|h| {
+                let len = length(*h);
+                dot(sub(p, center), *h).abs() / len <= len + 1e-6
+            }),
+            EcefVolume::Sphere { center, radius } => length(sub(p, center)) <= radius + 1e-6,
+        }
+    }
+
+    fn box_volume(volume: &EcefVolume) -> f64 {
+        let EcefVolume::Box { half_axes, .. } = volume else {
+            panic!("not a box")
+        };
+        half_axes.iter().map(|h| length(*h) * 2.0).product()
+    }
+
+    #[test]
+    fn parse_kind() {
+        assert_eq!("pca-box".parse(), Ok(BoundingVolumeKind::PcaBox));
+        assert!("obb".parse::<BoundingVolumeKind>().is_err());
+    }
+
+    #[test]
+    fn fitted_volumes_contain_all_points() {
+        let points = slab();
+        for kind in [
+            BoundingVolumeKind::Box,
+            BoundingVolumeKind::PcaBox,
+            BoundingVolumeKind::Sphere,
+        ] {
+            let volume = EcefVolume::from_points(kind, &points).unwrap();
+            assert!(points.iter().all(|p| contains(&volume, *p)), "{kind:?}");
+        }
+        assert!(EcefVolume::from_points(BoundingVolumeKind::Region, &points).is_none());
+    }
+
+    #[test]
+    fn pca_box_is_tighter_for_slanted_content() {
+        let points = slab();
+        let enu = EcefVolume::from_points(BoundingVolumeKind::Box, &points).unwrap();
+        let pca = EcefVolume::from_points(BoundingVolumeKind::PcaBox, &points).unwrap();
+        // The slab is 100 × 10 × 1 m³ but tilted by 45° in the east/up plane
+        assert!((box_volume(&pca) - 1000.0).abs() < 1.0);
+        assert!(box_volume(&enu) > 10.0 * box_volume(&pca));
+    }
+
+    #[test]
+    fn merged_volumes_contain_children() {
+        let points = slab();
+        let (a, b) = points.split_at(points.len() / 2);
+        for kind in [BoundingVolumeKind::PcaBox, BoundingVolumeKind::Sphere] {
+            let children = [
+                EcefVolume::from_points(kind, a).unwrap(),
+                EcefVolume::from_points(kind, b).unwrap(),
+            ];
+            let parent = EcefVolume::merge(&children).unwrap();
+            assert!(points.iter().all(|p| contains(&parent, *p)), "{kind:?}");
+            assert_eq!(
+                matches!(parent, EcefVolume::Sphere { .. }),
+                kind == BoundingVolumeKind::Sphere
+            );
+        }
+    }
+
+    #[test]
+    fn tileset_volume_is_relative_to_tile_origin() {
+        let volume = EcefVolume::Sphere {
+            center: [10.0, 20.0, 30.0],
+            radius: 5.0,
+        };
+        let bv = volume.to_tileset([10.0, 10.0, 10.0]);
+        assert_eq!(bv.sphere, Some([0.0, 10.0, 20.0, 5.0]));
+    }
+}
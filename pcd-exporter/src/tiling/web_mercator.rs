@@ -0,0 +1,89 @@
+use std::f64::consts::PI;
+
+use tinymvt::TileZXY;
+
+use super::scheme::{EARTH_CIRCUMFERENCE, GEOMETRIC_ERROR_PER_METRE, TilingScheme};
+
+/// Latitude limit of the Web Mercator projection (degrees)
+const MAX_LATITUDE: f64 = 85.051_128_779_806_59;
+
+/// Spherical Web Mercator (EPSG:3857) XYZ tiles, the same grid as common raster tile servers
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WebMercator;
+
+impl TilingScheme for WebMercator {
+    fn zxy_from_lng_lat(&self, z: u8, lng: f64, lat: f64) -> TileZXY {
+        let n = (1u64 << z) as f64;
+        let lat = lat.clamp(-MAX_LATITUDE, MAX_LATITUDE).to_radians();
+        let x = (lng + 180.0) / 360.0 * n;
+        let y = (1.0 - lat.tan().asinh() / PI) / 2.0 * n;
+        let max_index = n - 1.0;
+        (
+            z,
+            x.floor().clamp(0.0, max_index) as u32,
+            y.floor().clamp(0.0, max_index) as u32,
+        )
+    }
+
+    fn parent_zxy(&self, z: u8, x: u32, y: u32) -> TileZXY {
+        assert!(z > 0, "z=0 has no parent");
+        (z - 1, x / 2, y / 2)
+    }
+
+    fn bounds(&self, z: u8, x: u32, y: u32) -> (f64, f64, f64, f64) {
+        let n = (1u64 << z) as f64;
+        let lng = |x: f64| x / n * 360.0 - 180.0;
+        let lat = |y: f64| (PI * (1.0 - 2.0 * y / n)).sinh().atan().to_degrees();
+        let (x, y) = (x as f64, y as f64);
+        (lng(x), lat(y + 1.0), lng(x + 1.0), lat(y))
+    }
+
+    fn geometric_error(&self, z: u8, x: u32, y: u32) -> f64 {
+        // Tiles are square on the ground; use the edge closest to the equator
+        let (_, south, _, north) = self.bounds(z, x, y);
+        let lat = if south > 0.0 {
+            south
+        } else if north < 0.0 {
+            north
+        } else {
+            0.0
+        };
+        let width = EARTH_CIRCUMFERENCE * lat.to_radians().cos() / (1u64 << z) as f64;
+        width * GEOMETRIC_ERROR_PER_METRE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_xyz_tile_numbers() {
+        // Tokyo Station on the standard XYZ raster tiles
+        assert_eq!(
+            WebMercator.zxy_from_lng_lat(16, 139.7671, 35.6812),
+            (16, 58211, 25806)
+        );
+        assert_eq!(
+            WebMercator.zxy_from_lng_lat(0, 139.7671, 35.6812),
+            (0, 0, 0)
+        );
+        assert_eq!(WebMercator.zxy_from_lng_lat(1, 180.0, -90.0), (1, 1, 1));
+    }
+
+    #[test]
+    fn bounds_contain_the_tile_points() {
+        let (z, x, y) = WebMercator.zxy_from_lng_lat(16, 139.7671, 35.6812);
+        let (west, south, east, north) = WebMercator.bounds(z, x, y);
+        assert!(west <= 139.7671 && 139.7671 < east);
+        assert!(south <= 35.6812 && 35.6812 < north);
+        assert_eq!(WebMercator.parent_zxy(z, x, y), (15, x / 2, y / 2));
+    }
+
+    #[test]
+    fn geometric_error_halves_per_level() {
+        let error = WebMercator.geometric_error(16, 58211, 25806);
+        let parent_error = WebMercator.geometric_error(15, 29105, 12903);
+        assert!((parent_error / error - 2.0).abs() < 1e-3);
+    }
+}
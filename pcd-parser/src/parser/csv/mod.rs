@@ -90,12 +90,15 @@ impl Parser for CsvParser {
                 let attributes = PointAttributes {
                     intensity: None,
                     return_number: None,
-                    classification: None,
+                    classification: parse_classification(&record, &field_mapping)?,
                     scanner_channel: None,
                     scan_angle: None,
                     user_data: None,
                     point_source_id: None,
                     gps_time: None,
+                    source_file: None,
+                    point_index: None,
+                    normal: None,
                 };
                 // let attributes = PointAttributes {
                 //     intensity: parse_optional_field(&record, &field_mapping, "intensity")
@@ -226,3 +229,23 @@ fn parse_optional_field(
         None
     }
 }
+
+/// Classification as its canonical integer string; exports that write every column as a float
+/// give `2.000`
+fn parse_classification(
+    record: &csv::StringRecord,
+    field_mapping: &HashMap<String, usize>,
+) -> Result<Option<String>, String> {
+    parse_optional_field(record, field_mapping, "classification")
+        .map(|value| {
+            let number = value
+                .trim()
+                .parse::<f64>()
+                .map_err(|e| format!("Failed to parse 'classification': {}", e))?;
+            if number.fract() != 0.0 || !(0.0..=255.0).contains(&number) {
+                return Err(format!("Invalid classification: {}", value));
+            }
+            Ok((number as u8).to_string())
+        })
+        .transpose()
+}
@@ -43,12 +43,15 @@ impl Parser for LasParser {
                 let attributes = PointAttributes {
                     intensity: Some(las_point.intensity),
                     return_number: Some(las_point.return_number),
-                    classification: None,
+                    classification: Some(u8::from(las_point.classification).to_string()),
                     scanner_channel: Some(las_point.user_data),
                     scan_angle: Some(las_point.scan_angle),
                     user_data: Some(las_point.user_data),
                     point_source_id: Some(las_point.point_source_id),
                     gps_time: Some(las_point.gps_time.unwrap_or(0.0)),
+                    source_file: None,
+                    point_index: None,
+                    normal: None,
                 };
 
                 let point = Point {
@@ -0,0 +1,813 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufReader},
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use tiff::{
+    TiffError,
+    decoder::{Decoder, DecodingResult},
+    tags::{PlanarConfiguration, Tag},
+};
+
+const GEO_KEY_RASTER_TYPE: u16 = 1025;
+const GEO_KEY_GEOGRAPHIC_TYPE: u16 = 2048;
+const GEO_KEY_PROJECTED_CS_TYPE: u16 = 3072;
+const RASTER_PIXEL_IS_POINT: u16 = 2;
+/// GeoKey value of a CRS that is not given by an EPSG code
+const USER_DEFINED: u16 = 32767;
+
+const PHOTOMETRIC_BLACK_IS_ZERO: u16 = 1;
+const PHOTOMETRIC_RGB: u16 = 2;
+const PHOTOMETRIC_YCBCR: u16 = 6;
+const EXTRA_SAMPLE_ASSOCIATED_ALPHA: u16 = 1;
+const EXTRA_SAMPLE_UNASSOCIATED_ALPHA: u16 = 2;
+
+/// Color model of the samples
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Photometric {
+    Gray,
+    Rgb,
+    /// Luma and chroma, as written by JPEG compression
+    YCbCr,
+}
+
+/// Samples of a strip or tile, interleaved by pixel unless the image is planar
+#[derive(Debug)]
+enum Samples {
+    U8(Vec<u8>),
+    U16(Vec<u16>),
+}
+
+impl Samples {
+    fn get(&self, index: usize) -> f64 {
+        match self {
+            Self::U8(samples) => f64::from(samples[index]),
+            Self::U16(samples) => f64::from(samples[index]),
+        }
+    }
+
+    fn byte_len(&self) -> usize {
+        match self {
+            Self::U8(samples) => samples.len(),
+            Self::U16(samples) => samples.len() * 2,
+        }
+    }
+}
+
+/// A decoded strip or tile
+#[derive(Debug)]
+struct Chunk {
+    samples: Samples,
+    /// Width in pixels, which is less than the tile width at the right edge of tiled images
+    width: usize,
+}
+
+/// Decoder of the file, with the strips or tiles it decoded last
+#[derive(Debug)]
+struct ChunkCache {
+    decoder: Decoder<BufReader<File>>,
+    /// Decoded chunks by index, with the time they were last used
+    chunks: HashMap<u32, (Arc<Chunk>, u64)>,
+    bytes: usize,
+    max_bytes: usize,
+    clock: u64,
+}
+
+impl ChunkCache {
+    /// Decodes a chunk unless it is cached, evicting the least recently used chunks beyond
+    /// `max_bytes`; the last chunk is kept in any case
+    fn get(&mut self, index: u32) -> io::Result<Arc<Chunk>> {
+        self.clock += 1;
+        if let Some((chunk, last_use)) = self.chunks.get_mut(&index) {
+            *last_use = self.clock;
+            return Ok(chunk.clone());
+        }
+
+        let samples = match self.decoder.read_chunk(index).map_err(tiff_error)? {
+            DecodingResult::U8(samples) => Samples::U8(samples),
+            DecodingResult::U16(samples) => Samples::U16(samples),
+            _ => {
+                return Err(unsupported(
+                    "samples other than 8 or 16-bit unsigned integers",
+                ));
+            }
+        };
+        let width = self.decoder.chunk_data_dimensions(index).0 as usize;
+        let chunk = Arc::new(Chunk { samples, width });
+
+        let bytes = chunk.samples.byte_len();
+        while self.bytes + bytes > self.max_bytes {
+            let Some(oldest) = self
+                .chunks
+                .iter()
+                .min_by_key(|(_, (_, last_use))| *last_use)
+                .map(|(&index, _)| index)
+            else {
+                break;
+            };
+            let (evicted, _) = self.chunks.remove(&oldest).unwrap();
+            self.bytes -= evicted.samples.byte_len();
+        }
+        self.bytes += bytes;
+        self.chunks.insert(index, (chunk.clone(), self.clock));
+        Ok(chunk)
+    }
+}
+
+/// An 8 or 16-bit gray, RGB or YCbCr GeoTIFF. Supports strips and tiles, chunky and planar
+/// layouts, and every compression of the `tiff` crate, JPEG included. Strips and tiles are
+/// decoded on demand and cached up to a number of bytes.
+#[derive(Debug)]
+pub struct GeoTiff {
+    pub width: usize,
+    pub height: usize,
+    /// EPSG code of the model CRS, if the GeoKeys give one
+    pub epsg: Option<u16>,
+    /// Sample value marking pixels without data, from the GDAL_NODATA tag
+    pub nodata: Option<f64>,
+    chunks: Mutex<ChunkCache>,
+    chunk_width: usize,
+    chunk_height: usize,
+    chunks_across: usize,
+    chunks_per_plane: usize,
+    samples_per_pixel: usize,
+    planar: bool,
+    /// Largest sample value, which maps to 1
+    max_value: f64,
+    photometric: Photometric,
+    alpha: Option<usize>,
+    /// Raster to model affine transform: x = t[0] + col * t[1] + row * t[2], and y likewise
+    /// with t[3..6]; columns and rows are counted from the corner of the first pixel
+    transform: [f64; 6],
+    inverse: [f64; 6],
+}
+
+impl GeoTiff {
+    /// Opens the first image of the file, keeping up to `cache_bytes` of decoded strips or
+    /// tiles in memory
+    pub fn open(path: &Path, cache_bytes: usize) -> io::Result<Self> {
+        let file = BufReader::new(File::open(path)?);
+        let mut decoder = Decoder::new(file).map_err(tiff_error)?;
+
+        let (width, height) = decoder.dimensions().map_err(tiff_error)?;
+        let (width, height) = (width as usize, height as usize);
+        let samples_per_pixel = decoder
+            .find_tag_unsigned::<usize>(Tag::SamplesPerPixel)
+            .map_err(tiff_error)?
+            .unwrap_or(1);
+        let bits = unsigned_tag(&mut decoder, Tag::BitsPerSample)?.unwrap_or_else(|| vec![1]);
+        let bits = bits[0];
+        if !matches!(bits, 8 | 16) {
+            return Err(unsupported(format!("{bits} bits per sample")));
+        }
+        if unsigned_tag(&mut decoder, Tag::SampleFormat)?.is_some_and(|f| f.iter().any(|&f| f != 1))
+        {
+            return Err(unsupported("samples other than unsigned integers"));
+        }
+        let photometric = match decoder
+            .get_tag_unsigned::<u16>(Tag::PhotometricInterpretation)
+            .map_err(tiff_error)?
+        {
+            PHOTOMETRIC_BLACK_IS_ZERO => Photometric::Gray,
+            PHOTOMETRIC_RGB => Photometric::Rgb,
+            PHOTOMETRIC_YCBCR => Photometric::YCbCr,
+            photometric => {
+                return Err(unsupported(format!(
+                    "photometric interpretation {photometric}"
+                )));
+            }
+        };
+        let color_samples = if photometric == Photometric::Gray {
+            1
+        } else {
+            3
+        };
+        if samples_per_pixel < color_samples {
+            return Err(invalid("fewer samples per pixel than color components"));
+        }
+        let alpha = unsigned_tag(&mut decoder, Tag::ExtraSamples)?
+            .unwrap_or_default()
+            .iter()
+            .position(|&extra| {
+                matches!(
+                    extra,
+                    EXTRA_SAMPLE_ASSOCIATED_ALPHA | EXTRA_SAMPLE_UNASSOCIATED_ALPHA
+                )
+            })
+            .map(|index| color_samples + index);
+        let planar = decoder
+            .find_tag_unsigned::<u16>(Tag::PlanarConfiguration)
+            .map_err(tiff_error)?
+            == Some(PlanarConfiguration::Planar.to_u16());
+
+        let (chunk_width, chunk_height) = decoder.chunk_dimensions();
+        let (chunk_width, chunk_height) = (chunk_width as usize, chunk_height as usize);
+        if chunk_width == 0 || chunk_height == 0 {
+            return Err(invalid("empty strips or tiles"));
+        }
+        let chunks_across = width.div_ceil(chunk_width);
+        let chunks_per_plane = chunks_across * height.div_ceil(chunk_height);
+
+        let geo_keys =
+            GeoKeys::new(unsigned_tag(&mut decoder, Tag::GeoKeyDirectoryTag)?.unwrap_or_default());
+        let epsg = geo_keys
+            .get(GEO_KEY_PROJECTED_CS_TYPE)
+            .or_else(|| geo_keys.get(GEO_KEY_GEOGRAPHIC_TYPE))
+            .filter(|&code| code != USER_DEFINED && code != 0);
+        let mut transform =
+            if let Some(matrix) = f64_tag(&mut decoder, Tag::ModelTransformationTag)? {
+                if matrix.len() < 8 {
+                    return Err(invalid("ModelTransformationTag has fewer than 8 values"));
+                }
+                [
+                    matrix[3], matrix[0], matrix[1], matrix[7], matrix[4], matrix[5],
+                ]
+            } else {
+                let tiepoint = f64_tag(&mut decoder, Tag::ModelTiepointTag)?
+                    .filter(|tiepoint| tiepoint.len() >= 6)
+                    .ok_or_else(|| invalid("no georeferencing (ModelTiepointTag)"))?;
+                let scale = f64_tag(&mut decoder, Tag::ModelPixelScaleTag)?
+                    .filter(|scale| scale.len() >= 2)
+                    .ok_or_else(|| invalid("no georeferencing (ModelPixelScaleTag)"))?;
+                let [col, row, _, x, y, _] = tiepoint[..6] else {
+                    unreachable!()
+                };
+                [
+                    x - col * scale[0],
+                    scale[0],
+                    0.0,
+                    y + row * scale[1],
+                    0.0,
+                    -scale[1],
+                ]
+            };
+        // With PixelIsPoint, the model coordinates refer to the pixel centres
+        if geo_keys.get(GEO_KEY_RASTER_TYPE) == Some(RASTER_PIXEL_IS_POINT) {
+            transform[0] -= 0.5 * (transform[1] + transform[2]);
+            transform[3] -= 0.5 * (transform[4] + transform[5]);
+        }
+        let inverse = invert(transform).ok_or_else(|| invalid("degenerate geotransform"))?;
+        let nodata = decoder
+            .find_tag(Tag::GdalNodata)
+            .map_err(tiff_error)?
+            .and_then(|value| value.into_string().ok())
+            .and_then(|value| value.trim_end_matches('\0').trim().parse::<f64>().ok());
+
+        Ok(Self {
+            width,
+            height,
+            epsg,
+            nodata,
+            chunks: Mutex::new(ChunkCache {
+                decoder,
+                chunks: HashMap::new(),
+                bytes: 0,
+                max_bytes: cache_bytes,
+                clock: 0,
+            }),
+            chunk_width,
+            chunk_height,
+            chunks_across,
+            chunks_per_plane,
+            samples_per_pixel,
+            planar,
+            max_value: f64::from((1u32 << bits) - 1),
+            photometric,
+            alpha,
+            transform,
+            inverse,
+        })
+    }
+
+    /// Model coordinates of the image corners, in the order (min x, min y, max x, max y)
+    pub fn bounds(&self) -> [f64; 4] {
+        let corners = [
+            (0.0, 0.0),
+            (self.width as f64, 0.0),
+            (0.0, self.height as f64),
+        ]
+        .into_iter()
+        .chain([(self.width as f64, self.height as f64)])
+        .map(|(col, row)| apply(self.transform, col, row));
+        corners.fold(
+            [f64::MAX, f64::MAX, f64::MIN, f64::MIN],
+            |[min_x, min_y, max_x, max_y], (x, y)| {
+                [min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y)]
+            },
+        )
+    }
+
+    /// Color at the model coordinates, interpolated bilinearly between the centres of the
+    /// surrounding pixels that have data, from 0 to 1; `None` outside the image or where no
+    /// surrounding pixel has data
+    pub fn sample(&self, x: f64, y: f64) -> io::Result<Option<[f64; 3]>> {
+        let (col, row) = apply(self.inverse, x, y);
+        if !(0.0..self.width as f64).contains(&col) || !(0.0..self.height as f64).contains(&row) {
+            return Ok(None);
+        }
+        // Position relative to the centre of the top left of the four pixels
+        let (u, v) = (col - 0.5, row - 0.5);
+        let (col0, row0) = (u.floor(), v.floor());
+        let (fu, fv) = (u - col0, v - row0);
+
+        let mut chunks = self.chunks.lock().unwrap();
+        let mut sum = [0.0; 3];
+        let mut total_weight = 0.0;
+        for (dc, dr, weight) in [
+            (0, 0, (1.0 - fu) * (1.0 - fv)),
+            (1, 0, fu * (1.0 - fv)),
+            (0, 1, (1.0 - fu) * fv),
+            (1, 1, fu * fv),
+        ] {
+            if weight <= 0.0 {
+                continue;
+            }
+            // Pixels beyond the edge repeat the edge pixels
+            let col = (col0 as i64 + dc).clamp(0, self.width as i64 - 1) as usize;
+            let row = (row0 as i64 + dr).clamp(0, self.height as i64 - 1) as usize;
+            if let Some(color) = self.pixel(&mut chunks, col, row)? {
+                for (sum, value) in sum.iter_mut().zip(color) {
+                    *sum += value * weight;
+                }
+                total_weight += weight;
+            }
+        }
+        Ok((total_weight > 0.0).then(|| sum.map(|value| value / total_weight)))
+    }
+
+    /// Color of the pixel from 0 to 1, or `None` if it has no data: every color sample equals
+    /// the nodata value, or the alpha is zero
+    fn pixel(
+        &self,
+        chunks: &mut ChunkCache,
+        col: usize,
+        row: usize,
+    ) -> io::Result<Option<[f64; 3]>> {
+        let mut sample = |index: usize| -> io::Result<f64> {
+            let (plane, sample, samples_per_pixel) = if self.planar {
+                (index, 0, 1)
+            } else {
+                (0, index, self.samples_per_pixel)
+            };
+            let chunk_index = plane * self.chunks_per_plane
+                + row / self.chunk_height * self.chunks_across
+                + col / self.chunk_width;
+            let chunk = chunks.get(chunk_index as u32)?;
+            let pixel = (row % self.chunk_height) * chunk.width + col % self.chunk_width;
+            Ok(chunk.samples.get(pixel * samples_per_pixel + sample))
+        };
+
+        if let Some(alpha) = self.alpha
+            && sample(alpha)? == 0.0
+        {
+            return Ok(None);
+        }
+        let color = match self.photometric {
+            Photometric::Gray => [sample(0)?; 3],
+            Photometric::Rgb => [sample(0)?, sample(1)?, sample(2)?],
+            Photometric::YCbCr => {
+                ycbcr_to_rgb([sample(0)?, sample(1)?, sample(2)?], self.max_value)
+            }
+        };
+        if let Some(nodata) = self.nodata
+            && color.iter().all(|&value| value == nodata)
+        {
+            return Ok(None);
+        }
+        Ok(Some(color.map(|value| value / self.max_value)))
+    }
+}
+
+/// Full range BT.601 conversion of JPEG, rounded to sample values
+fn ycbcr_to_rgb([y, cb, cr]: [f64; 3], max_value: f64) -> [f64; 3] {
+    let half = (max_value + 1.0) / 2.0;
+    let (cb, cr) = (cb - half, cr - half);
+    [
+        y + 1.402 * cr,
+        y - 0.344136 * cb - 0.714136 * cr,
+        y + 1.772 * cb,
+    ]
+    .map(|value| value.round().clamp(0.0, max_value))
+}
+
+fn apply(transform: [f64; 6], col: f64, row: f64) -> (f64, f64) {
+    (
+        transform[0] + col * transform[1] + row * transform[2],
+        transform[3] + col * transform[4] + row * transform[5],
+    )
+}
+
+fn invert(t: [f64; 6]) -> Option<[f64; 6]> {
+    let det = t[1] * t[5] - t[2] * t[4];
+    if det == 0.0 || !det.is_finite() {
+        return None;
+    }
+    let (a, b, d, e) = (t[5] / det, -t[2] / det, -t[4] / det, t[1] / det);
+    Some([-(a * t[0] + b * t[3]), a, b, -(d * t[0] + e * t[3]), d, e])
+}
+
+/// GeoKey values that are stored directly in the key directory
+struct GeoKeys(Vec<(u16, u16)>);
+
+impl GeoKeys {
+    fn new(directory: Vec<u16>) -> Self {
+        let keys = directory
+            .get(4..)
+            .unwrap_or_default()
+            .chunks_exact(4)
+            .filter(|key| key[1] == 0)
+            .map(|key| (key[0], key[3]))
+            .collect();
+        Self(keys)
+    }
+
+    fn get(&self, id: u16) -> Option<u16> {
+        self.0
+            .iter()
+            .find(|(key, _)| *key == id)
+            .map(|(_, value)| *value)
+    }
+}
+
+fn unsigned_tag(decoder: &mut Decoder<BufReader<File>>, tag: Tag) -> io::Result<Option<Vec<u16>>> {
+    decoder.find_tag_unsigned_vec(tag).map_err(tiff_error)
+}
+
+fn f64_tag(decoder: &mut Decoder<BufReader<File>>, tag: Tag) -> io::Result<Option<Vec<f64>>> {
+    decoder
+        .find_tag(tag)
+        .map_err(tiff_error)?
+        .map(|value| value.into_f64_vec().map_err(tiff_error))
+        .transpose()
+}
+
+fn tiff_error(error: TiffError) -> io::Error {
+    match error {
+        TiffError::IoError(error) => error,
+        TiffError::UnsupportedError(error) => unsupported(error),
+        error => invalid(error.to_string()),
+    }
+}
+
+fn invalid(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+fn unsupported(what: impl std::fmt::Display) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("unsupported GeoTIFF: {what}"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write as _;
+
+    use flate2::{Compression as Level, write::ZlibEncoder};
+    use jpeg_encoder::{ColorType, Encoder, SamplingFactor};
+    use tempfile::TempDir;
+    use tiff::{
+        encoder::{
+            Compression, DeflateLevel, DirectoryEncoder, Predictor, TiffEncoder, TiffKindStandard,
+            colortype,
+        },
+        tags::{CompressionMethod, ExtraSamples},
+    };
+
+    use super::*;
+
+    /// Writes the georeferencing of 1 m pixels in EPSG:6677 with the top left corner at
+    /// (100, 200) and nodata 0
+    fn write_geo_tags(directory: &mut DirectoryEncoder<'_, File, TiffKindStandard>) {
+        directory
+            .write_tag(Tag::ModelPixelScaleTag, &[1.0, 1.0, 0.0][..])
+            .unwrap();
+        directory
+            .write_tag(
+                Tag::ModelTiepointTag,
+                &[0.0, 0.0, 0.0, 100.0, 200.0, 0.0][..],
+            )
+            .unwrap();
+        directory
+            .write_tag(
+                Tag::GeoKeyDirectoryTag,
+                &[1, 1, 0, 1, GEO_KEY_PROJECTED_CS_TYPE, 0, 1, 6677][..],
+            )
+            .unwrap();
+        directory.write_tag(Tag::GdalNodata, "0").unwrap();
+    }
+
+    /// Test pattern with a different color for every pixel, none of them nodata
+    fn rgb(col: usize, row: usize) -> [u8; 3] {
+        [
+            (col * 13 + 1) as u8,
+            (row * 11 + 1) as u8,
+            (col + row + 50) as u8,
+        ]
+    }
+
+    /// Color from 0 to 1 at the centre of the pixel
+    fn pixel_centre(image: &GeoTiff, col: usize, row: usize) -> Option<[f64; 3]> {
+        image
+            .sample(100.5 + col as f64, 199.5 - row as f64)
+            .unwrap()
+    }
+
+    fn assert_pattern(image: &GeoTiff, tolerance: f64) {
+        for row in 0..image.height {
+            for col in 0..image.width {
+                let expected = rgb(col, row).map(|value| f64::from(value) / 255.0);
+                let actual = pixel_centre(image, col, row).unwrap();
+                for (actual, expected) in actual.iter().zip(expected) {
+                    assert!(
+                        (actual - expected).abs() <= tolerance,
+                        "pixel ({col}, {row}): {actual} != {expected}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn strips_decode_with_every_compression_and_predictor() {
+        let dir = TempDir::new().unwrap();
+        let (width, height) = (5, 7);
+        let data = (0..height)
+            .flat_map(|row| (0..width).flat_map(move |col| rgb(col, row)))
+            .collect::<Vec<_>>();
+        let compressions = [
+            Compression::Uncompressed,
+            Compression::Lzw,
+            Compression::Deflate(DeflateLevel::default()),
+            Compression::Packbits,
+        ];
+        for (i, compression) in compressions.into_iter().enumerate() {
+            for predictor in [Predictor::None, Predictor::Horizontal] {
+                let path = dir.path().join(format!("{i}_{}.tif", predictor.to_u16()));
+                let mut encoder = TiffEncoder::new(File::create(&path).unwrap())
+                    .unwrap()
+                    .with_compression(compression)
+                    .with_predictor(predictor);
+                let mut image = encoder
+                    .new_image::<colortype::RGB8>(width as u32, height as u32)
+                    .unwrap();
+                image.rows_per_strip(3).unwrap();
+                write_geo_tags(image.encoder());
+                image.write_data(&data).unwrap();
+
+                let image = GeoTiff::open(&path, 1 << 20).unwrap();
+                assert_eq!((image.width, image.height), (5, 7));
+                assert_eq!(image.epsg, Some(6677));
+                assert_eq!(image.nodata, Some(0.0));
+                assert_eq!(image.bounds(), [100.0, 193.0, 105.0, 200.0]);
+                assert_pattern(&image, 0.0);
+            }
+        }
+
+        // 16-bit gray with the predictor
+        let path = dir.path().join("gray16.tif");
+        let mut encoder = TiffEncoder::new(File::create(&path).unwrap())
+            .unwrap()
+            .with_compression(Compression::Lzw)
+            .with_predictor(Predictor::Horizontal);
+        let mut image = encoder.new_image::<colortype::Gray16>(3, 2).unwrap();
+        write_geo_tags(image.encoder());
+        image
+            .write_data(&[1000, 2000, 65535, 30000, 20000, 10000])
+            .unwrap();
+        let image = GeoTiff::open(&path, 1 << 20).unwrap();
+        assert_eq!(pixel_centre(&image, 2, 0), Some([1.0; 3]));
+        assert_eq!(pixel_centre(&image, 1, 1), Some([20000.0 / 65535.0; 3]));
+    }
+
+    /// Writes a 20 by 18 pixel RGB image as 16 by 16 tiles, whose right and bottom tiles are
+    /// padded
+    fn write_tiled(path: &Path, planar: bool, deflate: bool) {
+        let (width, height, tile): (usize, usize, usize) = (20, 18, 16);
+        let planes = if planar { 3 } else { 1 };
+        let mut encoder = TiffEncoder::new(File::create(path).unwrap()).unwrap();
+        let mut directory = encoder.image_directory().unwrap();
+        let (mut offsets, mut byte_counts) = (Vec::new(), Vec::new());
+        for plane in 0..planes {
+            for tile_row in 0..height.div_ceil(tile) {
+                for tile_col in 0..width.div_ceil(tile) {
+                    let mut data = Vec::new();
+                    for row in tile_row * tile..(tile_row + 1) * tile {
+                        for col in tile_col * tile..(tile_col + 1) * tile {
+                            let color = rgb(col, row);
+                            if planar {
+                                data.push(color[plane]);
+                            } else {
+                                data.extend(color);
+                            }
+                        }
+                    }
+                    if deflate {
+                        let mut encoder = ZlibEncoder::new(Vec::new(), Level::default());
+                        encoder.write_all(&data).unwrap();
+                        data = encoder.finish().unwrap();
+                    }
+                    offsets.push(directory.write_data(&data[..]).unwrap() as u32);
+                    byte_counts.push(data.len() as u32);
+                }
+            }
+        }
+        let compression = if deflate {
+            CompressionMethod::Deflate
+        } else {
+            CompressionMethod::None
+        };
+        let planar = if planar {
+            PlanarConfiguration::Planar
+        } else {
+            PlanarConfiguration::Chunky
+        };
+        directory.write_tag(Tag::ImageWidth, width as u32).unwrap();
+        directory
+            .write_tag(Tag::ImageLength, height as u32)
+            .unwrap();
+        directory
+            .write_tag(Tag::BitsPerSample, &[8u16; 3][..])
+            .unwrap();
+        directory.write_tag(Tag::Compression, compression).unwrap();
+        directory
+            .write_tag(Tag::PhotometricInterpretation, PHOTOMETRIC_RGB)
+            .unwrap();
+        directory.write_tag(Tag::SamplesPerPixel, 3u16).unwrap();
+        directory
+            .write_tag(Tag::PlanarConfiguration, planar)
+            .unwrap();
+        directory.write_tag(Tag::TileWidth, tile as u32).unwrap();
+        directory.write_tag(Tag::TileLength, tile as u32).unwrap();
+        directory.write_tag(Tag::TileOffsets, &offsets[..]).unwrap();
+        directory
+            .write_tag(Tag::TileByteCounts, &byte_counts[..])
+            .unwrap();
+        write_geo_tags(&mut directory);
+        directory.finish().unwrap();
+    }
+
+    #[test]
+    fn tiles_are_decoded_on_demand_within_the_cache() {
+        let dir = TempDir::new().unwrap();
+        for (planar, deflate) in [(false, false), (false, true), (true, false), (true, true)] {
+            let path = dir.path().join(format!("{planar}_{deflate}.tif"));
+            write_tiled(&path, planar, deflate);
+
+            // Room for one whole tile, or one plane of it
+            let tile_bytes = if planar { 16 * 16 } else { 16 * 16 * 3 };
+            let image = GeoTiff::open(&path, tile_bytes).unwrap();
+            assert_eq!((image.width, image.height), (20, 18));
+            assert!(image.chunks.lock().unwrap().chunks.is_empty());
+            assert_eq!(
+                pixel_centre(&image, 0, 0),
+                Some(rgb(0, 0).map(|v| f64::from(v) / 255.0))
+            );
+            assert_eq!(image.chunks.lock().unwrap().chunks.len(), 1);
+
+            assert_pattern(&image, 0.0);
+            let chunks = image.chunks.lock().unwrap();
+            assert!(chunks.bytes <= chunks.max_bytes);
+            // Tiles at the right and bottom edges are smaller, but not all of them fit
+            assert!(chunks.chunks.len() < if planar { 12 } else { 4 });
+        }
+    }
+
+    #[test]
+    fn jpeg_ycbcr_strips_are_converted_to_rgb() {
+        // Quadrants of flat colors, which JPEG keeps apart along the 8 by 8 block edges
+        let quadrants = [[200, 30, 40], [20, 180, 60], [40, 60, 220], [128, 128, 128]];
+        let (width, height) = (16, 16);
+        let mut pixels = Vec::new();
+        for row in 0..height {
+            for col in 0..width {
+                pixels.extend(quadrants[row / 8 * 2 + col / 8]);
+            }
+        }
+        let mut jpeg = Vec::new();
+        let mut encoder = Encoder::new(&mut jpeg, 100);
+        encoder.set_sampling_factor(SamplingFactor::F_2_2);
+        encoder
+            .encode(&pixels, width as u16, height as u16, ColorType::Rgb)
+            .unwrap();
+
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("jpeg.tif");
+        let mut encoder = TiffEncoder::new(File::create(&path).unwrap()).unwrap();
+        let mut directory = encoder.image_directory().unwrap();
+        let offset = directory.write_data(&jpeg[..]).unwrap();
+        directory.write_tag(Tag::ImageWidth, width as u32).unwrap();
+        directory
+            .write_tag(Tag::ImageLength, height as u32)
+            .unwrap();
+        directory
+            .write_tag(Tag::BitsPerSample, &[8u16; 3][..])
+            .unwrap();
+        directory
+            .write_tag(Tag::Compression, CompressionMethod::ModernJPEG)
+            .unwrap();
+        directory
+            .write_tag(Tag::PhotometricInterpretation, PHOTOMETRIC_YCBCR)
+            .unwrap();
+        directory.write_tag(Tag::SamplesPerPixel, 3u16).unwrap();
+        directory
+            .write_tag(Tag::RowsPerStrip, height as u32)
+            .unwrap();
+        directory
+            .write_tag(Tag::StripOffsets, offset as u32)
+            .unwrap();
+        directory
+            .write_tag(Tag::StripByteCounts, jpeg.len() as u32)
+            .unwrap();
+        directory
+            .write_tag(Tag::ChromaSubsampling, &[2u16, 2][..])
+            .unwrap();
+        write_geo_tags(&mut directory);
+        directory.finish().unwrap();
+
+        let image = GeoTiff::open(&path, 1 << 20).unwrap();
+        for (i, expected) in quadrants.into_iter().enumerate() {
+            let (col, row) = (i % 2 * 8 + 4, i / 2 * 8 + 4);
+            let actual = pixel_centre(&image, col, row).unwrap();
+            for (actual, expected) in actual.iter().zip(expected) {
+                assert!(
+                    (actual * 255.0 - f64::from(expected)).abs() <= 3.0,
+                    "quadrant {i}: {actual} != {expected}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn transparent_pixels_have_no_data() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("alpha.tif");
+        let mut encoder = TiffEncoder::new(File::create(&path).unwrap()).unwrap();
+        let mut image = encoder.new_image::<colortype::RGB8>(2, 1).unwrap();
+        image
+            .extra_samples(&[ExtraSamples::UnassociatedAlpha])
+            .unwrap();
+        write_geo_tags(image.encoder());
+        image.write_data(&[255, 0, 0, 255, 0, 255, 0, 0]).unwrap();
+
+        let image = GeoTiff::open(&path, 1 << 20).unwrap();
+        assert_eq!(pixel_centre(&image, 0, 0), Some([1.0, 0.0, 0.0]));
+        assert_eq!(pixel_centre(&image, 1, 0), None);
+        // Halfway between the pixels only the opaque one counts
+        assert_eq!(image.sample(101.0, 199.5).unwrap(), Some([1.0, 0.0, 0.0]));
+        // Outside the image
+        assert_eq!(image.sample(99.5, 199.5).unwrap(), None);
+    }
+
+    #[test]
+    fn pixel_is_point_and_transformation_matrices_are_georeferenced() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("point.tif");
+        let mut encoder = TiffEncoder::new(File::create(&path).unwrap()).unwrap();
+        let mut image = encoder.new_image::<colortype::Gray8>(4, 2).unwrap();
+        let directory = image.encoder();
+        // 2 m pixels, rotated a quarter turn, with the first pixel centred at (10, 20)
+        directory
+            .write_tag(
+                Tag::ModelTransformationTag,
+                &[
+                    0.0, 2.0, 0.0, 10.0, 2.0, 0.0, 0.0, 20.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
+                    1.0,
+                ][..],
+            )
+            .unwrap();
+        directory
+            .write_tag(
+                Tag::GeoKeyDirectoryTag,
+                &[
+                    1,
+                    1,
+                    0,
+                    2,
+                    GEO_KEY_RASTER_TYPE,
+                    0,
+                    1,
+                    RASTER_PIXEL_IS_POINT,
+                    GEO_KEY_GEOGRAPHIC_TYPE,
+                    0,
+                    1,
+                    USER_DEFINED,
+                ][..],
+            )
+            .unwrap();
+        image.write_data(&[0, 10, 20, 30, 40, 50, 60, 70]).unwrap();
+
+        let image = GeoTiff::open(&path, 1 << 20).unwrap();
+        assert_eq!(image.epsg, None);
+        assert_eq!(image.nodata, None);
+        assert_eq!(image.bounds(), [9.0, 19.0, 13.0, 27.0]);
+        // Columns run north and rows east
+        assert_eq!(image.sample(10.0, 20.0).unwrap(), Some([0.0; 3]));
+        assert_eq!(image.sample(10.0, 26.0).unwrap(), Some([30.0 / 255.0; 3]));
+        assert_eq!(image.sample(12.0, 22.0).unwrap(), Some([50.0 / 255.0; 3]));
+    }
+}
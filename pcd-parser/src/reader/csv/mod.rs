@@ -1,9 +1,15 @@
-use std::{collections::HashMap, error::Error, fs::File, io, path::PathBuf};
+use std::{
+    collections::HashMap,
+    error::Error,
+    fs::File,
+    io,
+    path::{Path, PathBuf},
+};
 
 use csv::ReaderBuilder;
-use pcd_core::pointcloud::point::{Color, Point, PointAttributes};
+use pcd_core::pointcloud::point::{AvailableAttributes, Color, Point, PointAttributes};
 
-use super::PointReader;
+use super::{InputExtent, PointReader};
 
 fn create_field_mapping(
     headers: &csv::StringRecord,
@@ -90,11 +96,33 @@ fn parse_optional_field(
     }
 }
 
+/// Classification as its canonical integer string; exports that write every column as a float
+/// give `2.000`
+fn parse_classification(
+    record: &csv::StringRecord,
+    field_mapping: &HashMap<String, usize>,
+) -> Result<Option<String>, String> {
+    parse_optional_field(record, field_mapping, "classification")
+        .map(|value| {
+            let number = value
+                .trim()
+                .parse::<f64>()
+                .map_err(|e| format!("Failed to parse 'classification': {}", e))?;
+            if number.fract() != 0.0 || !(0.0..=255.0).contains(&number) {
+                return Err(format!("Invalid classification: {}", value));
+            }
+            Ok((number as u8).to_string())
+        })
+        .transpose()
+}
+
 pub struct CsvPointReader {
     pub files: Vec<PathBuf>,
     pub current_file_index: usize,
     pub current_reader: Option<csv::Reader<File>>,
     pub field_mapping: HashMap<String, usize>,
+    /// Index of the next point within the current file
+    pub current_point_index: u64,
 }
 
 impl CsvPointReader {
@@ -104,12 +132,39 @@ impl CsvPointReader {
             current_file_index: 0,
             current_reader: None,
             field_mapping: HashMap::new(),
+            current_point_index: 0,
         };
 
         reader.open_next_file()?;
         Ok(reader)
     }
 
+    /// CSV has no header with bounds, so this scans the whole file
+    pub fn read_extent(path: &Path) -> io::Result<InputExtent> {
+        let mut reader = Self::new(vec![path.to_path_buf()])?;
+        let mut extent = InputExtent::default();
+        while let Some(point) = reader.next_point()? {
+            extent.add_point(&point);
+        }
+        Ok(extent)
+    }
+
+    /// Reads the attributes that have a column in the file; only the color and classification
+    /// are parsed from CSV
+    pub fn read_attributes(path: &Path) -> io::Result<AvailableAttributes> {
+        let reader = Self::new(vec![path.to_path_buf()])?;
+        let has = |name: &&str| reader.field_mapping.contains_key(*name);
+        Ok(AvailableAttributes {
+            color: ["r", "g", "b", "red", "green", "blue"].iter().any(has),
+            intensity: false,
+            classification: has(&"classification"),
+            return_number: false,
+            scan_angle: false,
+            point_source_id: false,
+            gps_time: false,
+        })
+    }
+
     fn open_next_file(&mut self) -> io::Result<()> {
         if self.current_file_index < self.files.len() {
             let path = &self.files[self.current_file_index];
@@ -124,6 +179,7 @@ impl CsvPointReader {
 
             self.field_mapping = mapping;
             self.current_reader = Some(rdr);
+            self.current_point_index = 0;
             Ok(())
         } else {
             self.current_reader = None;
@@ -163,12 +219,15 @@ impl CsvPointReader {
         let attributes = PointAttributes {
             intensity: None,
             return_number: None,
-            classification: None,
+            classification: parse_classification(record, &self.field_mapping)?,
             scanner_channel: None,
             scan_angle: None,
             user_data: None,
             point_source_id: None,
             gps_time: None,
+            source_file: None,
+            point_index: None,
+            normal: None,
         };
         // TODO: To be implemented in the future
         // let attributes = PointAttributes {
@@ -223,7 +282,12 @@ impl PointReader for CsvPointReader {
 
             match reader.read_record(&mut record) {
                 Ok(true) => match self.parse_point(&record) {
-                    Ok(p) => return Ok(Some(p)),
+                    Ok(mut p) => {
+                        p.attributes.source_file = Some(self.current_file_index as u32 - 1);
+                        p.attributes.point_index = Some(self.current_point_index);
+                        self.current_point_index += 1;
+                        return Ok(Some(p));
+                    }
                     Err(e) => {
                         eprintln!("Error parsing CSV point: {}", e);
                         return Err(io::Error::other(format!("{}", e)));
@@ -240,3 +304,40 @@ impl PointReader for CsvPointReader {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write as _;
+
+    use super::*;
+
+    fn read_classifications(content: &str) -> io::Result<Vec<Option<String>>> {
+        let mut file = tempfile::NamedTempFile::with_suffix(".csv").unwrap();
+        file.write_all(content.as_bytes()).unwrap();
+        let mut reader = CsvPointReader::new(vec![file.path().to_path_buf()])?;
+        let mut classifications = Vec::new();
+        while let Some(point) = reader.next_point()? {
+            classifications.push(point.attributes.classification);
+        }
+        Ok(classifications)
+    }
+
+    #[test]
+    fn classifications_are_read_as_integers() {
+        let classifications = read_classifications(
+            "\"X\",\"Y\",\"Z\",\"Classification\"\n\
+             1.0,2.0,3.0,1.000\n\
+             1.0,2.0,3.0,6\n\
+             1.0,2.0,3.0,\n",
+        )
+        .unwrap();
+        assert_eq!(
+            classifications,
+            [Some("1".to_string()), Some("6".to_string()), None]
+        );
+
+        assert!(read_classifications("x,y,z,classification\n1.0,2.0,3.0,2.5\n").is_err());
+        assert!(read_classifications("x,y,z,classification\n1.0,2.0,3.0,256\n").is_err());
+        assert!(read_classifications("x,y,z,classification\n1.0,2.0,3.0,ground\n").is_err());
+    }
+}
@@ -1,18 +1,32 @@
 use std::{
+    collections::HashMap,
     fs::File,
     io::{self, BufReader},
     path::PathBuf,
+    sync::{LazyLock, Mutex},
 };
 
 use las::Reader;
-use pcd_core::pointcloud::point::{Color, Point, PointAttributes};
+use pcd_core::pointcloud::point::{AvailableAttributes, Color, Point, PointAttributes};
 
-use super::PointReader;
+use super::{InputExtent, PointReader};
+
+/// Runs of consecutive points, spread evenly over a file, sampled to detect 8-bit colors
+const COLOR_DEPTH_RUNS: u64 = 100;
+const COLOR_DEPTH_RUN_LENGTH: u64 = 100;
+
+/// Color scale of each file, detected once and shared by every reader of the file
+static COLOR_SCALES: LazyLock<Mutex<HashMap<PathBuf, u16>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
 
 pub struct LasPointReader {
     pub files: Vec<PathBuf>,
     pub current_file_index: usize,
     pub current_reader: Option<Reader>,
+    /// Factor widening the colors of the current file to 16 bits
+    pub color_scale: u16,
+    /// Index of the next point within the current file
+    pub current_point_index: u64,
 }
 
 impl LasPointReader {
@@ -21,6 +35,63 @@ impl LasPointReader {
             files,
             current_file_index: 0,
             current_reader: None,
+            color_scale: 1,
+            current_point_index: 0,
+        })
+    }
+
+    /// Factor widening the colors of the file to 16 bits: 257 when the file stores 8-bit values
+    /// in its 16-bit color fields, otherwise 1
+    pub fn color_scale(path: &PathBuf) -> io::Result<u16> {
+        if let Some(&scale) = COLOR_SCALES.lock().unwrap().get(path) {
+            return Ok(scale);
+        }
+        // 255 * 257 = 65535
+        let scale = if Self::has_8bit_color(path)? { 257 } else { 1 };
+        COLOR_SCALES.lock().unwrap().insert(path.clone(), scale);
+        Ok(scale)
+    }
+
+    /// Whether the file stores 8-bit values in its 16-bit color fields, decided from runs of
+    /// points spread over the file: some sampled color must be non-zero and none may exceed
+    /// 255, so files that are dark or black where sampled keep their colors
+    pub fn has_8bit_color(path: &PathBuf) -> io::Result<bool> {
+        let mut reader = Reader::from_path(path).map_err(io::Error::other)?;
+        if !reader.header().point_format().has_color {
+            return Ok(false);
+        }
+        let count = reader.header().number_of_points();
+        let runs = COLOR_DEPTH_RUNS.min(count);
+        let mut max = 0;
+        for run in 0..runs {
+            reader.seek(count / runs * run).map_err(io::Error::other)?;
+            for point in reader
+                .read_points(COLOR_DEPTH_RUN_LENGTH)
+                .map_err(io::Error::other)?
+            {
+                let Some(c) = point.color else { continue };
+                max = max.max(c.red).max(c.green).max(c.blue);
+                if max > 255 {
+                    return Ok(false);
+                }
+            }
+        }
+        Ok(max > 0)
+    }
+
+    /// Reads the attributes stored by the point format of the file; the others are filled in
+    /// with defaults, such as white for the color
+    pub fn read_attributes(path: &PathBuf) -> io::Result<AvailableAttributes> {
+        let reader = Reader::from_path(path).map_err(io::Error::other)?;
+        let format = reader.header().point_format();
+        Ok(AvailableAttributes {
+            color: format.has_color,
+            intensity: true,
+            classification: true,
+            return_number: true,
+            scan_angle: true,
+            point_source_id: true,
+            gps_time: format.has_gps_time,
         })
     }
 
@@ -33,13 +104,54 @@ impl LasPointReader {
             .unwrap_or_else(|_| path.metadata().map(|m| m.len()).unwrap_or(0))
     }
 
+    /// Reads the extent from the LAS header without decoding any point
+    pub fn read_extent(path: &PathBuf) -> io::Result<InputExtent> {
+        let reader = Reader::from_path(path).map_err(io::Error::other)?;
+        let header = reader.header();
+        let bounds = header.bounds();
+        Ok(InputExtent {
+            min: [bounds.min.x, bounds.min.y, bounds.min.z],
+            max: [bounds.max.x, bounds.max.y, bounds.max.z],
+            point_count: header.number_of_points(),
+        })
+    }
+
+    /// Reads `runs` runs of `run_length` consecutive points spread evenly over the file, a
+    /// cheap spatial sample of flight strips, which store their points in acquisition order
+    pub fn sample_points(path: &PathBuf, runs: u64, run_length: u64) -> io::Result<Vec<Point>> {
+        let color_scale = Self::color_scale(path)?;
+        let mut reader = Reader::from_path(path).map_err(io::Error::other)?;
+        let count = reader.header().number_of_points();
+        // Small files are read in full
+        let (runs, run_length) = if runs * run_length >= count {
+            (1, count)
+        } else {
+            (runs, run_length)
+        };
+        let mut points = Vec::new();
+        for run in 0..runs {
+            reader.seek(count / runs * run).map_err(io::Error::other)?;
+            for las_point in reader.read_points(run_length).map_err(io::Error::other)? {
+                let mut point = Self::convert_las_point(las_point);
+                let Color { r, g, b } = &mut point.color;
+                for c in [r, g, b] {
+                    *c = c.saturating_mul(color_scale);
+                }
+                points.push(point);
+            }
+        }
+        Ok(points)
+    }
+
     pub fn open_next_file(&mut self) -> io::Result<()> {
         if self.current_file_index < self.files.len() {
             let path = &self.files[self.current_file_index];
+            self.color_scale = Self::color_scale(path)?;
             let file = File::open(path).unwrap();
             let reader = Reader::new(BufReader::new(file)).unwrap();
             self.current_reader = Some(reader);
             self.current_file_index += 1;
+            self.current_point_index = 0;
             Ok(())
         } else {
             self.current_reader = None;
@@ -64,12 +176,15 @@ impl LasPointReader {
         let attributes = PointAttributes {
             intensity: Some(las_point.intensity),
             return_number: Some(las_point.return_number),
-            classification: None,
+            classification: Some(u8::from(las_point.classification).to_string()),
             scanner_channel: Some(las_point.user_data),
             scan_angle: Some(las_point.scan_angle),
             user_data: Some(las_point.user_data),
             point_source_id: Some(las_point.point_source_id),
             gps_time: Some(las_point.gps_time.unwrap_or(0.0)),
+            source_file: None,
+            point_index: None,
+            normal: None,
         };
 
         Point {
@@ -95,7 +210,16 @@ impl PointReader for LasPointReader {
             let reader = self.current_reader.as_mut().unwrap();
             match reader.points().next() {
                 Some(Ok(las_point)) => {
-                    let p = Self::convert_las_point(las_point);
+                    let mut p = Self::convert_las_point(las_point);
+                    p.attributes.source_file = Some(self.current_file_index as u32 - 1);
+                    p.attributes.point_index = Some(self.current_point_index);
+                    self.current_point_index += 1;
+                    if self.color_scale != 1 {
+                        let Color { r, g, b } = &mut p.color;
+                        for c in [r, g, b] {
+                            *c = c.saturating_mul(self.color_scale);
+                        }
+                    }
                     return Ok(Some(p));
                 }
                 Some(Err(e)) => {
@@ -145,3 +269,48 @@ impl<R: PointReader> Iterator for PointIterator<R> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use las::{Builder, Writer, point::Format};
+    use tempfile::TempDir;
+
+    use super::*;
+
+    /// Writes a file of point format 2 with the given gray levels
+    fn write_las(dir: &TempDir, name: &str, levels: impl IntoIterator<Item = u16>) -> PathBuf {
+        let path = dir.path().join(name);
+        let mut builder = Builder::from((1, 2));
+        builder.point_format = Format::new(2).unwrap();
+        let mut writer = Writer::from_path(&path, builder.into_header().unwrap()).unwrap();
+        for level in levels {
+            let point = las::Point {
+                color: Some(las::Color::new(level, level, level)),
+                ..Default::default()
+            };
+            writer.write_point(point).unwrap();
+        }
+        writer.close().unwrap();
+        path
+    }
+
+    #[test]
+    fn color_depth_is_detected_from_points_spread_over_the_file() {
+        let dir = TempDir::new().unwrap();
+        // 16-bit colors that stay dark over the first 20,000 points
+        let dark_start = write_las(
+            &dir,
+            "dark_start.las",
+            (0..40_000).map(|i| if i < 20_000 { 100 } else { 40_000 }),
+        );
+        let eight_bit = write_las(&dir, "8bit.las", (0..40_000).map(|i| (i % 256) as u16));
+        let black = write_las(&dir, "black.las", (0..40_000).map(|_| 0));
+        assert_eq!(LasPointReader::color_scale(&dark_start).unwrap(), 1);
+        assert_eq!(LasPointReader::color_scale(&eight_bit).unwrap(), 257);
+        assert_eq!(LasPointReader::color_scale(&black).unwrap(), 1);
+
+        let points = LasPointReader::sample_points(&eight_bit, 4, 2).unwrap();
+        assert_eq!(points.len(), 8);
+        assert_eq!(points[1].color.r, 257);
+    }
+}
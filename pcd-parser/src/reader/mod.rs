@@ -7,3 +7,40 @@ use std::io;
 pub trait PointReader {
     fn next_point(&mut self) -> io::Result<Option<Point>>;
 }
+
+/// Bounding box and number of points of an input, in the input CRS
+#[derive(Debug, Clone, Copy)]
+pub struct InputExtent {
+    pub min: [f64; 3],
+    pub max: [f64; 3],
+    pub point_count: u64,
+}
+
+impl Default for InputExtent {
+    fn default() -> Self {
+        Self {
+            min: [f64::MAX; 3],
+            max: [f64::MIN; 3],
+            point_count: 0,
+        }
+    }
+}
+
+impl InputExtent {
+    pub fn add_point(&mut self, point: &Point) {
+        let coords = [point.x, point.y, point.z];
+        for (i, v) in coords.into_iter().enumerate() {
+            self.min[i] = self.min[i].min(v);
+            self.max[i] = self.max[i].max(v);
+        }
+        self.point_count += 1;
+    }
+
+    pub fn merge(&self, other: &Self) -> Self {
+        Self {
+            min: std::array::from_fn(|i| self.min[i].min(other.min[i])),
+            max: std::array::from_fn(|i| self.max[i].max(other.max[i])),
+            point_count: self.point_count + other.point_count,
+        }
+    }
+}